@@ -0,0 +1,441 @@
+// [[file:../extxyz.note::96c1f3e8][96c1f3e8]]
+use std::borrow::Borrow;
+use std::path::Path;
+
+use anyhow::*;
+use serde_json::{json, Map, Value};
+
+use crate::element::{atomic_number, element_symbol};
+use crate::{Column, Columns, Frame, Info};
+// 96c1f3e8 ends here
+
+// [[file:../extxyz.note::3e0a5b72][3e0a5b72]]
+// ASE stores time in years since 2000
+const T2000: f64 = 946681200.0;
+const YEAR: f64 = 31557600.0;
+
+// per-frame calculator results in ASE
+const FRAME_RESULTS: [&str; 3] = ["energy", "free_energy", "magmom"];
+// per-atom calculator results in ASE
+const ATOM_RESULTS: [&str; 4] = ["forces", "charges", "magmoms", "energies"];
+// per-atom arrays in ASE row, other than numbers and positions
+const ATOM_ARRAYS: [&str; 5] = ["tags", "masses", "momenta", "initial_magmoms", "initial_charges"];
+
+fn ase_time() -> f64 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    (now.as_secs_f64() - T2000) / YEAR
+}
+
+fn unique_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    // `RandomState` is seeded differently for each instance
+    let mut id = String::new();
+    for _ in 0..2 {
+        let x = std::collections::hash_map::RandomState::new().build_hasher().finish();
+        id.push_str(&format!("{x:016x}"));
+    }
+    id
+}
+
+// Encode as numpy array in ASE json format
+fn ndarray(shape: &[usize], dtype: &str, values: impl IntoIterator<Item = impl Into<Value>>) -> Value {
+    let values: Vec<Value> = values.into_iter().map(Into::into).collect();
+    json!({"__ndarray__": [shape, dtype, values]})
+}
+
+fn column_to_ndarray(column: &Column) -> Value {
+    use crate::PropertyValueType as T;
+
+    let n = column.len();
+    let w = column.width();
+    let shape: Vec<usize> = if w == 1 && !matches!(column, Column::Real3(_)) { vec![n] } else { vec![n, w] };
+    let values = (0..n).filter_map(|i| column.get_value(i)).flat_map(|v| match v {
+        Value::Array(row) => row,
+        v => vec![v],
+    });
+    match column.value_type() {
+        T::Real => ndarray(&shape, "float64", values),
+        T::Integer => ndarray(&shape, "int64", values),
+        T::Logical => ndarray(&shape, "bool", values),
+        // string arrays are stored as nested lists
+        T::String => match shape[..] {
+            [_] => Value::Array(values.collect()),
+            _ => Value::Array(values.collect::<Vec<_>>().chunks(w).map(|row| row.to_vec().into()).collect()),
+        },
+    }
+}
+
+// Convert stress tensor in 3x3 into Voigt form in xx, yy, zz, yz, xz, xy
+fn stress_to_voigt(value: &Value) -> Option<Vec<f64>> {
+    let values: Vec<f64> = value.as_array()?.iter().map(|x| x.as_f64()).collect::<Option<_>>()?;
+    match values.len() {
+        6 => Some(values),
+        9 => Some([0, 4, 8, 5, 2, 1].iter().map(|&i| values[i]).collect()),
+        _ => None,
+    }
+}
+
+fn stress_from_voigt(s: &[f64]) -> Vec<f64> {
+    vec![s[0], s[5], s[4], s[5], s[1], s[3], s[4], s[3], s[2]]
+}
+
+impl Frame {
+    /// Convert into a row record in ASE json database, with atomic
+    /// numbers, positions, cell and pbc. The entries in `info` and
+    /// `arrays` are mapped as follows:
+    ///
+    /// * calculator results like `energy`, `free_energy`, `magmom`,
+    ///   `stress`, `dipole`, `forces`, `charges`, `magmoms` and `energies`
+    ///   are stored as results of a single point calculator. `stress` is
+    ///   stored in Voigt form.
+    /// * `tags`, `masses`, `momenta`, `initial_magmoms` and
+    ///   `initial_charges` are stored as atoms arrays.
+    /// * other numbers, strings and logicals in `info` go to
+    ///   `key_value_pairs`, and the rest of values go to `data`. Other
+    ///   per-atom properties go to `data.arrays`.
+    pub fn to_ase_row(&self) -> Result<Map<String, Value>> {
+        let n = self.symbols.len();
+        ensure!(self.positions.len() == n, "inconsistent number of positions");
+        let numbers = self
+            .symbols
+            .iter()
+            .map(|s| atomic_number(s).with_context(|| format!("invalid element symbol: {s:?}")))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut row = Map::new();
+        let time = ase_time();
+        row.insert("ctime".into(), time.into());
+        row.insert("mtime".into(), time.into());
+        row.insert("user".into(), std::env::var("USER").unwrap_or_else(|_| "extxyz".into()).into());
+        row.insert("numbers".into(), ndarray(&[n], "int64", numbers));
+        row.insert("positions".into(), ndarray(&[n, 3], "float64", self.positions.iter().flatten().copied()));
+        let cell = self.lattice.unwrap_or_default();
+        row.insert("cell".into(), ndarray(&[3, 3], "float64", cell.iter().flatten().copied()));
+        let pbc = self.pbc.unwrap_or([self.lattice.is_some(); 3]);
+        row.insert("pbc".into(), ndarray(&[3], "bool", pbc));
+
+        let mut has_results = false;
+        let mut key_value_pairs = Map::new();
+        let mut data = Map::new();
+        for (k, v) in self.info.raw_map() {
+            match (k.as_str(), v) {
+                (k, Value::Number(_)) if FRAME_RESULTS.contains(&k) => {
+                    row.insert(k.into(), v.clone());
+                    has_results = true;
+                }
+                ("stress", v) if stress_to_voigt(v).is_some() => {
+                    row.insert(k.into(), ndarray(&[6], "float64", stress_to_voigt(v).unwrap()));
+                    has_results = true;
+                }
+                ("dipole", Value::Array(x)) if x.len() == 3 && x.iter().all(|x| x.is_number()) => {
+                    row.insert(k.into(), ndarray(&[3], "float64", x.iter().filter_map(|x| x.as_f64())));
+                    has_results = true;
+                }
+                (_, Value::Number(_) | Value::String(_) | Value::Bool(_)) => {
+                    key_value_pairs.insert(k.into(), v.clone());
+                }
+                _ => {
+                    data.insert(k.into(), v.clone());
+                }
+            }
+        }
+
+        let mut arrays = Map::new();
+        for (k, column) in &self.arrays {
+            ensure!(column.len() == n, "inconsistent number of atoms in property {k:?}");
+            let is_real = matches!(column.value_type(), crate::PropertyValueType::Real);
+            match k.as_str() {
+                "forces" | "momenta" if matches!(column, Column::Real3(_)) => {}
+                "charges" | "magmoms" | "energies" | "masses" | "initial_magmoms" | "initial_charges" if is_real && column.width() == 1 => {}
+                "tags" if matches!(column, Column::Integer(_)) => {}
+                _ => {
+                    arrays.insert(k.into(), column_to_ndarray(column));
+                    continue;
+                }
+            }
+            has_results |= ATOM_RESULTS.contains(&k.as_str());
+            row.insert(k.into(), column_to_ndarray(column));
+        }
+        if !arrays.is_empty() {
+            data.insert("arrays".into(), arrays.into());
+        }
+
+        if has_results {
+            row.insert("calculator".into(), "unknown".into());
+            row.insert("calculator_parameters".into(), json!({}));
+        }
+        if !key_value_pairs.is_empty() {
+            row.insert("key_value_pairs".into(), key_value_pairs.into());
+        }
+        if !data.is_empty() {
+            row.insert("data".into(), data.into());
+        }
+        row.insert("unique_id".into(), unique_id().into());
+        Ok(row)
+    }
+}
+// 3e0a5b72 ends here
+
+// [[file:../extxyz.note::c8d41e6b][c8d41e6b]]
+// Decode array in ASE json format into shape, dtype and flattened
+// values. Plain nested lists are also accepted.
+fn decode_array(value: &Value) -> Option<(Vec<usize>, Option<&str>, Vec<Value>)> {
+    match value {
+        Value::Object(obj) => {
+            // the cell object in recent ASE versions
+            if let Some(array) = obj.get("array") {
+                return decode_array(array);
+            }
+            let [shape, dtype, values] = obj.get("__ndarray__")?.as_array()?.as_slice() else {
+                return None;
+            };
+            let shape = shape.as_array()?.iter().map(|x| x.as_u64().map(|x| x as usize)).collect::<Option<Vec<_>>>()?;
+            let dtype = dtype.as_str();
+            let mut values = values.as_array()?.clone();
+            // coerce integers in float arrays
+            if dtype.is_some_and(|t| t.starts_with("float")) {
+                values = values.iter().map(|x| x.as_f64().map(Value::from)).collect::<Option<_>>()?;
+            }
+            Some((shape, dtype, values))
+        }
+        Value::Array(items) => {
+            let mut shape = vec![items.len()];
+            let mut values = vec![];
+            let mut inner_shape = None;
+            for item in items {
+                match item {
+                    Value::Array(_) => {
+                        let (s, _, v) = decode_array(item)?;
+                        if inner_shape.get_or_insert_with(|| s.clone()) != &s {
+                            return None;
+                        }
+                        values.extend(v);
+                    }
+                    v => values.push(v.clone()),
+                }
+            }
+            shape.extend(inner_shape.unwrap_or_default());
+            (values.len() == shape.iter().product::<usize>()).then_some((shape, None, values))
+        }
+        _ => None,
+    }
+}
+
+fn decode_reals(value: &Value, n: usize) -> Option<Vec<f64>> {
+    let (_, _, values) = decode_array(value)?;
+    let values: Vec<f64> = values.iter().map(|x| x.as_f64()).collect::<Option<_>>()?;
+    (values.len() == n).then_some(values)
+}
+
+// Decode per-atom array for `natoms` atoms into column
+fn decode_column(value: &Value, natoms: usize) -> Option<Column> {
+    let (shape, _, values) = decode_array(value)?;
+    if shape.first() != Some(&natoms) || natoms == 0 {
+        return None;
+    }
+    let width: usize = shape[1..].iter().product();
+    let rows: Vec<Value> = match shape.len() {
+        1 => values,
+        _ => values.chunks(width).map(|row| row.to_vec().into()).collect(),
+    };
+    Column::from_values(&rows)
+}
+
+impl Frame {
+    /// Construct `Frame` from a `row` record in ASE json database. This
+    /// is the reverse of `to_ase_row`, except that `stress` is stored in
+    /// 3x3 form as in extxyz. Constraints are ignored.
+    pub fn from_ase_row(row: &Value) -> Result<Self> {
+        let row = row.as_object().context("expect an object for ASE row")?;
+        let (_, _, numbers) = row.get("numbers").and_then(decode_array).context("invalid or missing numbers")?;
+        let symbols = numbers
+            .iter()
+            .map(|z| {
+                let z = z.as_i64().context("invalid atomic number")?;
+                element_symbol(z).map(String::from).with_context(|| format!("invalid atomic number: {z}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let natoms = symbols.len();
+
+        let positions = row.get("positions").and_then(|v| decode_reals(v, natoms * 3)).context("invalid or missing positions")?;
+        let positions = positions.chunks(3).map(|x| [x[0], x[1], x[2]]).collect();
+        let lattice = match row.get("cell") {
+            Some(cell) => {
+                let x = decode_reals(cell, 9).context("invalid cell")?;
+                let lattice = [[x[0], x[1], x[2]], [x[3], x[4], x[5]], [x[6], x[7], x[8]]];
+                x.iter().any(|&x| x != 0.0).then_some(lattice)
+            }
+            None => None,
+        };
+        let pbc = match row.get("pbc").and_then(decode_array) {
+            Some((_, _, pbc)) => {
+                let pbc: Vec<bool> = pbc.iter().map(|x| x.as_bool().or_else(|| x.as_i64().map(|x| x != 0))).collect::<Option<_>>().context("invalid pbc")?;
+                <[bool; 3]>::try_from(pbc).ok().context("invalid pbc")?
+            }
+            None => [false; 3],
+        };
+        let pbc = (lattice.is_some() || pbc.iter().any(|&x| x)).then_some(pbc);
+
+        let mut info = Info::default();
+        let map = info.raw_map_mut();
+        if let Some(Value::Object(kvp)) = row.get("key_value_pairs") {
+            map.extend(kvp.clone());
+        }
+        for k in FRAME_RESULTS {
+            if let Some(v @ Value::Number(_)) = row.get(k) {
+                map.insert(k.into(), v.clone());
+            }
+        }
+        if let Some(stress) = row.get("stress") {
+            let s = decode_reals(stress, 6).or_else(|| decode_reals(stress, 9)).context("invalid stress")?;
+            let s = if s.len() == 6 { stress_from_voigt(&s) } else { s };
+            map.insert("stress".into(), s.into());
+        }
+        if let Some(dipole) = row.get("dipole") {
+            map.insert("dipole".into(), decode_reals(dipole, 3).context("invalid dipole")?.into());
+        }
+
+        let mut arrays = Columns::new();
+        for k in ATOM_RESULTS.iter().chain(&ATOM_ARRAYS) {
+            if let Some(v) = row.get(*k).filter(|v| !v.is_null()) {
+                let column = decode_column(v, natoms).with_context(|| format!("invalid per-atom array {k:?}"))?;
+                arrays.insert(k.to_string(), column);
+            }
+        }
+        if let Some(Value::Object(data)) = row.get("data") {
+            for (k, v) in data {
+                match (k.as_str(), v) {
+                    ("arrays", Value::Object(data_arrays)) => {
+                        for (k, v) in data_arrays {
+                            let column = decode_column(v, natoms).with_context(|| format!("invalid per-atom array {k:?}"))?;
+                            arrays.insert(k.into(), column);
+                        }
+                    }
+                    _ => {
+                        map.insert(k.into(), v.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            natoms,
+            symbols,
+            positions,
+            lattice,
+            pbc,
+            info,
+            arrays,
+        })
+    }
+}
+// c8d41e6b ends here
+
+// [[file:../extxyz.note::5f27a9d3][5f27a9d3]]
+/// Format `frames` as a database in ASE json format, with ids counting
+/// from 1. See `Frame::to_ase_row` for the mapping of entries.
+pub fn format_ase_json<F: Borrow<Frame>>(frames: impl IntoIterator<Item = F>) -> Result<String> {
+    let mut db = Map::new();
+    let mut ids = vec![];
+    for (i, frame) in frames.into_iter().enumerate() {
+        let id = i + 1;
+        let row = frame.borrow().to_ase_row().with_context(|| format!("failed to convert frame {i}"))?;
+        db.insert(id.to_string(), row.into());
+        ids.push(id);
+    }
+    db.insert("nextid".into(), (ids.len() + 1).into());
+    db.insert("ids".into(), ids.into());
+    Ok(serde_json::to_string_pretty(&db)?)
+}
+
+/// Parse all rows in database `s` in ASE json format into frames, in the
+/// order of ids.
+pub fn parse_ase_json(s: &str) -> Result<Vec<Frame>> {
+    let db: Map<String, Value> = serde_json::from_str(s).context("invalid ASE json database")?;
+    let ids: Vec<String> = match db.get("ids").and_then(|ids| ids.as_array()) {
+        Some(ids) => ids.iter().map(|id| id.to_string()).collect(),
+        None => {
+            let mut ids: Vec<u64> = db.keys().filter_map(|k| k.parse().ok()).collect();
+            ids.sort_unstable();
+            ids.iter().map(|id| id.to_string()).collect()
+        }
+    };
+    ids.iter()
+        .map(|id| {
+            let row = db.get(id).with_context(|| format!("missing row {id}"))?;
+            Frame::from_ase_row(row).with_context(|| format!("failed to read row {id}"))
+        })
+        .collect()
+}
+
+/// Write `frames` into file in `path` as ASE json database.
+pub fn write_ase_json<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let path = path.as_ref();
+    let s = format_ase_json(frames)?;
+    std::fs::write(path, s).with_context(|| format!("Failed to write file {path:?}"))?;
+    Ok(())
+}
+
+/// Read all frames from ASE json database in `path`.
+pub fn read_ase_json(path: impl AsRef<Path>) -> Result<Vec<Frame>> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("Failed to read file {path:?}"))?;
+    parse_ase_json(&s)
+}
+// 5f27a9d3 ends here
+
+// [[file:../extxyz.note::e4b8c0d2][e4b8c0d2]]
+#[test]
+fn test_ase_json() -> Result<()> {
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:tags:I:1:label:S:1:m:R:2 energy=-1.5 pbc="T T F" stress="1.0 6.0 5.0 6.0 2.0 4.0 5.0 4.0 3.0" config_type=bulk virial=[1,2,3]
+Si 0.0 0.0 0.0 0.1 0.2 0.3 1 a 1.0 2.0
+O 1.36 1.36 1.36 -0.1 -0.2 -0.3 2 b 3.0 4.0
+"#;
+    let frame = Frame::parse(input)?;
+    let row = frame.to_ase_row()?;
+    assert_eq!(row["numbers"], json!({"__ndarray__": [[2], "int64", [14, 8]]}));
+    assert_eq!(row["pbc"], json!({"__ndarray__": [[3], "bool", [true, true, false]]}));
+    assert_eq!(row["stress"]["__ndarray__"][2], json!([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+    assert_eq!(row["forces"]["__ndarray__"][0], json!([2, 3]));
+    assert_eq!(row["tags"]["__ndarray__"][1], "int64");
+    assert_eq!(row["calculator"], "unknown");
+    assert_eq!(row["key_value_pairs"], json!({"config_type": "bulk"}));
+    assert_eq!(row["data"]["virial"], json!([1, 2, 3]));
+    assert_eq!(row["data"]["arrays"]["label"], json!(["a", "b"]));
+    assert_eq!(row["unique_id"].as_str().unwrap().len(), 32);
+
+    // round trip
+    let s = format_ase_json([&frame, &frame])?;
+    let frames = parse_ase_json(&s)?;
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1], frame);
+
+    // written by ASE
+    let s = r#"{"1": {
+ "ctime": 24.5, "mtime": 24.5, "user": "ase",
+ "numbers": {"__ndarray__": [[2], "int64", [1, 1]]},
+ "positions": {"__ndarray__": [[2, 3], "float64", [0, 0, 0, 0, 0, 0.74]]},
+ "cell": {"array": {"__ndarray__": [[3, 3], "float64", [0, 0, 0, 0, 0, 0, 0, 0, 0]]}, "__ase_objtype__": "cell"},
+ "pbc": {"__ndarray__": [[3], "bool", [false, false, false]]},
+ "energy": -6.7, "forces": [[0, 0, -0.1], [0, 0, 0.1]],
+ "key_value_pairs": {"relaxed": true}},
+ "ids": [1], "nextid": 2}"#;
+    let frames = parse_ase_json(s)?;
+    let frame = &frames[0];
+    assert_eq!(frame.symbols, ["H", "H"]);
+    assert_eq!(frame.positions[1], [0.0, 0.0, 0.74]);
+    assert_eq!(frame.lattice, None);
+    assert_eq!(frame.pbc, None);
+    assert_eq!(frame.info.get("energy"), Some(&json!(-6.7)));
+    assert_eq!(frame.info.get("relaxed"), Some(&json!(true)));
+    assert_eq!(frame.arrays["forces"], Column::Real3(vec![[0.0, 0.0, -0.1], [0.0, 0.0, 0.1]]));
+
+    // invalid element
+    let frame = Frame::parse("1\nenergy=1.0\nXx 0.0 0.0 0.0")?;
+    assert!(frame.to_ase_row().is_err());
+
+    Ok(())
+}
+// e4b8c0d2 ends here
@@ -0,0 +1,463 @@
+// [[file:../../extxyz.note::5d8f2a6c][5d8f2a6c]]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use extxyz::{Frame, ParseOptions, ReadError};
+// 5d8f2a6c ends here
+
+// [[file:../../extxyz.note::b93e0c47][b93e0c47]]
+/// Inspect and manipulate trajectory files in xyz/extxyz format.
+///
+/// Use `-` as the file path to read from stdin.
+#[derive(Parser, Debug)]
+#[command(name = "extxyz", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the number of frames.
+    Count {
+        /// The trajectory file
+        path: PathBuf,
+    },
+    /// Print the keys and `Properties` of frame N.
+    Info {
+        /// The trajectory file
+        path: PathBuf,
+        /// The index of frame, counting from 0
+        #[arg(short, long, default_value_t = 0)]
+        frame: usize,
+    },
+    /// Write selected frames, such as `0:100:10` for every 10th frame in
+    /// the first 100 frames.
+    Slice {
+        /// The trajectory file
+        path: PathBuf,
+        /// The frames to select in `start:stop:step` format, or a single
+        /// frame index
+        selection: Slice,
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write frames with comment line matching filter expression, such
+    /// as `energy < -100 and config_type == bulk`.
+    Filter {
+        /// The trajectory file
+        path: PathBuf,
+        /// The filter expression
+        expr: extxyz::Filter,
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Concatenate frames in trajectory files.
+    Cat {
+        /// The trajectory files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that all frames are valid in extxyz format.
+    Validate {
+        /// The trajectory file
+        path: PathBuf,
+        /// Accept recoverable issues as warnings
+        #[arg(long)]
+        lenient: bool,
+    },
+    /// Convert trajectory into another format.
+    Convert {
+        /// The trajectory file
+        path: PathBuf,
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The input format, guessed from file extension by default
+        #[arg(short = 'f', long = "from", value_enum)]
+        from: Option<Format>,
+        /// The output format
+        #[arg(short = 't', long = "to", value_enum, default_value_t = Format::Extxyz)]
+        to: Format,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// Extended xyz, with all frame data normalized
+    Extxyz,
+    /// Plain xyz, with element symbols and positions only
+    Xyz,
+    /// ASE json database
+    AseJson,
+    /// VASP POSCAR/CONTCAR, for a single frame
+    Poscar,
+    /// VASP XDATCAR trajectory
+    Xdatcar,
+    /// LAMMPS dump trajectory
+    LammpsDump,
+    /// LAMMPS data file, for a single frame
+    LammpsData,
+    /// NumPy arrays in `.npz` archive, for output only
+    Npz,
+    /// Parquet file with one row per frame, for output only
+    #[cfg(feature = "arrow")]
+    Parquet,
+    /// Parquet file with one row per atom, for output only
+    #[cfg(feature = "arrow")]
+    ParquetAtoms,
+}
+// b93e0c47 ends here
+
+// [[file:../../extxyz.note::e07a1b95][e07a1b95]]
+/// Frame selection in Python slice notation, without negative indices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slice {
+    start: usize,
+    stop: Option<usize>,
+    step: usize,
+}
+
+impl std::str::FromStr for Slice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |x: &str| x.trim().parse::<usize>().with_context(|| format!("invalid index in slice: {x:?}"));
+        let parts: Vec<_> = s.split(':').collect();
+        let slice = match parts[..] {
+            [i] => {
+                let i = parse(i)?;
+                let stop = i.checked_add(1).with_context(|| format!("index out of range: {i}"))?;
+                Self { start: i, stop: Some(stop), step: 1 }
+            }
+            [start, stop] | [start, stop, ""] => Self {
+                start: if start.is_empty() { 0 } else { parse(start)? },
+                stop: if stop.is_empty() { None } else { Some(parse(stop)?) },
+                step: 1,
+            },
+            [start, stop, step] => Self {
+                step: parse(step)?,
+                ..format!("{start}:{stop}").parse()?
+            },
+            _ => bail!("invalid slice: {s:?}"),
+        };
+        ensure!(slice.step > 0, "slice step can not be zero");
+        Ok(slice)
+    }
+}
+
+impl Slice {
+    /// Return the selected frame indices.
+    fn indices(self) -> Box<dyn Iterator<Item = usize>> {
+        match self.stop {
+            Some(stop) => Box::new((self.start..stop).step_by(self.step)),
+            None => Box::new((self.start..).step_by(self.step)),
+        }
+    }
+}
+
+#[test]
+fn test_slice() -> Result<()> {
+    let indices = |s: &str| -> Result<Vec<usize>> { Ok(s.parse::<Slice>()?.indices().take(5).collect()) };
+    assert_eq!(indices("3")?, [3]);
+    assert_eq!(indices("1:4")?, [1, 2, 3]);
+    assert_eq!(indices(":3")?, [0, 1, 2]);
+    assert_eq!(indices("2:")?, [2, 3, 4, 5, 6]);
+    assert_eq!(indices("::10")?, [0, 10, 20, 30, 40]);
+    assert_eq!(indices("0:100:30")?, [0, 30, 60, 90]);
+    assert!("0:10:0".parse::<Slice>().is_err());
+    assert!("-1".parse::<Slice>().is_err());
+    assert!("1:2:3:4".parse::<Slice>().is_err());
+    assert!(usize::MAX.to_string().parse::<Slice>().is_err());
+    Ok(())
+}
+// e07a1b95 ends here
+
+// [[file:../../extxyz.note::8c2f6d1e][8c2f6d1e]]
+type FrameResult = std::result::Result<String, ReadError>;
+
+// Read selected frames from file in `path`, or stdin for `-`
+fn read_frames(path: &Path, selection: impl Iterator<Item = usize> + 'static) -> Result<Box<dyn Iterator<Item = FrameResult>>> {
+    if path == Path::new("-") {
+        Ok(Box::new(extxyz::try_read_xyz_frames_from(std::io::stdin(), selection)))
+    } else {
+        let frames = extxyz::try_read_xyz_frames(path.to_path_buf(), selection).with_context(|| format!("failed to open {path:?}"))?;
+        Ok(Box::new(frames))
+    }
+}
+
+// Read selected frames from file in `path` selectively without parsing
+// the skipped frames, or from stdin for `-`
+fn select_frames(path: &Path, selection: impl Iterator<Item = usize> + 'static) -> Result<Box<dyn Iterator<Item = FrameResult>>> {
+    if path == Path::new("-") {
+        read_frames(path, selection)
+    } else {
+        let frames = extxyz::try_read_xyz_frames_two_pass(path.to_path_buf(), selection).with_context(|| format!("failed to open {path:?}"))?;
+        Ok(Box::new(frames))
+    }
+}
+
+fn create_writer(output: Option<&Path>) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = match output {
+        Some(path) => {
+            let f = std::fs::File::create(path).with_context(|| format!("failed to create file {path:?}"))?;
+            Box::new(std::io::BufWriter::new(f))
+        }
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    Ok(writer)
+}
+
+fn write_frame(writer: &mut dyn Write, frame: &str) -> Result<()> {
+    writer.write_all(frame.as_bytes())?;
+    if !frame.ends_with('\n') {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn count(path: &Path) -> Result<()> {
+    let mut n = 0;
+    for frame in read_frames(path, 0..)? {
+        frame?;
+        n += 1;
+    }
+    println!("{n}");
+    Ok(())
+}
+
+fn info(path: &Path, i: usize) -> Result<()> {
+    let frame = select_frames(path, std::iter::once(i))?.next().with_context(|| format!("no frame {i} in {path:?}"))??;
+    let atoms = extxyz::RawAtoms::parse_from(&frame)?;
+    println!("frame: {i}");
+    println!("natoms: {}", atoms.natoms);
+
+    let info: extxyz::Info = match atoms.comment.parse() {
+        std::result::Result::Ok(info) if atoms.comment.contains('=') => info,
+        _ => {
+            println!("comment: {}", atoms.comment);
+            return Ok(());
+        }
+    };
+    println!("Properties:");
+    for field in info.schema()?.fields() {
+        println!("  {:<16} {}:{}  columns {:?}", field.name, field.r#type.to_char(), field.width, field.range());
+    }
+    println!("keys:");
+    for (k, v) in info.raw_map() {
+        if k != "Properties" {
+            println!("  {k:<16} {v}");
+        }
+    }
+    Ok(())
+}
+
+fn slice(path: &Path, selection: Slice, output: Option<&Path>) -> Result<()> {
+    let mut writer = create_writer(output)?;
+    for frame in select_frames(path, selection.indices())? {
+        write_frame(&mut writer, &frame?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn filter(path: &Path, filter: &extxyz::Filter, output: Option<&Path>) -> Result<()> {
+    let mut writer = create_writer(output)?;
+    for frame in read_frames(path, 0..)? {
+        let frame = frame?;
+        if filter.matches_text(&frame) {
+            write_frame(&mut writer, &frame)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn cat(paths: &[PathBuf], output: Option<&Path>) -> Result<()> {
+    let mut writer = create_writer(output)?;
+    for path in paths {
+        for frame in read_frames(path, 0..)? {
+            write_frame(&mut writer, &frame?)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn validate(path: &Path, lenient: bool) -> Result<()> {
+    use std::result::Result::Ok;
+
+    let options = if lenient { ParseOptions::lenient() } else { ParseOptions::strict() };
+    let mut nframes = 0;
+    let mut invalid = 0;
+    for (i, frame) in read_frames(path, 0..)?.enumerate() {
+        nframes += 1;
+        let frame = frame?;
+        match Frame::parse_with(&frame, &options) {
+            Ok((_, warnings)) => {
+                for w in warnings {
+                    eprintln!("warning: frame {i}, {w}");
+                }
+            }
+            Err(e) => {
+                invalid += 1;
+                match e.downcast::<extxyz::ParseError>() {
+                    Ok(e) => eprintln!("error: {}\n", e.with_frame(i)),
+                    Err(e) => eprintln!("error: frame {i}: {e:#}\n"),
+                }
+            }
+        }
+    }
+    ensure!(invalid == 0, "{invalid} of {nframes} frames are invalid");
+    println!("{nframes} frames are valid");
+    Ok(())
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    let mut s = String::new();
+    if path == Path::new("-") {
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut s)?;
+    } else {
+        s = std::fs::read_to_string(path).with_context(|| format!("failed to read file {path:?}"))?;
+    }
+    Ok(s)
+}
+
+// Guess file format from file name or extension in `path`
+fn guess_format(path: &Path) -> Format {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+    if name.starts_with("POSCAR") || name.starts_with("CONTCAR") {
+        return Format::Poscar;
+    }
+    if name.starts_with("XDATCAR") {
+        return Format::Xdatcar;
+    }
+    if name.starts_with("dump.") {
+        return Format::LammpsDump;
+    }
+    if name.starts_with("data.") {
+        return Format::LammpsData;
+    }
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("lammpstrj" | "dump") => Format::LammpsDump,
+        Some("data" | "lmp") => Format::LammpsData,
+        Some("vasp") => Format::Poscar,
+        Some("json") => Format::AseJson,
+        Some("npz") => Format::Npz,
+        #[cfg(feature = "arrow")]
+        Some("parquet") => Format::Parquet,
+        _ => Format::Extxyz,
+    }
+}
+
+// Read all frames in `path` in `format`
+fn read_input(path: &Path, format: Format) -> Result<Box<dyn Iterator<Item = Result<Frame>>>> {
+    let frames: Box<dyn Iterator<Item = Result<Frame>>> = match format {
+        Format::Extxyz | Format::Xyz => {
+            let frames = read_frames(path, 0..)?.enumerate();
+            Box::new(frames.map(|(i, frame)| Frame::parse(&frame?).with_context(|| format!("failed to parse frame {i}"))))
+        }
+        Format::AseJson => Box::new(extxyz::parse_ase_json(&read_to_string(path)?)?.into_iter().map(Ok)),
+        Format::Poscar => Box::new(std::iter::once(Frame::from_poscar(&read_to_string(path)?))),
+        Format::Xdatcar => Box::new(extxyz::parse_xdatcar(&read_to_string(path)?)?.into_iter().map(Ok)),
+        Format::LammpsDump if path == Path::new("-") => Box::new(extxyz::read_lammps_dump_from(std::io::stdin(), 0..)),
+        Format::LammpsDump => Box::new(extxyz::read_lammps_dump(path.to_path_buf(), 0..).with_context(|| format!("failed to open {path:?}"))?),
+        Format::LammpsData => Box::new(std::iter::once(Frame::from_lammps_data(&read_to_string(path)?))),
+        Format::Npz => bail!("npz format is for output only"),
+        #[cfg(feature = "arrow")]
+        Format::Parquet | Format::ParquetAtoms => bail!("parquet format is for output only"),
+    };
+    Ok(frames)
+}
+
+fn convert(path: &Path, output: Option<&Path>, from: Option<Format>, to: Format) -> Result<()> {
+    let from = from.unwrap_or_else(|| guess_format(path));
+    let frames = read_input(path, from)?;
+    #[cfg(feature = "arrow")]
+    if let Format::Parquet | Format::ParquetAtoms = to {
+        let output = output.context("parquet format requires an output file")?;
+        let layout = if let Format::Parquet = to { extxyz::TableLayout::Frames } else { extxyz::TableLayout::Atoms };
+        // stop at the first error, and report it after writing
+        let mut error = None;
+        let frames = frames.map_while(|frame| frame.map_err(|e| error = Some(e)).ok());
+        extxyz::write_parquet(output, frames, layout)?;
+        return error.map_or(Ok(()), Err);
+    }
+    let mut writer = create_writer(output)?;
+    match to {
+        Format::Extxyz | Format::Xyz => {
+            for frame in frames {
+                let frame = frame?;
+                let s = if let Format::Extxyz = to {
+                    frame.to_extxyz()?
+                } else {
+                    let mut s = format!("{}\n\n", frame.symbols.len());
+                    for (symbol, [x, y, z]) in frame.symbols.iter().zip(&frame.positions) {
+                        s.push_str(&format!("{symbol:<4} {x:18.8} {y:18.8} {z:18.8}\n"));
+                    }
+                    s
+                };
+                write_frame(&mut writer, &s)?;
+            }
+        }
+        Format::AseJson => {
+            let frames: Vec<_> = frames.collect::<Result<_>>()?;
+            writeln!(writer, "{}", extxyz::format_ase_json(&frames)?)?;
+        }
+        Format::Poscar => {
+            let frames: Vec<_> = frames.collect::<Result<_>>()?;
+            ensure!(frames.len() == 1, "poscar format is for a single frame, but found {} frames", frames.len());
+            write!(writer, "{}", frames[0].to_poscar()?)?;
+        }
+        Format::Xdatcar => {
+            let frames: Vec<_> = frames.collect::<Result<_>>()?;
+            write!(writer, "{}", extxyz::format_xdatcar(&frames)?)?;
+        }
+        Format::LammpsDump => {
+            // stop at the first error, and report it after writing
+            let mut error = None;
+            let frames = frames.map_while(|frame| frame.map_err(|e| error = Some(e)).ok());
+            extxyz::write_lammps_dump_to(&mut writer, frames)?;
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+        Format::LammpsData => {
+            let frames: Vec<_> = frames.collect::<Result<_>>()?;
+            ensure!(frames.len() == 1, "lammps-data format is for a single frame, but found {} frames", frames.len());
+            write!(writer, "{}", frames[0].to_lammps_data()?)?;
+        }
+        Format::Npz => {
+            let frames: Vec<_> = frames.collect::<Result<_>>()?;
+            extxyz::write_npz_to(&mut writer, &frames)?;
+        }
+        #[cfg(feature = "arrow")]
+        Format::Parquet | Format::ParquetAtoms => bail!("parquet format can not be written as a stream"),
+    }
+    writer.flush()?;
+    Ok(())
+}
+// 8c2f6d1e ends here
+
+// [[file:../../extxyz.note::41a7c3b8][41a7c3b8]]
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Count { path } => count(&path),
+        Command::Info { path, frame } => info(&path, frame),
+        Command::Slice { path, selection, output } => slice(&path, selection, output.as_deref()),
+        Command::Filter { path, expr, output } => filter(&path, &expr, output.as_deref()),
+        Command::Cat { paths, output } => cat(&paths, output.as_deref()),
+        Command::Validate { path, lenient } => validate(&path, lenient),
+        Command::Convert { path, output, from, to } => convert(&path, output.as_deref(), from, to),
+    }
+}
+// 41a7c3b8 ends here
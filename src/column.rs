@@ -0,0 +1,485 @@
+// [[file:../extxyz.note::c5c08a6d][c5c08a6d]]
+use anyhow::*;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::options::Diagnostics;
+use crate::parser::extxyz::{PropertyValue, PropertyValueType};
+use crate::parser::xyz::AtomLayout;
+use crate::{Info, ParseError, ParseErrorKind, ParseOptions, ParseWarning, RawAtom};
+// c5c08a6d ends here
+
+// [[file:../extxyz.note::29a8d5f0][29a8d5f0]]
+/// Represents the typed values of one per-atom property for all atoms
+/// in a frame (structure of arrays).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Column {
+    /// R:1
+    Real(Vec<f64>),
+    /// R:3, such as forces or velocities
+    Real3(Vec<[f64; 3]>),
+    /// I:1
+    Integer(Vec<i64>),
+    /// L:1
+    Logical(Vec<bool>),
+    /// S:1
+    String(Vec<String>),
+    /// R:n with n other than 1 or 3, stored row by row in flat vector
+    RealN(usize, Vec<f64>),
+    /// I:n with n > 1, stored row by row in flat vector
+    IntegerN(usize, Vec<i64>),
+    /// L:n with n > 1, stored row by row in flat vector
+    LogicalN(usize, Vec<bool>),
+    /// S:n with n > 1, stored row by row in flat vector
+    StringN(usize, Vec<String>),
+}
+
+/// Per-atom properties in columns, keyed by property name, in the order
+/// as declared in `Properties`.
+pub type Columns = IndexMap<String, Column>;
+
+impl Column {
+    /// Create an empty column for property value type `t` with
+    /// `width` columns, and reserve space for `n` atoms.
+    ///
+    /// # Panics
+    /// Panics if `width` is zero.
+    pub fn new(t: PropertyValueType, width: usize, n: usize) -> Self {
+        use PropertyValueType as T;
+
+        assert!(width > 0, "the number of columns of property should be positive");
+        match (t, width) {
+            (T::Real, 1) => Self::Real(Vec::with_capacity(n)),
+            (T::Real, 3) => Self::Real3(Vec::with_capacity(n)),
+            (T::Integer, 1) => Self::Integer(Vec::with_capacity(n)),
+            (T::Logical, 1) => Self::Logical(Vec::with_capacity(n)),
+            (T::String, 1) => Self::String(Vec::with_capacity(n)),
+            (T::Real, w) => Self::RealN(w, Vec::with_capacity(n * w)),
+            (T::Integer, w) => Self::IntegerN(w, Vec::with_capacity(n * w)),
+            (T::Logical, w) => Self::LogicalN(w, Vec::with_capacity(n * w)),
+            (T::String, w) => Self::StringN(w, Vec::with_capacity(n * w)),
+        }
+    }
+
+    /// Return the number of columns for each atom.
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Real3(_) => 3,
+            Self::RealN(w, _) | Self::IntegerN(w, _) | Self::LogicalN(w, _) | Self::StringN(w, _) => *w,
+            _ => 1,
+        }
+    }
+
+    /// Return the number of atoms. A column of zero width (which could
+    /// only be constructed directly) has no atoms.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Real(v) => v.len(),
+            Self::Real3(v) => v.len(),
+            Self::Integer(v) => v.len(),
+            Self::Logical(v) => v.len(),
+            Self::String(v) => v.len(),
+            Self::RealN(w, v) => v.len().checked_div(*w).unwrap_or(0),
+            Self::IntegerN(w, v) => v.len().checked_div(*w).unwrap_or(0),
+            Self::LogicalN(w, v) => v.len().checked_div(*w).unwrap_or(0),
+            Self::StringN(w, v) => v.len().checked_div(*w).unwrap_or(0),
+        }
+    }
+
+    /// Return true if there is no atom in column.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the value type in column.
+    pub fn value_type(&self) -> PropertyValueType {
+        use PropertyValueType as T;
+
+        match self {
+            Self::Real(_) | Self::Real3(_) | Self::RealN(..) => T::Real,
+            Self::Integer(_) | Self::IntegerN(..) => T::Integer,
+            Self::Logical(_) | Self::LogicalN(..) => T::Logical,
+            Self::String(_) | Self::StringN(..) => T::String,
+        }
+    }
+
+    /// Return the property definition in `Properties` for column `name`.
+    pub fn property(&self, name: &str) -> PropertyValue {
+        PropertyValue {
+            name: name.into(),
+            r#type: self.value_type(),
+            num_columns: self.width(),
+        }
+    }
+
+    /// Return values for `R:1` property.
+    pub fn as_real(&self) -> Option<&[f64]> {
+        match self {
+            Self::Real(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return values for `R:3` property.
+    pub fn as_real3(&self) -> Option<&[[f64; 3]]> {
+        match self {
+            Self::Real3(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return values for `I:1` property.
+    pub fn as_integer(&self) -> Option<&[i64]> {
+        match self {
+            Self::Integer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return values for `L:1` property.
+    pub fn as_logical(&self) -> Option<&[bool]> {
+        match self {
+            Self::Logical(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return values for `S:1` property.
+    pub fn as_string(&self) -> Option<&[String]> {
+        match self {
+            Self::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the value of `i`-th atom in json format.
+    pub fn get_value(&self, i: usize) -> Option<Value> {
+        if i >= self.len() {
+            return None;
+        }
+        let v = match self {
+            Self::Real(v) => v[i].into(),
+            Self::Real3(v) => v[i].to_vec().into(),
+            Self::Integer(v) => v[i].into(),
+            Self::Logical(v) => v[i].into(),
+            Self::String(v) => v[i].clone().into(),
+            Self::RealN(w, v) => v[i * w..(i + 1) * w].to_vec().into(),
+            Self::IntegerN(w, v) => v[i * w..(i + 1) * w].to_vec().into(),
+            Self::LogicalN(w, v) => v[i * w..(i + 1) * w].to_vec().into(),
+            Self::StringN(w, v) => v[i * w..(i + 1) * w].to_vec().into(),
+        };
+        Some(v)
+    }
+
+    /// Return an iterator over string values in column. The iterator
+    /// will be empty for columns of other types.
+    pub fn iter_strings(&self) -> impl Iterator<Item = &str> {
+        let values: &[String] = match self {
+            Self::String(v) | Self::StringN(_, v) => v,
+            _ => &[],
+        };
+        values.iter().map(|s| s.as_str())
+    }
+
+    /// Construct column from per-atom `values` in json format, with the
+    /// value type inferred as `L`, `I`, `R` or `S`. Each value is either
+    /// a scalar, or an array of the same length for multiple columns.
+    /// Return None if values are empty or not consistent in type.
+    pub(crate) fn from_values(values: &[Value]) -> Option<Self> {
+        let (width, scalars): (usize, Vec<&Value>) = match values.first()? {
+            Value::Array(row) if !row.is_empty() => {
+                let width = row.len();
+                let mut scalars = Vec::with_capacity(values.len() * width);
+                for v in values {
+                    scalars.extend(v.as_array().filter(|row| row.len() == width)?);
+                }
+                (width, scalars)
+            }
+            _ => (1, values.iter().collect()),
+        };
+
+        let column = if let Some(v) = scalars.iter().map(|x| x.as_bool()).collect::<Option<Vec<_>>>() {
+            match width {
+                1 => Self::Logical(v),
+                w => Self::LogicalN(w, v),
+            }
+        } else if let Some(v) = scalars.iter().map(|x| x.as_i64()).collect::<Option<Vec<_>>>() {
+            match width {
+                1 => Self::Integer(v),
+                w => Self::IntegerN(w, v),
+            }
+        } else if let Some(v) = scalars.iter().map(|x| x.as_f64()).collect::<Option<Vec<_>>>() {
+            match width {
+                1 => Self::Real(v),
+                3 => Self::Real3(v.chunks(3).map(|x| [x[0], x[1], x[2]]).collect()),
+                w => Self::RealN(w, v),
+            }
+        } else {
+            let v = scalars.iter().map(|x| x.as_str().map(String::from)).collect::<Option<Vec<_>>>()?;
+            match width {
+                1 => Self::String(v),
+                w => Self::StringN(w, v),
+            }
+        };
+        Some(column)
+    }
+
+    // Parse `width` tokens of one atom from `tokens` and push to the end
+    pub(crate) fn push_tokens<'a>(&mut self, tokens: &mut impl Iterator<Item = &'a str>) -> Result<()> {
+        let width = self.width();
+        let mut next = || tokens.next().context("not enough columns");
+        match self {
+            Self::Real(v) => v.push(parse_real(next()?)?),
+            Self::Real3(v) => v.push([parse_real(next()?)?, parse_real(next()?)?, parse_real(next()?)?]),
+            Self::Integer(v) => v.push(parse_integer(next()?)?),
+            Self::Logical(v) => v.push(parse_logical(next()?)?),
+            Self::String(v) => v.push(next()?.to_string()),
+            Self::RealN(_, v) => {
+                for _ in 0..width {
+                    v.push(parse_real(next()?)?);
+                }
+            }
+            Self::IntegerN(_, v) => {
+                for _ in 0..width {
+                    v.push(parse_integer(next()?)?);
+                }
+            }
+            Self::LogicalN(_, v) => {
+                for _ in 0..width {
+                    v.push(parse_logical(next()?)?);
+                }
+            }
+            Self::StringN(_, v) => {
+                for _ in 0..width {
+                    v.push(next()?.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+// 29a8d5f0 ends here
+
+// [[file:../extxyz.note::630a98e4][630a98e4]]
+// Real values in atom lines are decimal numbers as in comment line, so
+// `inf` or `NaN` is not allowed.
+pub(crate) fn parse_real(s: &str) -> Result<f64> {
+    use std::result::Result::Ok;
+    use winnow::Parser;
+
+    // fast path for numbers without the D format code
+    if s.bytes().all(|b| b.is_ascii_digit() || b".+-eE".contains(&b)) {
+        if let Ok(x) = s.parse() {
+            return Ok(x);
+        }
+    }
+    crate::parser::recognize_sci_float
+        .parse(s)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .with_context(|| format!("invalid real value: {s:?}"))
+}
+
+fn parse_integer(s: &str) -> Result<i64> {
+    s.parse().with_context(|| format!("invalid integer value: {s:?}"))
+}
+
+fn parse_logical(s: &str) -> Result<bool> {
+    match s {
+        "T" | "True" | "TRUE" | "true" => Ok(true),
+        "F" | "False" | "FALSE" | "false" => Ok(false),
+        _ => bail!("invalid logical value: {s:?}"),
+    }
+}
+
+// Split `extra` of `RawAtom` into tokens. For `layout` reordered,
+// `extra` is the whole atom line, and the columns for element symbol and
+// positions are skipped.
+fn extra_tokens(extra: &str, layout: Option<AtomLayout>) -> impl Iterator<Item = &str> {
+    let mut tokens = extra.split_ascii_whitespace();
+    let mut reordered = layout.map(|layout| layout.extra_columns(extra));
+    std::iter::from_fn(move || match &mut reordered {
+        Some(columns) => columns.next(),
+        None => tokens.next(),
+    })
+}
+
+// Parse extra columns of all `atoms` for parsed `properties`, with
+// issues reported in `diag`, as in `Info::parse_atom_columns_with`. The
+// `Properties` entry and the `layout` derived from it are parsed once
+// by caller.
+fn parse_columns_with(properties: &[PropertyValue], layout: Option<AtomLayout>, atoms: &[RawAtom], diag: &mut Diagnostics) -> Result<Columns> {
+    let properties: Vec<_> = properties.iter().filter(|p| p.name != "species" && p.name != "pos").collect();
+    let n = atoms.len();
+    // failed columns are set to None in lenient mode
+    let mut columns: Vec<_> = properties.iter().map(|p| Some(Column::new(p.r#type, p.num_columns, n))).collect();
+    for (i, atom) in atoms.iter().enumerate() {
+        // atom lines start from the third line in frame
+        let line = Some(i + 3);
+        let mut tokens = extra_tokens(atom.extra, layout);
+        for (p, column) in properties.iter().zip(columns.iter_mut()) {
+            let chunk: Vec<_> = tokens.by_ref().take(p.num_columns).collect();
+            let Some(col) = column else {
+                continue;
+            };
+            if let Err(e) = col.push_tokens(&mut chunk.into_iter()) {
+                diag.report(ParseErrorKind::InvalidProperty, line, format!("failed to parse property {:?} for atom {}: {e}", p.name, i + 1))?;
+                *column = None;
+            }
+        }
+        let rest = tokens.count();
+        if rest > 0 {
+            diag.report(ParseErrorKind::TrailingColumns, line, format!("{rest} trailing columns for atom {}: {:?}", i + 1, atom.extra))?;
+        }
+    }
+
+    let columns = properties
+        .into_iter()
+        .zip(columns)
+        .filter_map(|(p, column)| Some((p.name.clone(), column?)))
+        .collect();
+    Ok(columns)
+}
+
+impl Info {
+    /// Parse extra columns of all `atoms` at once according to the
+    /// `Properties` entry, and return typed values in columns keyed by
+    /// property name. The `species` and `pos` columns are excluded.
+    ///
+    /// This is much faster than calling `parse_extra_columns` for each
+    /// atom.
+    pub fn parse_atom_columns(&self, atoms: &[RawAtom]) -> Result<Columns> {
+        let properties = self.properties()?;
+        let layout = AtomLayout::reordered(&properties);
+        let properties: Vec<_> = properties.into_iter().filter(|p| p.name != "species" && p.name != "pos").collect();
+
+        let n = atoms.len();
+        let mut columns: Vec<_> = properties.iter().map(|p| Column::new(p.r#type, p.num_columns, n)).collect();
+        for (i, atom) in atoms.iter().enumerate() {
+            let mut tokens = extra_tokens(atom.extra, layout);
+            for (p, column) in properties.iter().zip(columns.iter_mut()) {
+                column
+                    .push_tokens(&mut tokens)
+                    .with_context(|| format!("failed to parse property {:?} for atom {}: {:?}", p.name, i + 1, atom.extra))?;
+            }
+        }
+
+        let columns = properties.into_iter().map(|p| p.name).zip(columns).collect();
+        Ok(columns)
+    }
+
+    /// Parse extra columns of all `atoms` like `parse_atom_columns`, but
+    /// with `options`. In strict mode, all property columns must be
+    /// present without trailing columns. In lenient mode, a property
+    /// that fails to parse is skipped with a warning, and so are the
+    /// columns if `Properties` entry is invalid.
+    pub fn parse_atom_columns_with(&self, atoms: &[RawAtom], options: &ParseOptions) -> Result<(Columns, Vec<ParseWarning>)> {
+        use std::result::Result::Ok;
+
+        let mut diag = Diagnostics::new(options);
+        let properties = match self.properties() {
+            Ok(properties) => properties,
+            Err(e) => {
+                diag.report(ParseErrorKind::InvalidProperties, Some(2), format!("{e:#}"))?;
+                vec![]
+            }
+        };
+        let columns = parse_columns_with(&properties, AtomLayout::reordered(&properties), atoms, &mut diag)?;
+        Ok((columns, diag.finish()))
+    }
+
+    /// Parse atom properties from extra columns in `extra` like
+    /// `parse_extra_columns`, but with `options` as in
+    /// `parse_atom_columns_with`.
+    pub fn parse_extra_columns_with(&self, extra: &str, options: &ParseOptions) -> Result<(Map<String, Value>, Vec<ParseWarning>)> {
+        let atom = RawAtom {
+            element: "",
+            position: [0.0; 3],
+            extra,
+        };
+        let (columns, warnings) = self.parse_atom_columns_with(std::slice::from_ref(&atom), options).map_err(|mut e| {
+            // locate the error in standalone `extra`
+            if let Some(e) = e.downcast_mut::<ParseError>() {
+                *e = ParseError::at_line(e.kind, std::mem::take(&mut e.message), extra, 1);
+            }
+            e
+        })?;
+        let map = columns.into_iter().filter_map(|(k, column)| Some((k, column.get_value(0)?))).collect();
+        // no line number for standalone `extra`
+        let warnings = warnings.into_iter().map(|w| ParseWarning { line: None, ..w }).collect();
+        Ok((map, warnings))
+    }
+}
+// 630a98e4 ends here
+
+// [[file:../extxyz.note::8ed3554b][8ed3554b]]
+#[test]
+fn test_parse_atom_columns() -> Result<()> {
+    let input = r#"3
+Properties=species:S:1:pos:R:3:forces:R:3:freeze:L:1:tag:I:1:label:S:1:m:R:2 energy=-1.5
+Si 0.0 0.0 0.0 0.1 0.2 0.3 T 1 a 1.0 2.0
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3D0 F 2 b 3.0 4.0
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 F -3 c 5.0 6.0
+"#;
+    let atoms = crate::RawAtoms::parse_from(input)?;
+    let info: Info = atoms.comment.parse()?;
+    let columns = info.parse_atom_columns(&atoms.atoms)?;
+    assert_eq!(columns.len(), 5);
+    assert_eq!(columns["forces"].as_real3().unwrap()[1], [-0.1, -0.2, -0.3]);
+    assert_eq!(columns["freeze"].as_logical().unwrap(), [true, false, false]);
+    assert_eq!(columns["tag"].as_integer().unwrap(), [1, 2, -3]);
+    assert_eq!(columns["label"].as_string().unwrap(), ["a", "b", "c"]);
+    assert_eq!(columns["m"], Column::RealN(2, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+    assert_eq!(columns["m"].len(), 3);
+    assert_eq!(Column::RealN(0, vec![]).len(), 0);
+    assert!(std::panic::catch_unwind(|| Column::new(PropertyValueType::Real, 0, 1)).is_err());
+    assert_eq!(columns["m"].get_value(2).unwrap(), serde_json::json!([5.0, 6.0]));
+
+    // consistent with `parse_extra_columns`
+    for (i, atom) in atoms.atoms.iter().enumerate() {
+        let map = info.parse_extra_columns(atom.extra)?;
+        for (k, v) in map {
+            assert_eq!(columns[&k].get_value(i).unwrap(), v);
+        }
+    }
+
+    // the same token grammar in both paths
+    let input = "1\nProperties=species:S:1:pos:R:3:label:S:1:q:R:1\nC 0 0 0 C.3 1d0\n";
+    let frame = crate::Frame::parse(input)?;
+    let info: Info = "Properties=species:S:1:pos:R:3:label:S:1:q:R:1".parse()?;
+    let map = info.parse_extra_columns("C.3 1d0")?;
+    assert_eq!((&map["label"], &map["q"]), (&"C.3".into(), &1.0.into()));
+    for (k, v) in &map {
+        assert_eq!(&frame.arrays[k].get_value(0).unwrap(), v);
+    }
+    assert!(crate::Frame::parse_with(&input.replace("1d0", "inf"), &ParseOptions::strict()).is_err());
+    assert!(info.parse_extra_columns("C.3 inf").is_err());
+    assert!(info.parse_extra_columns("C.3 1d0 junk").is_err());
+
+    // missing columns
+    let atoms = crate::RawAtoms::parse_from("1\nProperties=species:S:1:pos:R:3:forces:R:3\nH 0.0 0.0 0.0 1.0 2.0")?;
+    let info: Info = atoms.comment.parse()?;
+    assert!(info.parse_atom_columns(&atoms.atoms).is_err());
+    assert!(info.parse_atom_columns_with(&atoms.atoms, &ParseOptions::strict()).is_err());
+    let (columns, warnings) = info.parse_atom_columns_with(&atoms.atoms, &ParseOptions::lenient())?;
+    assert!(columns.is_empty());
+    assert_eq!(warnings[0].line, Some(3));
+
+    // trailing columns
+    let info: Info = "Properties=species:S:1:pos:R:3:q:R:1:tag:I:1".parse()?;
+    let e = info.parse_extra_columns_with("0.5 1 junk", &ParseOptions::strict()).unwrap_err();
+    let e = e.downcast::<ParseError>()?;
+    assert_eq!((e.kind, e.line, e.source_line.as_str()), (ParseErrorKind::TrailingColumns, 1, "0.5 1 junk"));
+    let (map, warnings) = info.parse_extra_columns_with("0.5 x junk", &ParseOptions::lenient())?;
+    assert_eq!(map.len(), 1);
+    assert_eq!(map["q"], 0.5);
+    let kinds: Vec<_> = warnings.iter().map(|w| w.kind).collect();
+    assert_eq!(kinds, [ParseErrorKind::InvalidProperty, ParseErrorKind::TrailingColumns]);
+    let (map, warnings) = info.parse_extra_columns_with("0.5 1", &ParseOptions::strict())?;
+    assert_eq!(map, info.parse_extra_columns("0.5 1")?);
+    assert!(warnings.is_empty());
+
+    Ok(())
+}
+// 8ed3554b ends here
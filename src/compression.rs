@@ -0,0 +1,163 @@
+// [[file:../extxyz.note::9d6a48a1][9d6a48a1]]
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use anyhow::*;
+// 9d6a48a1 ends here
+
+// [[file:../extxyz.note::821e7a86][821e7a86]]
+/// Represents the compression format of trajectory file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain text
+    None,
+    /// gzip (.gz)
+    Gzip,
+    /// bzip2 (.bz2)
+    Bzip2,
+    /// xz (.xz)
+    Xz,
+    /// zstd (.zst)
+    Zstd,
+}
+
+impl Compression {
+    /// Detect compression format from leading `magic` bytes.
+    pub fn from_magic(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [b'B', b'Z', b'h', ..] => Some(Self::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Guess compression format from file extension in `path`.
+    pub fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("bz2") => Self::Bzip2,
+            Some("xz") => Self::Xz,
+            Some("zst" | "zstd") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    /// Detect compression format of file in `path` by magic bytes,
+    /// falling back to file extension.
+    pub fn detect(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut f = File::open(path).with_context(|| format!("Failed to open file {path:?}"))?;
+        let mut magic = vec![];
+        f.by_ref().take(6).read_to_end(&mut magic)?;
+        let c = Self::from_magic(&magic).unwrap_or_else(|| Self::from_extension(path));
+        Ok(c)
+    }
+
+    /// Return true if the data is compressed.
+    pub fn is_compressed(&self) -> bool {
+        *self != Self::None
+    }
+}
+// 821e7a86 ends here
+
+// [[file:../extxyz.note::4a2d7c35][4a2d7c35]]
+/// A buffered reader that decompresses data in streaming fashion.
+pub(crate) type DynReader = Box<dyn BufRead + Send>;
+
+#[allow(unused_variables)]
+fn decoder(f: File, c: Compression) -> Result<DynReader> {
+    let r: DynReader = match c {
+        Compression::None => Box::new(BufReader::new(f)),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(f))),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Box::new(BufReader::new(bzip2::read::MultiBzDecoder::new(f))),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Box::new(BufReader::new(xz2::read::XzDecoder::new_multi_decoder(f))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::new(f)?)),
+        #[allow(unreachable_patterns)]
+        _ => bail!("{c:?} compressed file is not supported. Please enable the corresponding cargo feature."),
+    };
+    Ok(r)
+}
+
+/// Open file in `path` for reading text, which will be transparently
+/// decompressed if it is compressed.
+pub(crate) fn open_text_file(path: &Path) -> Result<DynReader> {
+    let c = Compression::detect(path)?;
+    let f = File::open(path).with_context(|| format!("Failed to open file {path:?}"))?;
+    decoder(f, c)
+}
+// 4a2d7c35 ends here
+
+// [[file:../extxyz.note::7d8f4199][7d8f4199]]
+#[test]
+fn test_compression_detect() -> Result<()> {
+    assert_eq!(Compression::from_magic(&[0x1f, 0x8b, 0x08]), Some(Compression::Gzip));
+    assert_eq!(Compression::from_magic(b"BZh91AY"), Some(Compression::Bzip2));
+    assert_eq!(Compression::from_magic(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]), Some(Compression::Xz));
+    assert_eq!(Compression::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]), Some(Compression::Zstd));
+    assert_eq!(Compression::from_magic(b"3\nProperties"), None);
+    assert_eq!(Compression::from_extension("traj.xyz.zst".as_ref()), Compression::Zstd);
+    assert_eq!(Compression::detect("tests/files/water.xyz")?, Compression::None);
+
+    Ok(())
+}
+// 7d8f4199 ends here
+
+// [[file:../extxyz.note::d6651932][d6651932]]
+#[cfg(all(feature = "gzip", feature = "bzip2", feature = "xz", feature = "zstd"))]
+#[test]
+fn test_compressed_frames() -> Result<()> {
+    use std::io::Write;
+
+    let raw = std::fs::read("tests/files/water.xyz")?;
+    let dir = std::env::temp_dir().join("extxyz-test-compression");
+    std::fs::create_dir_all(&dir)?;
+
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(&raw)?;
+    let mut bz = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+    bz.write_all(&raw)?;
+    let mut xz = xz2::write::XzEncoder::new(vec![], 6);
+    xz.write_all(&raw)?;
+    let files = [
+        ("water.xyz.gz", gz.finish()?),
+        ("water.xyz.bz2", bz.finish()?),
+        ("water.xyz.xz", xz.finish()?),
+        ("water.xyz.zst", zstd::encode_all(&raw[..], 0)?),
+    ];
+
+    let expected: Vec<_> = crate::read_xyz_frames_direct("tests/files/water.xyz")?.collect();
+    for (name, data) in files {
+        let path = dir.join(name);
+        std::fs::write(&path, data)?;
+        assert!(Compression::detect(&path)?.is_compressed());
+        let frames: Vec<_> = crate::read_xyz_frames_direct(&path)?.collect();
+        assert_eq!(frames, expected);
+        // skip frames with selection
+        let frames: Vec<_> = crate::read_xyz_frames(&path, [0, 2].into_iter())?.collect();
+        assert_eq!(frames, [expected[0].clone(), expected[2].clone()]);
+    }
+
+    // frames are separated by natoms lines as uncompressed trajectory,
+    // and selection should be in ascending order
+    let s = "1\ncomment\nH 0 0 0\nVEC1 1.0 0.0 0.0\n1\ncomment\nH 0 0 1\n";
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(s.as_bytes())?;
+    let path = dir.join("vec.xyz.gz");
+    std::fs::write(&path, gz.finish()?)?;
+    let frames: Vec<_> = crate::read_xyz_frames(&path, 0..)?.collect();
+    assert_eq!(frames, ["1\ncomment\nH 0 0 0\nVEC1 1.0 0.0 0.0\n", "1\ncomment\nH 0 0 1\n"]);
+    let frames: Vec<_> = crate::try_read_xyz_frames_two_pass(&path, [1, 0].into_iter())?.collect();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].is_ok());
+    assert!(matches!(frames[1].as_ref().unwrap_err().kind, crate::ReadErrorKind::NotAscending));
+
+    Ok(())
+}
+// d6651932 ends here
@@ -0,0 +1,284 @@
+// [[file:../extxyz.note::e6b3d0a4][e6b3d0a4]]
+use serde::de::value::Error;
+use serde::de::{self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+
+use crate::{Column, Columns, Frame, Info};
+// e6b3d0a4 ends here
+
+// [[file:../extxyz.note::71d8a2c5][71d8a2c5]]
+// A node in frame data, which is deserialized lazily by borrowing from
+// `Frame`.
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    U64(u64),
+    F64(f64),
+    I64(i64),
+    Bool(bool),
+    Str(&'a str),
+    F64s(&'a [f64]),
+    I64s(&'a [i64]),
+    Bools(&'a [bool]),
+    Strs(&'a [String]),
+    // positions, lattice or `R:3` column
+    Rows3(&'a [[f64; 3]]),
+    // per-atom values in column
+    Column(&'a Column),
+    // value in `info`
+    Json(&'a Value),
+    Info(&'a Info),
+    Arrays(&'a Columns),
+    Frame(&'a Frame),
+}
+
+type Nodes<'a> = Box<dyn Iterator<Item = Node<'a>> + 'a>;
+type Entries<'a> = Box<dyn Iterator<Item = (&'a str, Node<'a>)> + 'a>;
+
+fn column_nodes(column: &Column) -> Nodes<'_> {
+    match column {
+        Column::Real(v) => Box::new(v.iter().map(|x| Node::F64(*x))),
+        Column::Real3(v) => Box::new(v.iter().map(|x| Node::F64s(x))),
+        Column::Integer(v) => Box::new(v.iter().map(|x| Node::I64(*x))),
+        Column::Logical(v) => Box::new(v.iter().map(|x| Node::Bool(*x))),
+        Column::String(v) => Box::new(v.iter().map(|x| Node::Str(x))),
+        Column::RealN(w, v) => Box::new(v.chunks(*w).map(Node::F64s)),
+        Column::IntegerN(w, v) => Box::new(v.chunks(*w).map(Node::I64s)),
+        Column::LogicalN(w, v) => Box::new(v.chunks(*w).map(Node::Bools)),
+        Column::StringN(w, v) => Box::new(v.chunks(*w).map(Node::Strs)),
+    }
+}
+
+// The entries of frame as a map. Entries in `info` and `arrays` are
+// also flattened into the map, unless the key is already taken.
+fn frame_entries(frame: &Frame) -> Entries<'_> {
+    let mut entries = vec![
+        ("natoms", Node::U64(frame.natoms as u64)),
+        ("symbols", Node::Strs(&frame.symbols)),
+        ("positions", Node::Rows3(&frame.positions)),
+    ];
+    if let Some(lattice) = &frame.lattice {
+        entries.push(("lattice", Node::Rows3(lattice)));
+    }
+    if let Some(pbc) = &frame.pbc {
+        entries.push(("pbc", Node::Bools(pbc)));
+    }
+    entries.push(("info", Node::Info(&frame.info)));
+    entries.push(("arrays", Node::Arrays(&frame.arrays)));
+
+    let info = frame.info.raw_map().iter().map(|(k, v)| (k.as_str(), Node::Json(v)));
+    let arrays = frame.arrays.iter().map(|(k, v)| (k.as_str(), Node::Column(v)));
+    for (k, v) in info.chain(arrays) {
+        if entries.iter().all(|(key, _)| *key != k) {
+            entries.push((k, v));
+        }
+    }
+    Box::new(entries.into_iter())
+}
+
+struct NodeSeq<'a>(Nodes<'a>);
+
+impl<'de> SeqAccess<'de> for NodeSeq<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        self.0.next().map(|node| seed.deserialize(node)).transpose()
+    }
+}
+
+struct NodeMap<'a> {
+    entries: Entries<'a>,
+    value: Option<Node<'a>>,
+}
+
+impl<'de> MapAccess<'de> for NodeMap<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.value.take().ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(value)
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(visitor: V, nodes: Nodes<'de>) -> Result<V::Value, Error> {
+    visitor.visit_seq(NodeSeq(nodes))
+}
+
+fn visit_map<'de, V: Visitor<'de>>(visitor: V, entries: Entries<'de>) -> Result<V::Value, Error> {
+    visitor.visit_map(NodeMap { entries, value: None })
+}
+
+impl<'de> Deserializer<'de> for Node<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::U64(x) => visitor.visit_u64(x),
+            Node::F64(x) => visitor.visit_f64(x),
+            Node::I64(x) => visitor.visit_i64(x),
+            Node::Bool(x) => visitor.visit_bool(x),
+            Node::Str(x) => visitor.visit_borrowed_str(x),
+            Node::F64s(x) => visit_seq(visitor, Box::new(x.iter().map(|x| Node::F64(*x)))),
+            Node::I64s(x) => visit_seq(visitor, Box::new(x.iter().map(|x| Node::I64(*x)))),
+            Node::Bools(x) => visit_seq(visitor, Box::new(x.iter().map(|x| Node::Bool(*x)))),
+            Node::Strs(x) => visit_seq(visitor, Box::new(x.iter().map(|x| Node::Str(x)))),
+            Node::Rows3(x) => visit_seq(visitor, Box::new(x.iter().map(|x| Node::F64s(x)))),
+            Node::Column(x) => visit_seq(visitor, column_nodes(x)),
+            Node::Json(x) => x.deserialize_any(visitor).map_err(de::Error::custom),
+            Node::Info(x) => visit_map(visitor, Box::new(x.raw_map().iter().map(|(k, v)| (k.as_str(), Node::Json(v))))),
+            Node::Arrays(x) => visit_map(visitor, Box::new(x.iter().map(|(k, v)| (k.as_str(), Node::Column(v))))),
+            Node::Frame(x) => visit_map(visitor, frame_entries(x)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::Json(Value::Null) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::Str(x) => x.into_deserializer().deserialize_enum(name, variants, visitor),
+            Node::Json(x) => x.deserialize_enum(name, variants, visitor).map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+// 71d8a2c5 ends here
+
+// [[file:../extxyz.note::0b9c4f7e][0b9c4f7e]]
+/// Deserialize an instance of type `T` from `frame`, without copying
+/// the frame data.
+///
+/// The frame is presented as a map with entries:
+/// * `natoms`, `symbols`, `positions`, and `lattice`/`pbc` if present
+/// * `info`: key-value pairs in comment line
+/// * `arrays`: per-atom properties in `Properties` columns
+/// * entries in `info` and `arrays` flattened, such as `energy` or
+///   `forces`. If a key is found in both, the one in `info` is used.
+///
+/// # Example
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Structure {
+///     energy: f64,
+///     lattice: [[f64; 3]; 3],
+///     forces: Vec<[f64; 3]>,
+/// }
+///
+/// let s = r#"1
+/// Lattice="4.0 0.0 0.0 0.0 4.0 0.0 0.0 0.0 4.0" Properties=species:S:1:pos:R:3:forces:R:3 energy=-1.5
+/// Cu 0.0 0.0 0.0 0.1 0.2 0.3
+/// "#;
+/// let structure: Structure = extxyz::from_str(s).unwrap();
+/// assert_eq!(structure.energy, -1.5);
+/// assert_eq!(structure.lattice[1][1], 4.0);
+/// assert_eq!(structure.forces[0], [0.1, 0.2, 0.3]);
+/// ```
+pub fn from_frame<'de, T: serde::Deserialize<'de>>(frame: &'de Frame) -> anyhow::Result<T> {
+    let value = T::deserialize(Node::Frame(frame))?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from a complete frame `s` in
+/// xyz/extxyz format. See `from_frame` for the available entries.
+pub fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> anyhow::Result<T> {
+    let frame = Frame::parse(s)?;
+    from_frame(&frame)
+}
+// 0b9c4f7e ends here
+
+// [[file:../extxyz.note::5c2e8f19][5c2e8f19]]
+#[test]
+fn test_from_frame() -> anyhow::Result<()> {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Arrays<'a> {
+        energy: Vec<f64>,
+        #[serde(borrow)]
+        label: Vec<&'a str>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Phase {
+        Bulk,
+        Surface,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Structure<'a> {
+        natoms: usize,
+        symbols: Vec<String>,
+        positions: Vec<[f64; 3]>,
+        lattice: Option<[[f64; 3]; 3]>,
+        pbc: [bool; 3],
+        energy: f64,
+        virial: Vec<f64>,
+        config_type: Phase,
+        comment: Option<String>,
+        forces: Vec<[f64; 3]>,
+        fixed: Vec<bool>,
+        tag: Vec<i64>,
+        m: Vec<(f64, f64)>,
+        #[serde(borrow)]
+        arrays: Arrays<'a>,
+    }
+
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:fixed:L:1:tag:I:1:label:S:1:m:R:2:energy:R:1 energy=-1.5 pbc="T T F" virial="1 2 3" config_type=Bulk
+Si 0.0 0.0 0.0 0.1 0.2 0.3 T 1 a 1.0 2.0 -0.7
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 F 2 b 3.0 4.0 -0.8
+"#;
+    let frame = Frame::parse(input)?;
+    let s: Structure = from_frame(&frame)?;
+    assert_eq!(s.natoms, 2);
+    assert_eq!(s.symbols, ["Si", "Si"]);
+    assert_eq!(s.positions[1], [1.36; 3]);
+    assert_eq!(s.lattice.unwrap()[2][2], 5.44);
+    assert_eq!(s.pbc, [true, true, false]);
+    // `energy` in comment line is preferred
+    assert_eq!(s.energy, -1.5);
+    assert_eq!(s.arrays.energy, [-0.7, -0.8]);
+    assert_eq!(s.arrays.label, ["a", "b"]);
+    assert_eq!(s.virial, [1.0, 2.0, 3.0]);
+    assert_eq!(s.config_type, Phase::Bulk);
+    assert_eq!(s.comment, None);
+    assert_eq!(s.forces[1], [-0.1, -0.2, -0.3]);
+    assert_eq!(s.fixed, [true, false]);
+    assert_eq!(s.tag, [1, 2]);
+    assert_eq!(s.m[1], (3.0, 4.0));
+
+    // missing entries
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Periodic {
+        lattice: [[f64; 3]; 3],
+    }
+    let e = from_str::<Periodic>("1\nenergy=1.0\nH 0.0 0.0 0.0").unwrap_err();
+    assert!(e.to_string().contains("missing field `lattice`"), "{e}");
+
+    Ok(())
+}
+// 5c2e8f19 ends here
@@ -0,0 +1,51 @@
+// [[file:../extxyz.note::0c5e9f3a][0c5e9f3a]]
+#[rustfmt::skip]
+const SYMBOLS: [&str; 118] = [
+    "H",                                                                                                  "He",
+    "Li", "Be",                                                             "B",  "C",  "N",  "O",  "F",  "Ne",
+    "Na", "Mg",                                                             "Al", "Si", "P",  "S",  "Cl", "Ar",
+    "K",  "Ca", "Sc", "Ti", "V",  "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr",
+    "Rb", "Sr", "Y",  "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In", "Sn", "Sb", "Te", "I",  "Xe",
+    "Cs", "Ba",
+    "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu",
+                "Hf", "Ta", "W",  "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn",
+    "Fr", "Ra",
+    "Ac", "Th", "Pa", "U",  "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es", "Fm", "Md", "No", "Lr",
+                "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// Return the element symbol of atomic number `z`.
+pub(crate) fn element_symbol(z: i64) -> Option<&'static str> {
+    let i = usize::try_from(z).ok()?.checked_sub(1)?;
+    SYMBOLS.get(i).copied()
+}
+
+/// Return the atomic number of element `symbol`, which could also be
+/// an atomic number itself.
+pub(crate) fn atomic_number(symbol: &str) -> Option<i64> {
+    if let Ok(z) = symbol.parse::<i64>() {
+        return element_symbol(z).map(|_| z);
+    }
+    let i = SYMBOLS.iter().position(|s| s.eq_ignore_ascii_case(symbol))?;
+    Some(i as i64 + 1)
+}
+// 0c5e9f3a ends here
+
+// [[file:../extxyz.note::6a1d8b2e][6a1d8b2e]]
+#[test]
+fn test_element_symbol() {
+    assert_eq!(element_symbol(1), Some("H"));
+    assert_eq!(element_symbol(8), Some("O"));
+    assert_eq!(element_symbol(29), Some("Cu"));
+    assert_eq!(element_symbol(118), Some("Og"));
+    assert_eq!(element_symbol(0), None);
+    assert_eq!(element_symbol(119), None);
+
+    assert_eq!(atomic_number("H"), Some(1));
+    assert_eq!(atomic_number("Cu"), Some(29));
+    assert_eq!(atomic_number("CU"), Some(29));
+    assert_eq!(atomic_number("8"), Some(8));
+    assert_eq!(atomic_number("X"), None);
+    assert_eq!(atomic_number("0"), None);
+}
+// 6a1d8b2e ends here
@@ -0,0 +1,222 @@
+// [[file:../extxyz.note::c4e1a7d9][c4e1a7d9]]
+use std::fmt;
+// c4e1a7d9 ends here
+
+// [[file:../extxyz.note::2f8b0d63][2f8b0d63]]
+/// The kind of `ParseError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The first line is not the number of atoms
+    InvalidNatoms,
+    /// The frame ends before any atom line
+    MissingAtoms,
+    /// The comment line is not in extxyz format
+    InvalidComment,
+    /// An atom line can not be parsed
+    InvalidAtomLine,
+    /// A key appears more than once in the comment line
+    DuplicateKey,
+    /// The `Properties` entry does not fit atom lines
+    InvalidProperties,
+    /// The number of atom lines differs from the natoms line
+    NatomsMismatch,
+    /// A per-atom property value can not be parsed
+    InvalidProperty,
+    /// There are more columns in atom line than in `Properties`
+    TrailingColumns,
+    /// The `Lattice` or `pbc` entry is invalid
+    InvalidLattice,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::InvalidNatoms => "invalid natoms line",
+            Self::MissingAtoms => "missing atom lines",
+            Self::InvalidComment => "invalid extxyz comment line",
+            Self::InvalidAtomLine => "invalid atom line",
+            Self::DuplicateKey => "duplicate key",
+            Self::InvalidProperties => "invalid Properties",
+            Self::NatomsMismatch => "natoms mismatch",
+            Self::InvalidProperty => "invalid property value",
+            Self::TrailingColumns => "trailing columns",
+            Self::InvalidLattice => "invalid lattice",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Represents an error in parsing xyz/extxyz frame, located by line and
+/// column in the frame text.
+///
+/// It is returned wrapped in `anyhow::Error` by the parse functions,
+/// and could be recovered with `downcast_ref::<ParseError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The kind of error
+    pub kind: ParseErrorKind,
+    /// Details of the error
+    pub message: String,
+    /// The index of frame in trajectory, if known
+    pub frame: Option<usize>,
+    /// The line number in frame, counting from 1
+    pub line: usize,
+    /// The column number in characters, counting from 1, or 0 if unknown
+    pub column: usize,
+    /// The offending line
+    pub source_line: String,
+}
+
+// the maximum number of characters of source line for display
+const MAX_WIDTH: usize = 80;
+
+impl ParseError {
+    /// Construct error of `kind` at byte `offset` of `input` text.
+    pub(crate) fn new(kind: ParseErrorKind, message: impl Into<String>, input: &str, offset: usize) -> Self {
+        let offset = offset.min(input.len());
+        let head = &input[..offset];
+        let line_start = head.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = input[offset..].find('\n').map(|i| i + offset).unwrap_or(input.len());
+        Self {
+            kind,
+            message: message.into(),
+            frame: None,
+            line: head.matches('\n').count() + 1,
+            column: input[line_start..offset].chars().count() + 1,
+            source_line: input[line_start..line_end].trim_end_matches('\r').to_string(),
+        }
+    }
+
+    /// Construct error of `kind` pointing at `token`, which is a
+    /// substring borrowed from `input`.
+    pub(crate) fn at_token(kind: ParseErrorKind, message: impl Into<String>, input: &str, token: &str) -> Self {
+        let offset = (token.as_ptr() as usize).saturating_sub(input.as_ptr() as usize);
+        Self::new(kind, message, input, offset)
+    }
+
+    /// Construct error of `kind` at `line` (counting from 1) of `input`
+    /// text, pointing at the first non-blank column.
+    pub(crate) fn at_line(kind: ParseErrorKind, message: impl Into<String>, input: &str, line: usize) -> Self {
+        let source_line = input.lines().nth(line.saturating_sub(1)).unwrap_or_default().trim_end_matches('\r');
+        Self {
+            kind,
+            message: message.into(),
+            frame: None,
+            line,
+            column: source_line.chars().take_while(|c| c.is_whitespace()).count() + 1,
+            source_line: source_line.to_string(),
+        }
+    }
+
+    /// Set the frame index in trajectory.
+    pub fn with_frame(mut self, frame: usize) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Shift line number by `n` lines, for error found in part of the
+    /// frame text, such as the comment line.
+    pub(crate) fn shift_lines(mut self, n: usize) -> Self {
+        self.line += n;
+        self
+    }
+
+    // the source line to display and the caret position in it, which is
+    // clipped around the error column for long lines
+    fn snippet(&self) -> (String, usize) {
+        let chars: Vec<_> = self.source_line.chars().collect();
+        // column 0 (unknown) is taken as the first column
+        let caret = self.column.saturating_sub(1);
+        if chars.len() <= MAX_WIDTH {
+            return (self.source_line.clone(), caret);
+        }
+        let start = caret.saturating_sub(MAX_WIDTH / 2).min(chars.len().saturating_sub(MAX_WIDTH));
+        let end = (start + MAX_WIDTH).min(chars.len());
+        let mut s: String = chars[start..end].iter().collect();
+        let mut caret = caret - start;
+        if start > 0 {
+            s.insert_str(0, "...");
+            caret += 3;
+        }
+        if end < chars.len() {
+            s.push_str("...");
+        }
+        (s, caret)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message.replace('\n', "; "))?;
+        }
+        let location = format!("line {}, column {}", self.line, self.column);
+        match self.frame {
+            Some(i) => writeln!(f, "\n --> frame {i}, {location}")?,
+            None => writeln!(f, "\n --> {location}")?,
+        }
+
+        // point a caret at the offending token
+        let (source, caret) = self.snippet();
+        let width = source.chars().skip(caret).take_while(|c| !c.is_whitespace()).count().max(1);
+        let lineno = self.line.to_string();
+        let pad = " ".repeat(lineno.len());
+        writeln!(f, "{pad} |")?;
+        writeln!(f, "{lineno} | {source}")?;
+        write!(f, "{pad} | {}{}", " ".repeat(caret), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Set the source line from frame text `input` in `e` if it is a
+/// `ParseError` located without the frame text.
+pub(crate) fn with_source_line(mut e: anyhow::Error, input: &str) -> anyhow::Error {
+    if let Some(e) = e.downcast_mut::<ParseError>() {
+        if e.source_line.is_empty() {
+            *e = ParseError::at_line(e.kind, std::mem::take(&mut e.message), input, e.line);
+        }
+    }
+    e
+}
+
+/// Set frame index `i` in `e` if it is a `ParseError`.
+#[cfg(any(feature = "mmap", feature = "rayon"))]
+pub(crate) fn with_frame(mut e: anyhow::Error, i: usize) -> anyhow::Error {
+    if let Some(e) = e.downcast_mut::<ParseError>() {
+        e.frame = Some(i);
+    }
+    e
+}
+// 2f8b0d63 ends here
+
+// [[file:../extxyz.note::8a3c5e1b][8a3c5e1b]]
+#[test]
+fn test_parse_error() {
+    let input = "2\ncomment\nH 0.0 0.0 0.0\nO 1.0 abc 0.0\n";
+    let offset = input.find("abc").unwrap();
+    let e = ParseError::new(ParseErrorKind::InvalidAtomLine, "invalid xyz coords", input, offset).with_frame(3);
+    assert_eq!(e.line, 4);
+    assert_eq!(e.column, 7);
+    assert_eq!(e.source_line, "O 1.0 abc 0.0");
+    let expected = "invalid atom line: invalid xyz coords
+ --> frame 3, line 4, column 7
+  |
+4 | O 1.0 abc 0.0
+  |       ^^^";
+    assert_eq!(e.to_string(), expected);
+
+    // long line is clipped around the error
+    let line = format!("H {} x", "0.0 ".repeat(100));
+    let e = ParseError::at_token(ParseErrorKind::InvalidAtomLine, "", &line, &line[line.len() - 1..]);
+    let (source, caret) = e.snippet();
+    assert!(source.starts_with("...") && source.len() < 100);
+    assert_eq!(source.chars().nth(caret), Some('x'));
+
+    // unknown column constructed directly
+    let e = ParseError { column: 0, ..e };
+    assert!(e.to_string().contains("column 0"));
+}
+// 8a3c5e1b ends here
@@ -0,0 +1,320 @@
+// [[file:../extxyz.note::2e7b9c14][2e7b9c14]]
+use std::cmp::Ordering;
+use std::result::Result::Ok;
+
+use anyhow::*;
+use regex::Regex;
+use serde_json::Value;
+use winnow::ascii::{multispace0, multispace1};
+use winnow::combinator::{alt, delimited, opt, preceded, separated};
+use winnow::token::{take, take_while};
+use winnow::{PResult, Parser};
+
+use crate::parser::extxyz::{parse_plain_value, quoted_string};
+use crate::{Frame, Info};
+// 2e7b9c14 ends here
+
+// [[file:../extxyz.note::a6d3f850][a6d3f850]]
+/// The comparison operator in `Filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+/// Represents a predicate on key-value pairs in extxyz comment line,
+/// for selecting frames in trajectory.
+///
+/// A filter can be built in code, or parsed from an expression such as
+/// `energy < -100 and config_type == bulk`. The expression syntax:
+/// * `key op value`: compare value of `key`, where `op` is one of `==`,
+///   `!=`, `<`, `<=`, `>` or `>=`. Numbers are compared numerically, and
+///   strings lexically. The value should be quoted if it contains
+///   whitespace, parentheses or operator characters.
+/// * `key =~ regex`, `key !~ regex`: match string value of `key`
+///   against the regex, which ends at whitespace or an unbalanced `)`
+///   unless quoted
+/// * `key`: `key` is present
+/// * `not p` or `!p`, `p and q` or `p && q`, `p or q` or `p || q`, and
+///   parentheses for grouping
+///
+/// A predicate on a missing key is false, except for negations.
+///
+/// # Example
+///
+/// ```
+/// let filter: extxyz::Filter = "energy < -100 and config_type == bulk".parse().unwrap();
+/// let info: extxyz::Info = "energy=-120.5 config_type=bulk".parse().unwrap();
+/// assert!(filter.matches(&info));
+/// ```
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// The key is present
+    Exists(String),
+    /// The value of key compares with value
+    Compare(String, CompareOp, Value),
+    /// The string value of key matches the regex
+    Matches(String, Regex),
+    /// Negation
+    Not(Box<Filter>),
+    /// Both are true
+    And(Box<Filter>, Box<Filter>),
+    /// Either is true
+    Or(Box<Filter>, Box<Filter>),
+}
+
+// Compare numbers numerically and strings lexically. Other values can
+// only be equal or not.
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (a.as_f64(), b.as_f64()) {
+        return a.partial_cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (a.as_str(), b.as_str()) {
+        return Some(a.cmp(b));
+    }
+    (a == b).then_some(Ordering::Equal)
+}
+
+impl CompareOp {
+    /// Return true if `a op b` holds.
+    pub fn eval(self, a: &Value, b: &Value) -> bool {
+        use Ordering::*;
+
+        let ord = compare_values(a, b);
+        match self {
+            Self::Eq => ord == Some(Equal),
+            Self::Ne => ord != Some(Equal),
+            Self::Lt => ord == Some(Less),
+            Self::Le => matches!(ord, Some(Less | Equal)),
+            Self::Gt => ord == Some(Greater),
+            Self::Ge => matches!(ord, Some(Greater | Equal)),
+        }
+    }
+}
+
+impl Filter {
+    /// Filter on presence of `key`.
+    pub fn exists(key: impl Into<String>) -> Self {
+        Self::Exists(key.into())
+    }
+
+    /// Filter on comparing value of `key` with `value`.
+    pub fn compare(key: impl Into<String>, op: CompareOp, value: impl Into<Value>) -> Self {
+        Self::Compare(key.into(), op, value.into())
+    }
+
+    /// Filter on matching string value of `key` against `regex`.
+    pub fn regex(key: impl Into<String>, regex: &str) -> Result<Self> {
+        let regex = Regex::new(regex).with_context(|| format!("invalid regex: {regex:?}"))?;
+        Ok(Self::Matches(key.into(), regex))
+    }
+
+    /// Combine with `other` by logical AND.
+    pub fn and(self, other: Filter) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combine with `other` by logical OR.
+    pub fn or(self, other: Filter) -> Self {
+        Self::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Negate the filter.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+
+    /// Return true if key-value pairs in `info` satisfy the filter.
+    pub fn matches(&self, info: &Info) -> bool {
+        match self {
+            Self::Exists(key) => info.get(key).is_some(),
+            Self::Compare(key, op, value) => info.get(key).is_some_and(|v| op.eval(v, value)),
+            Self::Matches(key, regex) => info.get(key).and_then(|v| v.as_str()).is_some_and(|s| regex.is_match(s)),
+            Self::Not(f) => !f.matches(info),
+            Self::And(a, b) => a.matches(info) && b.matches(info),
+            Self::Or(a, b) => a.matches(info) || b.matches(info),
+        }
+    }
+
+    /// Return true if the `info` of `frame` satisfies the filter.
+    pub fn matches_frame(&self, frame: &Frame) -> bool {
+        self.matches(&frame.info)
+    }
+
+    /// Return true if the comment line of `frame` text in xyz/extxyz
+    /// format satisfies the filter. The atom lines are not parsed. A
+    /// comment line not in extxyz format has no key-value pairs.
+    pub fn matches_text(&self, frame: &str) -> bool {
+        let comment = frame.lines().nth(1).unwrap_or_default();
+        self.matches(&crate::parser::xyz::extxyz_info(comment).unwrap_or_default())
+    }
+}
+
+/// Return an iterator over frames in `frames` satisfying `filter`,
+/// such as those from `read_xyz_frames`. Only comment lines are parsed
+/// for filtering.
+pub fn filter_xyz_frames(frames: impl IntoIterator<Item = String>, filter: Filter) -> impl Iterator<Item = String> {
+    frames.into_iter().filter(move |frame| filter.matches_text(frame))
+}
+// a6d3f850 ends here
+
+// [[file:../extxyz.note::f1c86a2b][f1c86a2b]]
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-.:".contains(c)
+}
+
+fn is_value_char(c: char) -> bool {
+    !c.is_whitespace() && !"()\"&|<>=!".contains(c)
+}
+
+// Unquoted regex ends at whitespace or a `)` without matching `(`, so
+// it could contain groups, such as `^(bulk|slab)$`.
+fn plain_regex<'s>(input: &mut &'s str) -> PResult<&'s str> {
+    let mut depth = 0;
+    let mut escaped = false;
+    let n = input
+        .find(|c: char| {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '(' => depth += 1,
+                ')' if depth == 0 => return true,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            c.is_whitespace() || c == '"'
+        })
+        .unwrap_or(input.len());
+    take(n).verify(|s: &str| !s.is_empty()).parse_next(input)
+}
+
+fn key(input: &mut &str) -> PResult<String> {
+    alt((quoted_string, take_while(1.., is_key_char).map(String::from))).parse_next(input)
+}
+
+fn value(input: &mut &str) -> PResult<Value> {
+    alt((quoted_string.map(Value::String), take_while(1.., is_value_char).map(parse_plain_value))).parse_next(input)
+}
+
+fn regex_value(input: &mut &str) -> PResult<Regex> {
+    alt((quoted_string, plain_regex.map(String::from)))
+        .try_map(|s| Regex::new(&s))
+        .parse_next(input)
+}
+
+fn compare_op(input: &mut &str) -> PResult<CompareOp> {
+    alt((
+        "==".value(CompareOp::Eq),
+        "!=".value(CompareOp::Ne),
+        "<=".value(CompareOp::Le),
+        ">=".value(CompareOp::Ge),
+        "<".value(CompareOp::Lt),
+        ">".value(CompareOp::Gt),
+    ))
+    .parse_next(input)
+}
+
+// key op value, key =~ regex, or key alone
+fn term(input: &mut &str) -> PResult<Filter> {
+    let key = key.parse_next(input)?;
+    let matches = (delimited(multispace0, alt(("=~".value(true), "!~".value(false))), multispace0), regex_value);
+    if let Some((positive, regex)) = opt(matches).parse_next(input)? {
+        let filter = Filter::Matches(key, regex);
+        return Ok(if positive { filter } else { filter.not() });
+    }
+    let compare = (delimited(multispace0, compare_op, multispace0), value);
+    let filter = match opt(compare).parse_next(input)? {
+        Some((op, value)) => Filter::Compare(key, op, value),
+        None => Filter::Exists(key),
+    };
+    Ok(filter)
+}
+
+fn unary(input: &mut &str) -> PResult<Filter> {
+    alt((
+        preceded(("not", multispace1), unary).map(Filter::not),
+        preceded(("!", multispace0), unary).map(Filter::not),
+        delimited(("(", multispace0), or_expr, (multispace0, ")")),
+        term,
+    ))
+    .parse_next(input)
+}
+
+fn and_expr(input: &mut &str) -> PResult<Filter> {
+    let op = alt((delimited(multispace1, "and", multispace1), delimited(multispace0, "&&", multispace0)));
+    let filters: Vec<_> = separated(1.., unary, op).parse_next(input)?;
+    Ok(filters.into_iter().reduce(Filter::and).expect("at least one filter"))
+}
+
+fn or_expr(input: &mut &str) -> PResult<Filter> {
+    let op = alt((delimited(multispace1, "or", multispace1), delimited(multispace0, "||", multispace0)));
+    let filters: Vec<_> = separated(1.., and_expr, op).parse_next(input)?;
+    Ok(filters.into_iter().reduce(Filter::or).expect("at least one filter"))
+}
+
+impl std::str::FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        delimited(multispace0, or_expr, multispace0)
+            .parse(s)
+            .map_err(|e| anyhow!("invalid filter expression at column {}: {s:?}", e.offset() + 1))
+    }
+}
+// f1c86a2b ends here
+
+// [[file:../extxyz.note::7c40e5d9][7c40e5d9]]
+#[test]
+fn test_filter() -> Result<()> {
+    let info: Info = r#"energy=-120.5 config_type=bulk name="Si bulk" fixed nsteps=10"#.parse()?;
+    let check = |s: &str| -> Result<bool> { Ok(s.parse::<Filter>()?.matches(&info)) };
+    assert!(check("energy < -100 and config_type == bulk")?);
+    assert!(check("energy<-100&&config_type==bulk")?);
+    assert!(!check("energy >= -100")?);
+    assert!(check("nsteps == 10.0 and nsteps != 11")?);
+    assert!(check(r#"name == "Si bulk""#)?);
+    assert!(check("name =~ ^Si")?);
+    assert!(check(r"(config_type =~ ^(bulk|slab)\(?$) and name !~ (Cu)")?);
+    assert!(check(r#"name !~ "^Cu""#)?);
+    assert!(check("config_type > alpha")?);
+    assert!(check("fixed == T")?);
+    assert!(check("fixed and not stress")?);
+    assert!(check("!stress")?);
+    assert!(check("stress or energy < 0")?);
+    // predicate on missing key is false
+    assert!(!check("stress < 0")?);
+    assert!(!check("stress != 0")?);
+    // precedence and grouping
+    assert!(check("nsteps > 100 and fixed or energy < 0")?);
+    assert!(!check("nsteps > 100 and (fixed or energy < 0)")?);
+    assert!(check("not (nsteps > 100 or stress)")?);
+
+    // invalid expressions
+    assert!("energy <".parse::<Filter>().is_err());
+    assert!("energy < 1 and".parse::<Filter>().is_err());
+    assert!("(energy < 1".parse::<Filter>().is_err());
+    assert!("name =~ \"[\"".parse::<Filter>().is_err());
+
+    // built in code
+    let filter = Filter::compare("energy", CompareOp::Lt, -100).and(Filter::regex("config_type", "bulk|surface")?);
+    assert!(filter.matches(&info));
+
+    // filter frames by comment line
+    let frames = crate::read_xyz_frames_direct("tests/files/water.xyz")?;
+    let filter: Filter = "pi > 3".parse()?;
+    assert_eq!(filter_xyz_frames(frames, filter).count(), 1);
+
+    Ok(())
+}
+// 7c40e5d9 ends here
@@ -0,0 +1,234 @@
+// [[file:../extxyz.note::99f2154b][99f2154b]]
+use anyhow::*;
+use serde::{Deserialize, Serialize};
+
+use crate::options::Diagnostics;
+use crate::parser::xyz::split_xyz_frame;
+use crate::{Columns, Info, Lattice, ParseError, ParseErrorKind, ParseOptions, ParseWarning, RawAtoms};
+// 99f2154b ends here
+
+// [[file:../extxyz.note::6d4b1fa1][6d4b1fa1]]
+/// Represents an owned frame in xyz/extxyz format, with all data in
+/// comment line and atom lines parsed.
+///
+/// Unlike `RawAtoms`, `Frame` does not borrow from the frame text, so
+/// it can outlive the string returned by `read_xyz_frames` and be sent
+/// across threads.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Frame {
+    /// The number of atoms specified in the first line
+    pub natoms: usize,
+    /// Element symbols or numbers of atoms
+    pub symbols: Vec<String>,
+    /// The Cartesian coordinates of atoms
+    pub positions: Vec<[f64; 3]>,
+    /// Lattice vectors in rows, from the `Lattice` entry in comment line
+    pub lattice: Option<[[f64; 3]; 3]>,
+    /// Periodic boundary conditions, from the `pbc` entry in comment
+    /// line, which defaults to `T T T` if `Lattice` is present.
+    pub pbc: Option<[bool; 3]>,
+    /// The remaining key-value pairs in comment line. The `Lattice`,
+    /// `pbc` and `Properties` entries are removed.
+    pub info: Info,
+    /// Per-atom properties defined in `Properties` (excluding `species`
+    /// and `pos`) in typed columns, keyed by property name in declared
+    /// order.
+    pub arrays: Columns,
+}
+// 6d4b1fa1 ends here
+
+// [[file:../extxyz.note::40d9dd96][40d9dd96]]
+impl Frame {
+    /// Parse `Frame` from a complete frame `input` in xyz/extxyz format.
+    ///
+    /// This is `parse_with` in lenient mode with warnings ignored, so
+    /// invalid property columns or `Lattice` entry are skipped. Use
+    /// `parse_with` in strict mode to reject them. If the comment line is
+    /// not in extxyz format, it will be kept as it is in `info` with the
+    /// key `comment`.
+    pub fn parse(input: &str) -> Result<Self> {
+        Self::parse_with(input, &ParseOptions::lenient()).map(|(frame, _)| frame)
+    }
+
+    /// Construct `Frame` from parsed `atoms` in raw xyz format, in
+    /// lenient mode as `parse`.
+    pub fn from_raw_atoms(atoms: &RawAtoms) -> Result<Self> {
+        let options = ParseOptions::lenient();
+        let mut diag = Diagnostics::new(&options);
+        let extxyz = parse_comment(atoms.comment, &options, &mut diag)?;
+        Self::from_parts(atoms, extxyz, &mut diag)
+    }
+
+    /// Parse `Frame` from a complete frame `input` with `options`. See
+    /// `ParseOptions` for the checks in strict and lenient modes. In
+    /// lenient mode, an invalid comment line is kept as `comment`, and
+    /// invalid `Lattice` or `pbc` entries are ignored with warnings.
+    pub fn parse_with(input: &str, options: &ParseOptions) -> Result<(Self, Vec<ParseWarning>)> {
+        let (_, comment, _) = split_xyz_frame(input.trim_end())?;
+        // issues in comment line are reported after those in atom lines
+        let mut comment_diag = Diagnostics::new(options).with_input(input);
+        let extxyz = parse_comment(comment, options, &mut comment_diag)?;
+        // the atom lines are located with the parsed comment line
+        let (atoms, warnings) = RawAtoms::parse_with_info(input, extxyz.as_ref(), options)?;
+        let mut diag = Diagnostics::new(options).with_input(input);
+        diag.extend(warnings, None);
+        diag.extend(comment_diag.finish(), None);
+
+        let frame = Self::from_parts(&atoms, extxyz, &mut diag).map_err(|e| crate::error::with_source_line(e, input))?;
+        Ok((frame, diag.finish()))
+    }
+
+    // Construct `Frame` from `atoms` and `extxyz` info parsed from its
+    // comment line, with issues reported in `diag`.
+    fn from_parts(atoms: &RawAtoms, extxyz: Option<Info>, diag: &mut Diagnostics) -> Result<Self> {
+        let mut info = extxyz.unwrap_or_default();
+        if info.raw_map().is_empty() {
+            info.raw_map_mut().insert("comment".into(), atoms.comment.into());
+        }
+
+        let (arrays, warnings) = info.parse_atom_columns_with(&atoms.atoms, &diag.options())?;
+        diag.extend(warnings, None);
+        let lattice = info
+            .try_lattice()
+            .or_else(|e| diag.report(ParseErrorKind::InvalidLattice, Some(2), e.to_string()).map(|_| None))?;
+        // avoid reporting invalid `Lattice` twice for default `pbc`
+        let pbc = match info.get("pbc") {
+            Some(_) => info
+                .try_pbc()
+                .or_else(|e| diag.report(ParseErrorKind::InvalidLattice, Some(2), e.to_string()).map(|_| None))?,
+            None => lattice.map(|_| [true; 3]),
+        };
+        let _ = info.pop("Lattice");
+        let _ = info.pop("pbc");
+        let _ = info.pop("Properties");
+
+        let frame = Self {
+            natoms: atoms.natoms,
+            symbols: atoms.atoms.iter().map(|atom| atom.element.to_string()).collect(),
+            positions: atoms.atoms.iter().map(|atom| atom.position).collect(),
+            lattice,
+            pbc,
+            info,
+            arrays,
+        };
+        Ok(frame)
+    }
+
+    /// Return the periodic cell defined by `lattice`.
+    pub fn cell(&self) -> Option<Lattice> {
+        self.lattice.map(Lattice::new)
+    }
+}
+
+impl std::str::FromStr for Frame {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        Self::parse(input)
+    }
+}
+
+// Parse extxyz info from `comment` line (the second line in frame), or
+// return None if it is not in extxyz format.
+fn parse_comment(comment: &str, options: &ParseOptions, diag: &mut Diagnostics) -> Result<Option<Info>> {
+    use std::result::Result::Ok;
+
+    if !comment.contains('=') {
+        return Ok(None);
+    }
+    match Info::parse_with(comment, options) {
+        Ok((info, warnings)) => {
+            diag.extend(warnings, Some(2));
+            Ok(Some(info))
+        }
+        // locate the error in comment line of the frame
+        Err(e) => match e.downcast::<ParseError>() {
+            Ok(e) if options.is_strict() => Err(e.shift_lines(1).into()),
+            Ok(e) => diag.report(e.kind, Some(2), format!("{}: {} at column {}", e.kind, e.message, e.column)).map(|_| None),
+            Err(e) => diag.report(ParseErrorKind::InvalidComment, Some(2), format!("{e:#}")).map(|_| None),
+        },
+    }
+}
+// 40d9dd96 ends here
+
+// [[file:../extxyz.note::caccdd0a][caccdd0a]]
+#[test]
+fn test_frame_parse() -> Result<()> {
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:freeze:L:1 energy=-1.5 pbc="T T F"
+Si 0.0 0.0 0.0 0.1 0.2 0.3 T
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 F
+"#;
+    let frame = Frame::parse(input)?;
+    assert_eq!(frame.natoms, 2);
+    assert_eq!(frame.symbols, ["Si", "Si"]);
+    assert_eq!(frame.positions[1], [1.36; 3]);
+    assert_eq!(frame.lattice.unwrap()[1][1], 5.44);
+    assert!((frame.cell().unwrap().volume() - 5.44f64.powi(3)).abs() < 1e-8);
+    assert_eq!(frame.pbc, Some([true, true, false]));
+    assert_eq!(frame.info.get("energy").unwrap(), -1.5);
+    assert!(frame.info.get("Lattice").is_none());
+    assert!(frame.info.get("Properties").is_none());
+    assert_eq!(frame.arrays["forces"].as_real3().unwrap()[1][2], -0.3);
+    assert!(!frame.arrays["freeze"].as_logical().unwrap()[1]);
+
+    // element and positions located by `Properties`
+    let input = "2
+Properties=pos:R:3:Z:I:1:forces:R:3 energy=-1.0
+0.0 0.0 0.0 14 0.1 0.2 0.3
+1.36 1.36 1.36 8 -0.1 -0.2 -0.3
+";
+    let frame = Frame::parse(input)?;
+    assert_eq!(frame.symbols, ["Si", "O"]);
+    assert_eq!(frame.positions[1], [1.36; 3]);
+    assert_eq!(frame.arrays["Z"].as_integer().unwrap(), [14, 8]);
+    assert_eq!(frame.arrays["forces"].as_real3().unwrap()[1], [-0.1, -0.2, -0.3]);
+    assert_eq!(Frame::parse(&frame.to_extxyz()?)?, frame);
+
+    // plain xyz format
+    let input = "1\nhello world\nH 0.0 0.0 1.0";
+    let frame: Frame = input.parse()?;
+    assert_eq!(frame.info.get("comment").unwrap(), "hello world");
+    assert!(frame.lattice.is_none());
+    assert!(frame.arrays.is_empty());
+
+    // strict and lenient modes
+    let input = "3
+Lattice=\"5.44 0.0 0.0\" Properties=species:S:1:pos:R:3:q:R:1 e=1 e=2
+Si 0.0 0.0 0.0 0.1 junk
+Si 1.36 1.36 1.36 x
+";
+    let e = Frame::parse_with(input, &ParseOptions::strict()).unwrap_err();
+    assert_eq!(e.downcast_ref::<ParseError>().map(|e| (e.kind, e.line)), Some((ParseErrorKind::DuplicateKey, 2)));
+    let input_ = input.replace(" e=2", "");
+    let e = Frame::parse_with(&input_, &ParseOptions::strict()).unwrap_err();
+    let e = e.downcast::<ParseError>()?;
+    assert_eq!((e.kind, e.line), (ParseErrorKind::NatomsMismatch, 1));
+    let input_ = input_.replacen('3', "2", 1);
+    let e = Frame::parse_with(&input_, &ParseOptions::strict()).unwrap_err();
+    let e = e.downcast::<ParseError>()?;
+    assert_eq!((e.kind, e.line, e.source_line.as_str()), (ParseErrorKind::TrailingColumns, 3, "Si 0.0 0.0 0.0 0.1 junk"));
+    let (frame_, warnings) = Frame::parse_with(input, &ParseOptions::lenient())?;
+    assert_eq!(frame_.symbols.len(), 2);
+    assert!(frame_.arrays.is_empty());
+    assert!(frame_.lattice.is_none());
+    assert_eq!(frame_.info.get("e").unwrap(), 2);
+    let lines: Vec<_> = warnings.iter().map(|w| w.line).collect();
+    // natoms, duplicate key, trailing columns, invalid q, invalid lattice
+    assert_eq!(lines, [Some(1), Some(2), Some(3), Some(4), Some(2)]);
+    assert_eq!(warnings[4].kind, ParseErrorKind::InvalidLattice);
+    // the same as in lenient mode
+    assert_eq!(Frame::parse(input)?, frame_);
+
+    // error located in comment line
+    let e = Frame::parse_with("1\na=1 b=[1, T]\nH 0.0 0.0 0.0", &ParseOptions::strict()).unwrap_err();
+    let e = e.downcast::<ParseError>()?;
+    assert_eq!((e.line, e.column), (2, 7));
+
+    // owned frame can be sent across threads
+    let frame = std::thread::spawn(move || frame).join().unwrap();
+    assert_eq!(frame.symbols, ["H"]);
+
+    Ok(())
+}
+// caccdd0a ends here
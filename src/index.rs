@@ -0,0 +1,265 @@
+// [[file:../extxyz.note::f5de54c7][f5de54c7]]
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::result::Result::Ok;
+use std::time::UNIX_EPOCH;
+
+use anyhow::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Compression, ReadError, ReadErrorKind};
+// f5de54c7 ends here
+
+// [[file:../extxyz.note::d81bcfbb][d81bcfbb]]
+/// Represents a persistent index of frames in a trajectory file in
+/// xyz/extxyz format, for random access into huge trajectories.
+///
+/// # NOTE
+/// * The first line in `xyz` frame should be the real number of atom
+///   lines. That is, `VEC` atom line specifications are not supported.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FrameIndex {
+    /// The size of trajectory file in bytes when index was built
+    file_size: u64,
+    /// The modification time (seconds and nanoseconds since UNIX epoch)
+    /// of trajectory file when index was built
+    mtime: (u64, u32),
+    /// The byte offset and the number of atoms of each frame
+    frames: Vec<(u64, usize)>,
+}
+
+fn file_stamp(path: &Path) -> Result<(u64, (u64, u32))> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read metadata of {path:?}"))?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok((metadata.len(), (mtime.as_secs(), mtime.subsec_nanos())))
+}
+
+// Scan frames in `reader` by the number of atoms in the first line of
+// each frame.
+pub(crate) fn scan_frames<R: BufRead>(mut reader: R) -> Result<Vec<(u64, usize)>> {
+    let mut frames = vec![];
+    let mut offset = 0;
+    let mut line = vec![];
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            break;
+        }
+        let frame_offset = offset;
+        offset += n as u64;
+        let text = std::str::from_utf8(&line)?.trim();
+        // allow blank lines between frames or at the end
+        if text.is_empty() {
+            continue;
+        }
+        let natoms: usize = text
+            .parse()
+            .with_context(|| format!("invalid natoms line at byte offset {frame_offset}: {text:?}"))?;
+        // skip the comment line and atom lines
+        for _ in 0..natoms + 1 {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line)?;
+            ensure!(n > 0, "incomplete frame at byte offset {frame_offset}");
+            offset += n as u64;
+        }
+        frames.push((frame_offset, natoms));
+    }
+    Ok(frames)
+}
+
+impl FrameIndex {
+    /// Build frame index for trajectory file in `path`, by scanning the
+    /// whole file once.
+    pub fn build(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        ensure!(!Compression::detect(path)?.is_compressed(), "random access into compressed file {path:?} is not supported");
+        let (file_size, mtime) = file_stamp(path)?;
+        let f = File::open(path).with_context(|| format!("Failed to open file {path:?}"))?;
+        let frames = scan_frames(BufReader::new(f))?;
+        Ok(Self { file_size, mtime, frames })
+    }
+
+    /// Return the path of sidecar index file for trajectory in `path`.
+    /// For example, `traj.xyz.idx` for `traj.xyz`.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut p = path.as_ref().as_os_str().to_owned();
+        p.push(".idx");
+        p.into()
+    }
+
+    /// Load frame index from file in `index_path`.
+    pub fn load(index_path: impl AsRef<Path>) -> Result<Self> {
+        let index_path = index_path.as_ref();
+        let f = File::open(index_path).with_context(|| format!("Failed to open index file {index_path:?}"))?;
+        let index = serde_json::from_reader(BufReader::new(f))?;
+        Ok(index)
+    }
+
+    /// Save frame index into file in `index_path`.
+    pub fn save(&self, index_path: impl AsRef<Path>) -> Result<()> {
+        let index_path = index_path.as_ref();
+        let f = File::create(index_path).with_context(|| format!("Failed to create index file {index_path:?}"))?;
+        serde_json::to_writer(std::io::BufWriter::new(f), self)?;
+        Ok(())
+    }
+
+    /// Check if the index is up to date with trajectory file in `path`,
+    /// by comparing file size and modification time.
+    pub fn is_valid_for(&self, path: impl AsRef<Path>) -> bool {
+        file_stamp(path.as_ref()).map(|stamp| stamp == (self.file_size, self.mtime)).unwrap_or(false)
+    }
+
+    /// Open frame index for trajectory file in `path`. The sidecar
+    /// index file will be used if it is valid; otherwise the index
+    /// will be rebuilt and saved next to the trajectory file.
+    ///
+    /// # NOTE
+    /// * Failure in saving the sidecar index file is ignored, so that
+    ///   trajectory in read-only directory can still be read.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let index_path = Self::sidecar_path(path);
+        if let Ok(index) = Self::load(&index_path) {
+            if index.is_valid_for(path) {
+                return Ok(index);
+            }
+        }
+        let index = Self::build(path)?;
+        let _ = index.save(&index_path);
+        Ok(index)
+    }
+
+    /// Return the number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Return true if there is no frame.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Return the byte offset of frame `i`.
+    pub fn offset(&self, i: usize) -> Option<u64> {
+        self.frames.get(i).map(|x| x.0)
+    }
+
+    /// Return the number of atoms in frame `i`.
+    pub fn natoms(&self, i: usize) -> Option<usize> {
+        self.frames.get(i).map(|x| x.1)
+    }
+
+    /// Read the text of frame `i` from trajectory in `reader` directly.
+    pub fn read_frame<R: Read + Seek>(&self, reader: &mut R, i: usize) -> Result<String> {
+        let start = self.offset(i).with_context(|| format!("frame {i} out of range"))?;
+        Ok(self.read_frame_at(reader, i, start)?)
+    }
+
+    // Read the text of frame `i` starting at byte offset `start`
+    fn read_frame_at<R: Read + Seek>(&self, reader: &mut R, i: usize, start: u64) -> std::io::Result<String> {
+        let end = self.offset(i + 1).unwrap_or(self.file_size);
+        reader.seek(SeekFrom::Start(start))?;
+        let mut buf = String::new();
+        reader.take(end - start).read_to_string(&mut buf)?;
+        Ok(buf)
+    }
+}
+// d81bcfbb ends here
+
+// [[file:../extxyz.note::14fd3a86][14fd3a86]]
+/// Return an iterator that yields strings of the selected frames in the
+/// `xyz/extxyz` format from trajectory in `path`, using persistent
+/// frame index saved in sidecar file (see `FrameIndex::open`). Once the
+/// index is built, jumping to any frame costs no scan.
+///
+/// # NOTE
+/// * Unlike `read_xyz_frames_two_pass`, frames are delimited by the
+///   number of atoms in the first line, so `VEC` atom lines are not
+///   supported.
+/// * Reading stops at the first error, e.g. an out-of-range frame index
+///   in `selection`. Use `try_read_xyz_frames_indexed` to have errors
+///   reported.
+///
+/// # Parameters
+/// * `path`: path to the trajectory file
+/// * `selection`: an iterator over indices of selected frames
+pub fn read_xyz_frames_indexed(path: impl AsRef<Path>, selection: impl Iterator<Item = usize>) -> Result<impl Iterator<Item = String>> {
+    let frames = try_read_xyz_frames_indexed(path, selection)?;
+    Ok(frames.map_while(|x| x.ok()))
+}
+
+/// Return an iterator over the selected frames in trajectory in `path`,
+/// like `read_xyz_frames_indexed`, but reporting errors as `ReadError`
+/// (such as an out-of-range frame index) instead of silently stopping.
+/// The iterator stops after the first error.
+pub fn try_read_xyz_frames_indexed(
+    path: impl AsRef<Path>,
+    mut selection: impl Iterator<Item = usize>,
+) -> Result<impl Iterator<Item = std::result::Result<String, ReadError>>> {
+    let path = path.as_ref();
+    let index = FrameIndex::open(path)?;
+    let mut f = File::open(path).with_context(|| format!("Failed to open file {path:?}"))?;
+
+    let mut failed = false;
+    let frames = std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let j = selection.next()?;
+        let frame = match index.offset(j) {
+            Some(offset) => index.read_frame_at(&mut f, j, offset).map_err(|e| ReadError {
+                frame: j,
+                offset,
+                line: 0,
+                kind: ReadErrorKind::Io(e),
+            }),
+            None => Err(ReadError {
+                frame: j,
+                offset: index.file_size,
+                line: 0,
+                kind: ReadErrorKind::OutOfRange { nframes: index.len() },
+            }),
+        };
+        failed = frame.is_err();
+        Some(frame)
+    });
+    Ok(frames)
+}
+// 14fd3a86 ends here
+
+// [[file:../extxyz.note::e1fab731][e1fab731]]
+#[test]
+fn test_frame_index() -> Result<()> {
+    let dir = std::env::temp_dir().join("extxyz-test-index");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("water.xyz");
+    std::fs::copy("tests/files/water.xyz", &path)?;
+    let _ = std::fs::remove_file(FrameIndex::sidecar_path(&path));
+
+    let index = FrameIndex::open(&path)?;
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.natoms(1), Some(4));
+    assert!(FrameIndex::sidecar_path(&path).exists());
+    let index_ = FrameIndex::open(&path)?;
+    assert_eq!(index, index_);
+
+    let frames: Vec<_> = read_xyz_frames_indexed(&path, [2, 0].into_iter())?.collect();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].starts_with("3\nProperties=species:S:1:pos:R:3:Z:I:1:masses:R:1"));
+    assert!(frames[1].starts_with("3\nProperties=species:S:1:pos:R:3:some_label:L:2"));
+    let frames: Vec<_> = try_read_xyz_frames_indexed(&path, [1, 3, 0].into_iter())?.collect();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].is_ok());
+    assert!(matches!(frames[1].as_ref().unwrap_err().kind, ReadErrorKind::OutOfRange { nframes: 3 }));
+
+    // index will be rebuilt for modified file
+    std::fs::write(&path, "1\n\nH 0.0 0.0 0.0\n")?;
+    assert!(!index.is_valid_for(&path));
+    let index = FrameIndex::open(&path)?;
+    assert_eq!(index.len(), 1);
+
+    Ok(())
+}
+// e1fab731 ends here
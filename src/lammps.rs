@@ -0,0 +1,993 @@
+// [[file:../extxyz.note::3f8a1d6c][3f8a1d6c]]
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::*;
+
+use crate::element::atomic_number;
+use crate::trajectory::reader::TextReader;
+use crate::trajectory::try_read_frames_sequential_with;
+use crate::vasp::{comment_info, comment_line};
+use crate::writer::{format_column_row, format_real};
+use crate::{Column, Frame, PropertyValueType, ReadError, ReadErrorKind};
+// 3f8a1d6c ends here
+
+// [[file:../extxyz.note::a51c7e92][a51c7e92]]
+// The simulation box in LAMMPS, as lattice vectors in rows and the
+// origin. The origin is kept in `info` of frame if it is not zero.
+struct SimBox {
+    lattice: [[f64; 3]; 3],
+    origin: [f64; 3],
+}
+
+impl SimBox {
+    // Construct from bounds and tilt factors `xy`, `xz` and `yz`, as in
+    // LAMMPS data file
+    fn from_bounds(lo: [f64; 3], hi: [f64; 3], [xy, xz, yz]: [f64; 3]) -> Self {
+        let lattice = [[hi[0] - lo[0], 0.0, 0.0], [xy, hi[1] - lo[1], 0.0], [xz, yz, hi[2] - lo[2]]];
+        Self { lattice, origin: lo }
+    }
+
+    // Construct from bounding box of tilted box, as in LAMMPS dump file
+    fn from_dump_bounds(lo: [f64; 3], hi: [f64; 3], tilt: [f64; 3]) -> Self {
+        let [xy, xz, yz] = tilt;
+        let lo = [lo[0] - 0f64.min(xy).min(xz).min(xy + xz), lo[1] - 0f64.min(yz), lo[2]];
+        let hi = [hi[0] - 0f64.max(xy).max(xz).max(xy + xz), hi[1] - 0f64.max(yz), hi[2]];
+        Self::from_bounds(lo, hi, tilt)
+    }
+
+    fn from_frame(frame: &Frame) -> Option<Self> {
+        let origin = frame.info.get("origin").and_then(|v| serde_json::from_value(v.clone()).ok());
+        Some(Self {
+            lattice: frame.lattice?,
+            origin: origin.unwrap_or_default(),
+        })
+    }
+
+    // Return bounds and tilt factors for restricted triclinic box, or
+    // None for general triclinic box.
+    fn bounds(&self) -> Option<([f64; 3], [f64; 3], [f64; 3])> {
+        const EPS: f64 = 1e-10;
+
+        let [a, b, c] = self.lattice;
+        if a[1].abs() > EPS || a[2].abs() > EPS || b[2].abs() > EPS || a[0] <= 0.0 || b[1] <= 0.0 || c[2] <= 0.0 {
+            return None;
+        }
+        let lo = self.origin;
+        let hi = [lo[0] + a[0], lo[1] + b[1], lo[2] + c[2]];
+        Some((lo, hi, [b[0], c[0], c[1]]))
+    }
+
+    // Put lattice and origin into `frame`
+    fn apply(&self, frame: &mut Frame) {
+        frame.lattice = Some(self.lattice);
+        if self.origin != [0.0; 3] {
+            frame.info.raw_map_mut().insert("origin".into(), self.origin.to_vec().into());
+        }
+    }
+}
+
+// The D format code for exponent is also supported
+fn parse_real(s: &str) -> Result<f64> {
+    s.parse()
+        .or_else(|_| s.replace(['D', 'd'], "E").parse())
+        .with_context(|| format!("invalid real value: {s:?}"))
+}
+
+fn parse_reals(line: &str) -> Result<Vec<f64>> {
+    line.split_whitespace().map(parse_real).collect()
+}
+
+fn next_line<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<&'a str> {
+    lines.next().context("unexpected end of input")
+}
+
+// Atom types with element symbols, sorted by types
+type TypeLabels = Vec<(i64, String)>;
+
+// The atom types and type labels, from `type` property or assigned to
+// element symbols in the order of first appearance
+fn atom_types(frame: &Frame) -> Result<(Vec<i64>, TypeLabels)> {
+    let types = match frame.arrays.get("type") {
+        Some(column) => column.as_integer().context("invalid type of property type")?.to_vec(),
+        None => {
+            let mut symbols: Vec<&str> = vec![];
+            let mut types = vec![];
+            for s in &frame.symbols {
+                let t = match symbols.iter().position(|x| x == s) {
+                    Some(i) => i + 1,
+                    None => {
+                        symbols.push(s);
+                        symbols.len()
+                    }
+                };
+                types.push(t as i64);
+            }
+            types
+        }
+    };
+    ensure!(types.len() == frame.symbols.len(), "inconsistent number of atoms in property type");
+    let mut labels: TypeLabels = vec![];
+    for (&t, s) in types.iter().zip(&frame.symbols) {
+        if !labels.iter().any(|(x, _)| *x == t) {
+            labels.push((t, s.clone()));
+        }
+    }
+    labels.sort_by_key(|(t, _)| *t);
+    Ok((types, labels))
+}
+
+fn format_reals(values: &[f64]) -> String {
+    values.iter().map(|&x| format_real(x)).collect::<Vec<_>>().join(" ")
+}
+// a51c7e92 ends here
+
+// [[file:../extxyz.note::60d4b8f3][60d4b8f3]]
+// Columns in dump file grouped into per-atom properties
+const TRIPLES: [(&str, [&str; 3]); 3] = [("forces", ["fx", "fy", "fz"]), ("velocities", ["vx", "vy", "vz"]), ("image", ["ix", "iy", "iz"])];
+
+// Columns in dump file renamed into per-atom properties, as in data file
+const SINGLES: [(&str, &str); 1] = [("masses", "mass")];
+
+// Columns in dump file for positions, and if they are scaled
+const POSITIONS: [([&str; 3], bool); 4] = [
+    (["x", "y", "z"], false),
+    (["xu", "yu", "zu"], false),
+    (["xs", "ys", "zs"], true),
+    (["xsu", "ysu", "zsu"], true),
+];
+
+// Columns parsed as integers
+const INTEGERS: [&str; 6] = ["id", "mol", "type", "proc", "procp1", "image"];
+
+fn parse_dump_box<'a>(flags: &str, lines: &mut impl Iterator<Item = &'a str>) -> Result<(SimBox, [bool; 3])> {
+    let flags: Vec<&str> = flags.split_whitespace().collect();
+    let rows = (0..3).map(|_| parse_reals(next_line(lines)?)).collect::<Result<Vec<_>>>()?;
+    let general = flags.contains(&"abc");
+    let n = if general { 4 } else if flags.contains(&"xy") { 3 } else { 2 };
+    ensure!(rows.iter().all(|row| row.len() >= n), "expect {n} numbers for box bounds");
+    let col = |k: usize| [rows[0][k], rows[1][k], rows[2][k]];
+    let simbox = if general {
+        let lattice = [0, 1, 2].map(|i| [rows[i][0], rows[i][1], rows[i][2]]);
+        SimBox { lattice, origin: col(3) }
+    } else {
+        let tilt = if n == 3 { col(2) } else { [0.0; 3] };
+        SimBox::from_dump_bounds(col(0), col(1), tilt)
+    };
+    // boundary flags, such as `pp pp ff`
+    let boundary: Vec<_> = flags.iter().filter(|s| s.len() == 2 && s.chars().all(|c| "pfsm".contains(c))).collect();
+    let pbc = if boundary.len() == 3 { [0, 1, 2].map(|i| *boundary[i] == "pp") } else { [true; 3] };
+    Ok((simbox, pbc))
+}
+
+// Group columns `names` with indices into properties, such as `fx fy
+// fz` into `forces` and `c_s[1] c_s[2]` into `c_s`
+fn group_columns(names: &[(usize, &str)]) -> Vec<(String, Vec<usize>)> {
+    let mut groups = vec![];
+    let mut i = 0;
+    while i < names.len() {
+        let rest: Vec<&str> = names[i..].iter().map(|(_, s)| *s).collect();
+        let n = if let Some((name, _)) = TRIPLES.iter().find(|(_, t)| rest.starts_with(t)) {
+            groups.push((name.to_string(), 3));
+            3
+        } else if let Some(prefix) = rest[0].strip_suffix("[1]") {
+            let n = rest.iter().enumerate().take_while(|(k, s)| **s == format!("{prefix}[{}]", k + 1)).count();
+            groups.push((prefix.to_string(), n));
+            n
+        } else {
+            let name = SINGLES.iter().find(|(_, s)| *s == rest[0]).map_or(rest[0], |(name, _)| name);
+            groups.push((name.to_string(), 1));
+            1
+        };
+        i += n;
+    }
+    let mut k = 0;
+    groups
+        .into_iter()
+        .map(|(name, n)| {
+            let indices = names[k..k + n].iter().map(|(i, _)| *i).collect();
+            k += n;
+            (name, indices)
+        })
+        .collect()
+}
+
+// Parse atom lines `rows` for columns `names` in dump file
+fn parse_dump_atoms(frame: &mut Frame, names: &[&str], rows: &[Vec<&str>], scale: &SimBox) -> Result<()> {
+    let n = rows.len();
+    let find = |name: &str| names.iter().position(|s| *s == name);
+    let mut used = vec![false; names.len()];
+
+    let (columns, scaled) = POSITIONS
+        .iter()
+        .find_map(|(xyz, scaled)| Some(([find(xyz[0])?, find(xyz[1])?, find(xyz[2])?], *scaled)))
+        .context("missing columns for positions")?;
+    let lattice = crate::Lattice::new(scale.lattice);
+    for row in rows {
+        let [x, y, z] = columns.map(|k| parse_real(row[k]));
+        let v = [x?, y?, z?];
+        let position = if scaled {
+            let p = lattice.to_cart(v);
+            [0, 1, 2].map(|k| p[k] + scale.origin[k])
+        } else {
+            v
+        };
+        frame.positions.push(position);
+    }
+    columns.iter().for_each(|&k| used[k] = true);
+
+    // element symbols, or atom types if missing
+    if let Some(k) = find("element") {
+        used[k] = true;
+    }
+    let k = find("element").or_else(|| find("type")).context("missing element or type column")?;
+    frame.symbols = rows.iter().map(|row| row[k].to_string()).collect();
+
+    let names: Vec<(usize, &str)> = names.iter().copied().enumerate().filter(|(i, _)| !used[*i]).collect();
+    for (name, indices) in group_columns(&names) {
+        let values = || rows.iter().flat_map(|row| indices.iter().map(|&k| row[k]));
+        let t = if INTEGERS.contains(&name.as_str()) && values().all(|s| s.parse::<i64>().is_ok()) {
+            PropertyValueType::Integer
+        } else if values().all(|s| parse_real(s).is_ok()) {
+            PropertyValueType::Real
+        } else {
+            PropertyValueType::String
+        };
+        let mut column = Column::new(t, indices.len(), n);
+        for row in rows {
+            let mut tokens = indices.iter().map(|&k| row[k]);
+            column.push_tokens(&mut tokens)?;
+        }
+        frame.arrays.insert(name, column);
+    }
+    Ok(())
+}
+
+// The dump column names for property `name`
+fn dump_column_names(name: &str, column: &Column) -> Vec<String> {
+    match column.width() {
+        1 => {
+            let name = SINGLES.iter().find(|(x, _)| *x == name).map_or(name, |(_, s)| s);
+            vec![name.to_string()]
+        }
+        3 if let Some((_, t)) = TRIPLES.iter().find(|(x, _)| *x == name) => t.iter().map(|s| s.to_string()).collect(),
+        w => (1..=w).map(|k| format!("{name}[{k}]")).collect(),
+    }
+}
+
+impl Frame {
+    /// Parse `Frame` from text `s` of one frame in LAMMPS dump format,
+    /// such as written by `dump custom` or `dump atom`.
+    ///
+    /// The box bounds and tilt factors (or general triclinic box) are
+    /// mapped to `lattice` and `pbc`, and the box origin is kept as
+    /// `origin` in `info` if not zero. The timestep, time and units are
+    /// kept as `timestep`, `time` and `units` in `info`.
+    ///
+    /// Element symbols are taken from `element` column, or atom types if
+    /// missing. Positions are taken from `x y z`, `xu yu zu`, `xs ys zs`
+    /// or `xsu ysu zsu` columns, in order of preference. Other columns
+    /// are kept as per-atom properties, with `fx fy fz`, `vx vy vz` and
+    /// `ix iy iz` grouped into `forces`, `velocities` and `image`, `mass`
+    /// renamed into `masses` as in `from_lammps_data`, and vector columns
+    /// `c_name[1] c_name[2] ...` grouped into `c_name`.
+    /// Atoms are kept in order as in the dump, which is not necessarily
+    /// sorted by `id`.
+    pub fn from_lammps_dump(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let mut frame = Frame::default();
+        let mut natoms = None;
+        let mut simbox = None;
+        let mut names = None;
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let item = line.strip_prefix("ITEM:").with_context(|| format!("expect ITEM line: {line:?}"))?.trim();
+            let info = frame.info.raw_map_mut();
+            if item == "TIMESTEP" {
+                let s = next_line(&mut lines)?.trim();
+                info.insert("timestep".into(), s.parse::<i64>().with_context(|| format!("invalid timestep: {s:?}"))?.into());
+            } else if item == "TIME" {
+                info.insert("time".into(), parse_real(next_line(&mut lines)?.trim())?.into());
+            } else if item == "UNITS" {
+                info.insert("units".into(), next_line(&mut lines)?.trim().into());
+            } else if item == "NUMBER OF ATOMS" {
+                let s = next_line(&mut lines)?.trim();
+                natoms = Some(s.parse::<usize>().with_context(|| format!("invalid number of atoms: {s:?}"))?);
+            } else if let Some(flags) = item.strip_prefix("BOX BOUNDS") {
+                simbox = Some(parse_dump_box(flags, &mut lines).context("invalid box bounds")?);
+            } else if let Some(s) = item.strip_prefix("ATOMS") {
+                names = Some(s.split_whitespace().collect::<Vec<_>>());
+                break;
+            } else {
+                bail!("unsupported item in LAMMPS dump: {line:?}");
+            }
+        }
+        let natoms = natoms.context("missing number of atoms")?;
+        let (simbox, pbc) = simbox.context("missing box bounds")?;
+        let names = names.context("missing atom columns")?;
+        let rows: Vec<Vec<&str>> = lines.take(natoms).map(|line| line.split_whitespace().collect()).collect();
+        ensure!(rows.len() == natoms, "expect {natoms} atoms, but found {}", rows.len());
+        for row in &rows {
+            ensure!(row.len() == names.len(), "expect {} columns in atom line: {:?}", names.len(), row.join(" "));
+        }
+
+        parse_dump_atoms(&mut frame, &names, &rows, &simbox)?;
+        frame.natoms = natoms;
+        simbox.apply(&mut frame);
+        frame.pbc = Some(pbc);
+        Ok(frame)
+    }
+
+    /// Format `Frame` as text in LAMMPS dump format, with columns `id
+    /// type element x y z` followed by per-atom properties. This is the
+    /// reverse of `from_lammps_dump`.
+    ///
+    /// The timestep is taken from `timestep` in `info`, or zero if
+    /// missing. Atom ids and types are taken from `id` and `type`
+    /// properties, or assigned in order. A frame without lattice is
+    /// written in the bounding box of atoms, with non-periodic boundary.
+    /// Other entries in `info` are ignored.
+    pub fn to_lammps_dump(&self) -> Result<String> {
+        let timestep = self.info.get("timestep").and_then(|v| v.as_i64()).unwrap_or(0);
+        self.format_lammps_dump(timestep)
+    }
+
+    fn format_lammps_dump(&self, timestep: i64) -> Result<String> {
+        let n = self.symbols.len();
+        ensure!(self.positions.len() == n, "inconsistent number of positions");
+        let (simbox, pbc) = match SimBox::from_frame(self) {
+            Some(simbox) => (simbox, self.pbc.unwrap_or([true; 3])),
+            None => {
+                let lo = [0, 1, 2].map(|k| self.positions.iter().map(|p| p[k]).fold(f64::INFINITY, f64::min));
+                let hi = [0, 1, 2].map(|k| self.positions.iter().map(|p| p[k]).fold(f64::NEG_INFINITY, f64::max));
+                let (lo, hi) = if n == 0 { ([0.0; 3], [0.0; 3]) } else { (lo, hi) };
+                (SimBox::from_bounds(lo, hi, [0.0; 3]), [false; 3])
+            }
+        };
+        let boundary = pbc.map(|p| if p { "pp" } else { "ff" }).join(" ");
+
+        let mut s = format!("ITEM: TIMESTEP\n{timestep}\n");
+        if let Some(t) = self.info.get("time").and_then(|v| v.as_f64()) {
+            s.push_str(&format!("ITEM: TIME\n{}\n", format_real(t)));
+        }
+        if let Some(units) = self.info.get("units").and_then(|v| v.as_str()) {
+            s.push_str(&format!("ITEM: UNITS\n{units}\n"));
+        }
+        s.push_str(&format!("ITEM: NUMBER OF ATOMS\n{n}\n"));
+        match simbox.bounds() {
+            Some((lo, hi, [0.0, 0.0, 0.0])) => {
+                s.push_str(&format!("ITEM: BOX BOUNDS {boundary}\n"));
+                for k in 0..3 {
+                    s.push_str(&format!("{}\n", format_reals(&[lo[k], hi[k]])));
+                }
+            }
+            Some((lo, hi, [xy, xz, yz])) => {
+                let lo = [lo[0] + 0f64.min(xy).min(xz).min(xy + xz), lo[1] + 0f64.min(yz), lo[2]];
+                let hi = [hi[0] + 0f64.max(xy).max(xz).max(xy + xz), hi[1] + 0f64.max(yz), hi[2]];
+                s.push_str(&format!("ITEM: BOX BOUNDS xy xz yz {boundary}\n"));
+                for (k, tilt) in [xy, xz, yz].into_iter().enumerate() {
+                    s.push_str(&format!("{}\n", format_reals(&[lo[k], hi[k], tilt])));
+                }
+            }
+            None => {
+                s.push_str(&format!("ITEM: BOX BOUNDS abc origin {boundary}\n"));
+                for (v, o) in simbox.lattice.iter().zip(simbox.origin) {
+                    s.push_str(&format!("{}\n", format_reals(&[v[0], v[1], v[2], o])));
+                }
+            }
+        }
+
+        let ids: Vec<i64> = match self.arrays.get("id") {
+            Some(column) => column.as_integer().context("invalid type of property id")?.to_vec(),
+            None => (1..=n as i64).collect(),
+        };
+        let (types, _) = atom_types(self)?;
+        let mut names: Vec<String> = ["id", "type", "element", "x", "y", "z"].map(String::from).to_vec();
+        let mut columns = vec![];
+        for (name, column) in &self.arrays {
+            if name == "id" || name == "type" {
+                continue;
+            }
+            ensure!(column.len() == n, "inconsistent number of atoms in property {name:?}");
+            names.extend(dump_column_names(name, column));
+            columns.push(column);
+        }
+        s.push_str(&format!("ITEM: ATOMS {}\n", names.join(" ")));
+        for i in 0..n {
+            let mut row = vec![ids[i].to_string(), types[i].to_string(), self.symbols[i].clone(), format_reals(&self.positions[i])];
+            for column in &columns {
+                row.extend(format_column_row(column, i));
+            }
+            s.push_str(&row.join(" "));
+            s.push('\n');
+        }
+        Ok(s)
+    }
+}
+// 60d4b8f3 ends here
+
+// [[file:../extxyz.note::c2e97a05][c2e97a05]]
+// A section in LAMMPS data file, such as `Atoms # full`
+struct Section<'a> {
+    name: &'a str,
+    // the comment in section line, such as atom style
+    hint: &'a str,
+    rows: Vec<&'a str>,
+}
+
+// Split `line` into content and comment after `#`
+fn split_comment(line: &str) -> (&str, &str) {
+    match line.split_once('#') {
+        Some((a, b)) => (a.trim(), b.trim()),
+        None => (line.trim(), ""),
+    }
+}
+
+// The columns in `Atoms` section for atom `style`, excluding optional
+// image flags
+fn atom_style_columns(style: &str) -> Option<&'static [&'static str]> {
+    let columns: &[&str] = match style {
+        "atomic" => &["id", "type", "x", "y", "z"],
+        "charge" => &["id", "type", "q", "x", "y", "z"],
+        "molecular" | "bond" | "angle" => &["id", "mol", "type", "x", "y", "z"],
+        "full" => &["id", "mol", "type", "q", "x", "y", "z"],
+        _ => return None,
+    };
+    Some(columns)
+}
+
+impl Frame {
+    /// Parse `Frame` from text `s` in LAMMPS data format, as read by
+    /// `read_data` command.
+    ///
+    /// The box bounds and tilt factors (or general triclinic box) are
+    /// mapped to `lattice`, and the box origin is kept as `origin` in
+    /// `info` if not zero. The title line is parsed as key-value pairs if
+    /// in extxyz format, and kept as `comment` otherwise.
+    ///
+    /// Atoms in `atomic`, `charge`, `molecular`, `bond`, `angle` or `full`
+    /// style are supported, which is taken from the comment of `Atoms`
+    /// section, or guessed from the number of columns if unambiguous.
+    /// Element symbols are taken from `Atom Type Labels`, comments in
+    /// `Masses`, or atom types if missing. Atom types could be given as
+    /// type labels in `Masses` and `Atoms` sections. The `id`, `type`,
+    /// `mol`, `q`, image flags, velocities and masses are kept as per-atom
+    /// properties `id`, `type`, `mol`, `q`, `image`, `velocities` and
+    /// `masses`. Other sections, such as bonds and force field
+    /// coefficients, are ignored.
+    pub fn from_lammps_data(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let title = lines.next().context("empty LAMMPS data file")?.trim();
+
+        // header entries and sections
+        let mut header: HashMap<String, Vec<f64>> = HashMap::new();
+        let mut sections: Vec<Section> = vec![];
+        for line in lines {
+            let (content, hint) = split_comment(line);
+            if content.is_empty() {
+                continue;
+            }
+            let tokens: Vec<&str> = content.split_whitespace().collect();
+            let k = tokens.iter().take_while(|s| parse_real(s).is_ok()).count();
+            // a row could start with type label, such as `O 15.999`
+            let is_row = !sections.is_empty() && tokens.get(1).is_some_and(|s| parse_real(s).is_ok());
+            if k == 0 && !is_row {
+                sections.push(Section { name: content, hint, rows: vec![] });
+            } else if let Some(section) = sections.last_mut() {
+                section.rows.push(line);
+            } else {
+                let values = tokens[..k].iter().map(|s| parse_real(s)).collect::<Result<_>>()?;
+                header.insert(tokens[k..].join(" "), values);
+            }
+        }
+        let get = |key: &str, n: usize| -> Result<Option<Vec<f64>>> {
+            match header.get(key) {
+                Some(v) if v.len() == n => Ok(Some(v.clone())),
+                Some(_) => bail!("expect {n} numbers for {key:?}"),
+                None => Ok(None),
+            }
+        };
+        let simbox = match get("avec", 3)? {
+            Some(a) => {
+                let b = get("bvec", 3)?.context("missing bvec")?;
+                let c = get("cvec", 3)?.context("missing cvec")?;
+                let o = get("abc origin", 3)?.unwrap_or(vec![0.0; 3]);
+                let v = |x: Vec<f64>| [x[0], x[1], x[2]];
+                SimBox { lattice: [v(a), v(b), v(c)], origin: v(o) }
+            }
+            None => {
+                let x = get("xlo xhi", 2)?.context("missing xlo xhi")?;
+                let y = get("ylo yhi", 2)?.context("missing ylo yhi")?;
+                let z = get("zlo zhi", 2)?.context("missing zlo zhi")?;
+                let tilt = get("xy xz yz", 3)?.unwrap_or(vec![0.0; 3]);
+                SimBox::from_bounds([x[0], y[0], z[0]], [x[1], y[1], z[1]], [tilt[0], tilt[1], tilt[2]])
+            }
+        };
+
+        // element symbols for atom types
+        let mut labels: HashMap<i64, String> = HashMap::new();
+        for section in sections.iter().filter(|s| s.name == "Atom Type Labels") {
+            for row in &section.rows {
+                let tokens: Vec<&str> = split_comment(row).0.split_whitespace().collect();
+                let t = tokens[0].parse::<i64>().with_context(|| format!("invalid atom type: {row:?}"))?;
+                let label = tokens.get(1).with_context(|| format!("missing type label: {row:?}"))?;
+                labels.insert(t, label.to_string());
+            }
+        }
+        // atom type from number or type label
+        let type_labels = labels.clone();
+        let atom_type = move |s: &str| -> Result<i64> {
+            s.parse::<i64>().or_else(|_| {
+                type_labels
+                    .iter()
+                    .find_map(|(t, label)| (label == s).then_some(*t))
+                    .with_context(|| format!("invalid atom type or undefined type label: {s:?}"))
+            })
+        };
+        let mut masses: HashMap<i64, f64> = HashMap::new();
+        for section in sections.iter().filter(|s| s.name == "Masses") {
+            for row in &section.rows {
+                let (content, comment) = split_comment(row);
+                let tokens: Vec<&str> = content.split_whitespace().collect();
+                let t = atom_type(tokens[0]).with_context(|| format!("invalid atom type: {row:?}"))?;
+                let m = tokens.get(1).with_context(|| format!("missing mass: {row:?}"))?;
+                masses.insert(t, parse_real(m)?);
+                let symbol = comment.split_whitespace().next().filter(|s| atomic_number(s).is_some());
+                if let Some(symbol) = symbol {
+                    labels.entry(t).or_insert(symbol.to_string());
+                }
+            }
+        }
+
+        let atoms = sections.iter().find(|s| s.name == "Atoms").context("missing Atoms section")?;
+        let ncols = atoms.rows.first().map(|row| split_comment(row).0.split_whitespace().count()).unwrap_or(5);
+        let names = match atoms.hint.split_whitespace().next() {
+            Some(style) => atom_style_columns(style).with_context(|| format!("unsupported atom style: {style}"))?,
+            // `charge` and `molecular` styles have the same number of columns
+            None => match ncols {
+                5 | 8 => atom_style_columns("atomic").unwrap(),
+                6 | 9 => bail!("atom style required in comment of Atoms section for {ncols} columns, such as `Atoms # charge`"),
+                7 | 10 => atom_style_columns("full").unwrap(),
+                _ => bail!("unknown atom style with {ncols} columns"),
+            },
+        };
+        if let Some(n) = get("atoms", 1)? {
+            ensure!(atoms.rows.len() == n[0] as usize, "expect {} atoms, but found {}", n[0], atoms.rows.len());
+        }
+
+        let mut frame = Frame::default();
+        let mut columns: Vec<Column> = names
+            .iter()
+            .map(|&name| match name {
+                "q" => Column::new(PropertyValueType::Real, 1, atoms.rows.len()),
+                _ => Column::new(PropertyValueType::Integer, 1, atoms.rows.len()),
+            })
+            .collect();
+        let mut image = Column::new(PropertyValueType::Integer, 3, atoms.rows.len());
+        let kt = names.iter().position(|&name| name == "type").expect("type column");
+        for row in &atoms.rows {
+            let mut tokens: Vec<&str> = split_comment(row).0.split_whitespace().collect();
+            ensure!(tokens.len() == names.len() || tokens.len() == names.len() + 3, "invalid atom line: {row:?}");
+            let t = atom_type(tokens[kt]).with_context(|| format!("invalid atom line: {row:?}"))?.to_string();
+            tokens[kt] = &t;
+            let mut position = [0.0; 3];
+            for (k, &name) in names.iter().enumerate() {
+                match name {
+                    "x" => position[0] = parse_real(tokens[k])?,
+                    "y" => position[1] = parse_real(tokens[k])?,
+                    "z" => position[2] = parse_real(tokens[k])?,
+                    _ => columns[k].push_tokens(&mut tokens[k..].iter().copied())?,
+                }
+            }
+            frame.positions.push(position);
+            if tokens.len() > names.len() {
+                image.push_tokens(&mut tokens[names.len()..].iter().copied())?;
+            }
+        }
+        for (&name, column) in names.iter().zip(columns) {
+            if !["x", "y", "z"].contains(&name) {
+                frame.arrays.insert(name.into(), column);
+            }
+        }
+        if !image.is_empty() {
+            ensure!(image.len() == frame.positions.len(), "missing image flags in some atom lines");
+            frame.arrays.insert("image".into(), image);
+        }
+
+        let types = frame.arrays["type"].as_integer().expect("integer types").to_vec();
+        frame.symbols = types.iter().map(|t| labels.get(t).cloned().unwrap_or_else(|| t.to_string())).collect();
+        if !masses.is_empty() {
+            let m = types.iter().map(|t| masses.get(t).copied().with_context(|| format!("missing mass for atom type {t}")));
+            frame.arrays.insert("masses".into(), Column::Real(m.collect::<Result<_>>()?));
+        }
+
+        // velocities in the order of atom ids
+        if let Some(section) = sections.iter().find(|s| s.name == "Velocities") {
+            let ids = frame.arrays["id"].as_integer().expect("integer ids");
+            let index: HashMap<i64, usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+            let mut velocities = vec![[0.0; 3]; ids.len()];
+            for row in &section.rows {
+                let v = parse_reals(split_comment(row).0)?;
+                ensure!(v.len() >= 4, "invalid velocities line: {row:?}");
+                let i = index.get(&(v[0] as i64)).with_context(|| format!("invalid atom id in velocities: {row:?}"))?;
+                velocities[*i] = [v[1], v[2], v[3]];
+            }
+            frame.arrays.insert("velocities".into(), Column::Real3(velocities));
+        }
+
+        frame.natoms = frame.positions.len();
+        frame.info = comment_info(title);
+        simbox.apply(&mut frame);
+        frame.pbc = Some([true; 3]);
+        Ok(frame)
+    }
+
+    /// Format `Frame` as text in LAMMPS data format. This is the reverse
+    /// of `from_lammps_data`.
+    ///
+    /// The atom style is `full`, `charge`, `molecular` or `atomic`,
+    /// depending on the presence of `mol` and `q` properties. Atom ids and
+    /// types are taken from `id` and `type` properties, or assigned in
+    /// order, and element symbols are written in `Atom Type Labels`.
+    /// Image flags, masses and velocities are written from `image`,
+    /// `masses` and `velocities` properties if present. The `info` is
+    /// formatted in the title line.
+    pub fn to_lammps_data(&self) -> Result<String> {
+        let n = self.symbols.len();
+        ensure!(self.positions.len() == n, "inconsistent number of positions");
+        let simbox = SimBox::from_frame(self).context("LAMMPS data file requires lattice")?;
+        let (types, labels) = atom_types(self)?;
+        let integers = |name: &str| -> Result<Option<&[i64]>> {
+            match self.arrays.get(name) {
+                Some(column) => {
+                    let v = column.as_integer().with_context(|| format!("invalid type of property {name}"))?;
+                    ensure!(v.len() == n, "inconsistent number of atoms in property {name:?}");
+                    Ok(Some(v))
+                }
+                None => Ok(None),
+            }
+        };
+        let ids: Vec<i64> = integers("id")?.map(|x| x.to_vec()).unwrap_or_else(|| (1..=n as i64).collect());
+        let mol = integers("mol")?;
+        let charges = match self.arrays.get("q") {
+            Some(column) => Some(column.as_real().context("invalid type of property q")?),
+            None => None,
+        };
+        let style = match (mol, charges) {
+            (Some(_), Some(_)) => "full",
+            (None, Some(_)) => "charge",
+            (Some(_), None) => "molecular",
+            (None, None) => "atomic",
+        };
+
+        let mut s = comment_line(self, "LAMMPS data file") + "\n\n";
+        s.push_str(&format!("{n} atoms\n"));
+        s.push_str(&format!("{} atom types\n\n", labels.last().map(|(t, _)| *t).unwrap_or(0)));
+        match simbox.bounds() {
+            Some((lo, hi, tilt)) => {
+                for (k, x) in ["x", "y", "z"].iter().enumerate() {
+                    s.push_str(&format!("{} {x}lo {x}hi\n", format_reals(&[lo[k], hi[k]])));
+                }
+                if tilt != [0.0; 3] {
+                    s.push_str(&format!("{} xy xz yz\n", format_reals(&tilt)));
+                }
+            }
+            None => {
+                for (v, key) in simbox.lattice.iter().zip(["avec", "bvec", "cvec"]) {
+                    s.push_str(&format!("{} {key}\n", format_reals(v)));
+                }
+                s.push_str(&format!("{} abc origin\n", format_reals(&simbox.origin)));
+            }
+        }
+
+        // type labels should not start with a digit
+        let valid_label = |s: &str| !s.is_empty() && !s.starts_with(|c: char| c.is_ascii_digit()) && !s.contains(char::is_whitespace);
+        if labels.iter().all(|(_, s)| valid_label(s)) {
+            s.push_str("\nAtom Type Labels\n\n");
+            for (t, label) in &labels {
+                s.push_str(&format!("{t} {label}\n"));
+            }
+        }
+        if let Some(column) = self.arrays.get("masses") {
+            let masses = column.as_real().context("invalid type of property masses")?;
+            ensure!(masses.len() == n, "inconsistent number of atoms in property masses");
+            s.push_str("\nMasses\n\n");
+            for (t, _) in &labels {
+                let i = types.iter().position(|x| x == t).expect("atom type");
+                s.push_str(&format!("{t} {}\n", format_real(masses[i])));
+            }
+        }
+
+        let image = match self.arrays.get("image") {
+            Some(Column::IntegerN(3, v)) if v.len() == 3 * n => Some(v),
+            Some(_) => bail!("invalid type of property image"),
+            None => None,
+        };
+        s.push_str(&format!("\nAtoms # {style}\n\n"));
+        for i in 0..n {
+            let mut row = vec![ids[i].to_string()];
+            if let Some(mol) = mol {
+                row.push(mol[i].to_string());
+            }
+            row.push(types[i].to_string());
+            if let Some(q) = charges {
+                row.push(format_real(q[i]));
+            }
+            row.push(format_reals(&self.positions[i]));
+            if let Some(image) = image {
+                row.extend(image[3 * i..3 * i + 3].iter().map(|x| x.to_string()));
+            }
+            s.push_str(&row.join(" "));
+            s.push('\n');
+        }
+
+        if let Some(column) = self.arrays.get("velocities") {
+            let velocities = column.as_real3().context("invalid type of property velocities")?;
+            ensure!(velocities.len() == n, "inconsistent number of atoms in property velocities");
+            s.push_str("\nVelocities\n\n");
+            for (id, v) in ids.iter().zip(velocities) {
+                s.push_str(&format!("{id} {}\n", format_reals(v)));
+            }
+        }
+        Ok(s)
+    }
+}
+// c2e97a05 ends here
+
+// [[file:../extxyz.note::7b3f52d8][7b3f52d8]]
+// Read the `i`-th frame in LAMMPS dump format into `buf`, from the `ITEM:
+// TIMESTEP` line to the last atom line following `ITEM: ATOMS`. Blank
+// lines before the frame are ignored. Return the number of atoms, or
+// None if the stream has reached EOF.
+fn read_dump_frame<R: std::io::BufRead>(reader: &mut TextReader<R>, buf: &mut String, i: usize) -> Result<Option<usize>, ReadError> {
+    use std::result::Result::Ok;
+
+    let m = buf.len();
+    let mut declared = None;
+    let mut natoms_line = false;
+    // the header lines
+    let natoms = loop {
+        let (offset, line) = reader.position();
+        let error = |kind| ReadError { frame: i, offset, line, kind };
+        let start = buf.len();
+        match reader.try_read_line(buf) {
+            Ok(0) if buf[m..].trim().is_empty() => {
+                buf.truncate(m);
+                return Ok(None);
+            }
+            Ok(0) => {
+                let kind = ReadErrorKind::Truncated {
+                    natoms: declared.unwrap_or(0),
+                    found: 0,
+                };
+                return Err(error(kind));
+            }
+            Ok(_) => {}
+            Err(e) => return Err(error(ReadErrorKind::Io(e))),
+        }
+        let s = buf[start..].trim();
+        if natoms_line {
+            match s.parse::<usize>() {
+                Ok(n) => declared = Some(n),
+                Err(_) => return Err(error(ReadErrorKind::InvalidNatoms(s.to_string()))),
+            }
+            natoms_line = false;
+        } else if s.starts_with("ITEM: NUMBER OF ATOMS") {
+            natoms_line = true;
+        } else if s.starts_with("ITEM: ATOMS") {
+            match declared {
+                Some(n) => break n,
+                None => return Err(error(ReadErrorKind::InvalidNatoms(s.to_string()))),
+            }
+        } else if buf[m..].trim().is_empty() {
+            buf.truncate(m);
+        }
+    };
+
+    // the atom lines
+    for k in 0..natoms {
+        let (offset, line) = reader.position();
+        let error = |kind| ReadError { frame: i, offset, line, kind };
+        match reader.try_read_line(buf) {
+            Ok(0) => return Err(error(ReadErrorKind::Truncated { natoms, found: k })),
+            Ok(_) => {}
+            Err(e) => return Err(error(ReadErrorKind::Io(e))),
+        }
+    }
+    Ok(Some(natoms))
+}
+
+fn parse_dump_frames<R: std::io::BufRead>(reader: TextReader<R>, selection: impl Iterator<Item = usize>) -> impl Iterator<Item = Result<Frame>> {
+    try_read_frames_sequential_with(reader, selection, false, read_dump_frame).map(|frame| Frame::from_lammps_dump(&frame?))
+}
+
+/// Return an iterator over the selected frames in LAMMPS dump file in
+/// `path`. Frames are read one by one as `try_read_xyz_frames`, so large
+/// dump files (including compressed ones) can be processed as streams.
+/// See `Frame::from_lammps_dump` for the mapping of data.
+///
+/// The indices in `selection` should be in ascending order. The iterator
+/// stops after the first error in reading frames.
+pub fn read_lammps_dump(path: impl AsRef<Path>, selection: impl Iterator<Item = usize>) -> Result<impl Iterator<Item = Result<Frame>>> {
+    let reader = TextReader::try_from_path(path.as_ref())?;
+    Ok(parse_dump_frames(reader, selection))
+}
+
+/// Return an iterator over the selected frames in LAMMPS dump format from
+/// any `reader`, such as stdin. See `read_lammps_dump`.
+pub fn read_lammps_dump_from<R: std::io::Read>(reader: R, selection: impl Iterator<Item = usize>) -> impl Iterator<Item = Result<Frame>> {
+    parse_dump_frames(TextReader::new(reader), selection)
+}
+
+/// Write `frames` into `writer` in LAMMPS dump format one by one. The
+/// timestep is taken from `timestep` in `info`, or the frame index if
+/// missing. See `Frame::to_lammps_dump`.
+pub fn write_lammps_dump_to<F: Borrow<Frame>>(mut writer: impl Write, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    for (i, frame) in frames.into_iter().enumerate() {
+        let frame = frame.borrow();
+        let timestep = frame.info.get("timestep").and_then(|v| v.as_i64()).unwrap_or(i as i64);
+        let s = frame.format_lammps_dump(timestep).with_context(|| format!("failed to convert frame {i}"))?;
+        writer.write_all(s.as_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `frames` into file in `path` in LAMMPS dump format.
+pub fn write_lammps_dump<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).with_context(|| format!("Failed to create file {path:?}"))?;
+    write_lammps_dump_to(std::io::BufWriter::new(f), frames)
+}
+
+/// Read `Frame` from file in `path` in LAMMPS data format.
+pub fn read_lammps_data(path: impl AsRef<Path>) -> Result<Frame> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("Failed to read file {path:?}"))?;
+    Frame::from_lammps_data(&s)
+}
+
+/// Write `frame` into file in `path` in LAMMPS data format.
+pub fn write_lammps_data(path: impl AsRef<Path>, frame: &Frame) -> Result<()> {
+    let path = path.as_ref();
+    let s = frame.to_lammps_data()?;
+    std::fs::write(path, s).with_context(|| format!("Failed to write file {path:?}"))?;
+    Ok(())
+}
+// 7b3f52d8 ends here
+
+// [[file:../extxyz.note::e80a4c6b][e80a4c6b]]
+#[test]
+fn test_lammps() -> Result<()> {
+    let dump = "ITEM: TIMESTEP
+100
+ITEM: NUMBER OF ATOMS
+2
+ITEM: BOX BOUNDS xy xz yz pp pp ff
+-1.0 5.0 1.0
+0.0 4.0 0.0
+0.0 6.0 0.0
+ITEM: ATOMS id type element xs ys zs fx fy fz c_pe c_s[1] c_s[2] ix iy iz
+2 1 Si 0.0 0.0 0.0 0.1 0.2 0.3 -1.5 1 2 0 0 1
+1 2 O 0.5 0.5 0.5 -0.1 -0.2 -0.3 -2.5 3 4 0 1 0
+
+ITEM: TIMESTEP
+200
+ITEM: NUMBER OF ATOMS
+1
+ITEM: BOX BOUNDS pp pp pp
+0.0 5.0
+0.0 4.0
+0.0 6.0
+ITEM: ATOMS id type x y z mass
+1 1 1.0 2.0 3.0 12.0
+";
+    let frames: Vec<_> = read_lammps_dump_from(dump.as_bytes(), 0..).collect::<Result<_>>()?;
+    assert_eq!(frames.len(), 2);
+    let frame = &frames[0];
+    assert_eq!(frame.symbols, ["Si", "O"]);
+    assert_eq!(frame.lattice, Some([[5.0, 0.0, 0.0], [1.0, 4.0, 0.0], [0.0, 0.0, 6.0]]));
+    assert_eq!(frame.pbc, Some([true, true, false]));
+    assert_eq!(frame.positions[1], [2.0, 2.0, 3.0]);
+    assert_eq!(frame.info.get("origin"), Some(&serde_json::json!([-1.0, 0.0, 0.0])));
+    assert_eq!(frame.info.get("timestep"), Some(&100.into()));
+    assert_eq!(frame.arrays["forces"].as_real3().unwrap()[1], [-0.1, -0.2, -0.3]);
+    assert_eq!(frame.arrays["c_s"], Column::RealN(2, vec![1.0, 2.0, 3.0, 4.0]));
+    assert_eq!(frame.arrays["image"], Column::IntegerN(3, vec![0, 0, 1, 0, 1, 0]));
+    assert_eq!(frame.arrays["id"].as_integer(), Some(&[2, 1][..]));
+    // without element column
+    assert_eq!(frames[1].symbols, ["1"]);
+    assert_eq!(frames[1].positions[0], [1.0, 2.0, 3.0]);
+    assert_eq!(frames[1].arrays["masses"].as_real(), Some(&[12.0][..]));
+
+    // round trip
+    let mut buf = vec![];
+    write_lammps_dump_to(&mut buf, &frames)?;
+    let frames_: Vec<_> = read_lammps_dump_from(&buf[..], 0..).collect::<Result<_>>()?;
+    assert_eq!(frames_[0].lattice, frames[0].lattice);
+    assert_eq!(frames_[0].positions, frames[0].positions);
+    assert_eq!(frames_[0].arrays, frames[0].arrays);
+    assert_eq!(frames_[1].info.get("timestep"), Some(&200.into()));
+    assert_eq!(frames_[1].arrays, frames[1].arrays);
+    assert!(std::str::from_utf8(&buf)?.contains("ITEM: ATOMS id type element x y z mass\n"));
+    // selected frames
+    let frames_: Vec<_> = read_lammps_dump_from(&buf[..], [1].into_iter()).collect::<Result<_>>()?;
+    assert_eq!(frames_.len(), 1);
+    assert_eq!(frames_[0].positions, frames[1].positions);
+
+    // truncated dump
+    let frames_: Vec<_> = read_lammps_dump_from(&dump.as_bytes()[..dump.len() - 20], 0..).collect();
+    assert!(frames_[0].is_ok() && frames_[1].is_err());
+
+    let data = "water config_type=liquid
+
+3 atoms
+2 atom types
+
+0.0 10.0 xlo xhi
+0.0 10.0 ylo yhi
+-5.0 5.0 zlo zhi
+
+Masses
+
+1 15.9994 # O
+2 1.008 # H
+
+Atoms # full
+
+1 1 1 -0.8 0.0 0.0 0.0 0 0 1
+3 1 2 0.4 0.0 -0.8 0.6 0 0 1
+2 1 2 0.4 0.0 0.8 0.6 0 0 1
+
+Velocities
+
+2 0.1 0.0 0.0
+3 0.2 0.0 0.0
+1 0.0 0.0 0.0
+
+Bonds
+
+1 1 1 2
+2 1 1 3
+";
+    let frame = Frame::from_lammps_data(data)?;
+    assert_eq!(frame.symbols, ["O", "H", "H"]);
+    assert_eq!(frame.lattice.unwrap()[2], [0.0, 0.0, 10.0]);
+    assert_eq!(frame.info.get("origin"), Some(&serde_json::json!([0.0, 0.0, -5.0])));
+    assert_eq!(frame.info.get("config_type"), Some(&"liquid".into()));
+    assert_eq!(frame.arrays["q"].as_real(), Some(&[-0.8, 0.4, 0.4][..]));
+    assert_eq!(frame.arrays["velocities"].as_real3().unwrap()[1], [0.2, 0.0, 0.0]);
+    assert_eq!(frame.arrays["masses"].as_real().unwrap()[2], 1.008);
+    // atom types in type labels
+    let data_ = data
+        .replace("Masses\n\n1 15.9994 # O\n2 1.008 # H", "Atom Type Labels\n\n1 O\n2 H\n\nMasses\n\nO 15.9994\nH 1.008")
+        .replace("1 1 1 -0.8", "1 1 O -0.8")
+        .replace("3 1 2 0.4", "3 1 H 0.4");
+    assert_eq!(Frame::from_lammps_data(&data_)?, frame);
+    let e = Frame::from_lammps_data(&data.replace("3 1 2 0.4", "3 1 H 0.4")).unwrap_err();
+    assert!(format!("{e:#}").contains("undefined type label"));
+    // `id mol type x y z` could not be told from `id type q x y z`
+    let data_ = "\n1 atoms\n1 atom types\n0 1 xlo xhi\n0 1 ylo yhi\n0 1 zlo zhi\n\nAtoms\n\n1 1 1 0.0 0.0 0.0\n";
+    let e = Frame::from_lammps_data(data_).unwrap_err();
+    assert!(e.to_string().contains("atom style required"), "{e}");
+    assert!(Frame::from_lammps_data(&data_.replace("Atoms", "Atoms # molecular")).is_ok());
+
+    let s = frame.to_lammps_data()?;
+    assert!(s.contains("Atoms # full") && s.contains("Atom Type Labels"));
+    assert_eq!(Frame::from_lammps_data(&s)?, frame);
+    // extxyz frame in general triclinic box
+    let frame = Frame::parse("1\nLattice=\"2.0 1.0 0.0 0.0 3.0 0.0 0.0 0.0 4.0\"\nC 0.5 0.5 0.5")?;
+    let frame_ = Frame::from_lammps_data(&frame.to_lammps_data()?)?;
+    assert_eq!(frame_.lattice, frame.lattice);
+    assert_eq!(frame_.symbols, frame.symbols);
+    let frame_ = Frame::from_lammps_dump(&frame.to_lammps_dump()?)?;
+    assert_eq!(frame_.lattice, frame.lattice);
+
+    Ok(())
+}
+// e80a4c6b ends here
@@ -0,0 +1,97 @@
+// [[file:../extxyz.note::8197bf04][8197bf04]]
+use serde::{Deserialize, Serialize};
+
+type Matrix3 = [[f64; 3]; 3];
+type Vector3 = [f64; 3];
+// 8197bf04 ends here
+
+// [[file:../extxyz.note::10b4d2b4][10b4d2b4]]
+fn dot(a: Vector3, b: Vector3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Represents the periodic cell defined by three lattice vectors, as in
+/// `Lattice` entry of extxyz comment line.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Lattice {
+    /// Lattice vectors in rows
+    matrix: Matrix3,
+}
+
+impl Lattice {
+    /// Construct `Lattice` from lattice vectors in rows.
+    pub fn new(matrix: Matrix3) -> Self {
+        Self { matrix }
+    }
+
+    /// Return lattice vectors in rows.
+    pub fn matrix(&self) -> Matrix3 {
+        self.matrix
+    }
+
+    /// Return the volume of the cell.
+    pub fn volume(&self) -> f64 {
+        let [a, b, c] = self.matrix;
+        dot(a, cross(b, c)).abs()
+    }
+
+    /// Return the reciprocal lattice vectors in rows, without the
+    /// factor of 2π. That is, `a_i · b_j = δ_ij`.
+    ///
+    /// The result is not finite for a singular cell.
+    pub fn reciprocal(&self) -> Matrix3 {
+        let [a, b, c] = self.matrix;
+        // signed volume
+        let v = dot(a, cross(b, c));
+        [cross(b, c), cross(c, a), cross(a, b)].map(|x| x.map(|xi| xi / v))
+    }
+
+    /// Convert Cartesian coordinates `cart` into fractional coordinates.
+    pub fn to_frac(&self, cart: Vector3) -> Vector3 {
+        self.reciprocal().map(|b| dot(b, cart))
+    }
+
+    /// Convert fractional coordinates `frac` into Cartesian coordinates.
+    pub fn to_cart(&self, frac: Vector3) -> Vector3 {
+        let [a, b, c] = self.matrix;
+        [0, 1, 2].map(|i| frac[0] * a[i] + frac[1] * b[i] + frac[2] * c[i])
+    }
+}
+
+impl From<Matrix3> for Lattice {
+    fn from(matrix: Matrix3) -> Self {
+        Self::new(matrix)
+    }
+}
+// 10b4d2b4 ends here
+
+// [[file:../extxyz.note::cb4bd2e8][cb4bd2e8]]
+#[test]
+fn test_lattice() {
+    let lattice = Lattice::new([[5.0, 0.0, 0.0], [1.0, 4.0, 0.0], [0.5, 0.5, 3.0]]);
+    assert!((lattice.volume() - 60.0).abs() < 1e-10);
+
+    let cart = [1.2, 2.3, 0.4];
+    let frac = lattice.to_frac(cart);
+    let cart_ = lattice.to_cart(frac);
+    for i in 0..3 {
+        assert!((cart[i] - cart_[i]).abs() < 1e-10);
+    }
+    // lattice vector `a` is (1, 0, 0) in fractional coordinates
+    let frac = lattice.to_frac([5.0, 0.0, 0.0]);
+    assert!((frac[0] - 1.0).abs() < 1e-10 && frac[1].abs() < 1e-10 && frac[2].abs() < 1e-10);
+
+    let a = lattice.matrix();
+    let b = lattice.reciprocal();
+    for (i, ai) in a.into_iter().enumerate() {
+        for (j, bj) in b.into_iter().enumerate() {
+            let delta = if i == j { 1.0 } else { 0.0 };
+            assert!((dot(ai, bj) - delta).abs() < 1e-10);
+        }
+    }
+}
+// cb4bd2e8 ends here
@@ -26,7 +26,7 @@
 //!         // get atom's properties
 //!         for atom in atoms.atoms {
 //!             // parse extra data for each atom
-//!             let atom_properties = info.parse_extra_columns(&atom.extra)?;
+//!             let atom_properties = info.parse_extra_columns(atom.extra)?;
 //!             // get `forces` component for each atom
 //!             let forces = &atom_properties["forces"];
 //!         }
@@ -37,8 +37,31 @@
 //! ```
 
 // [[file:../extxyz.note::10e3ae82][10e3ae82]]
+mod ase;
+mod column;
+mod compression;
+mod de;
+mod element;
+mod error;
+mod filter;
+mod frame;
+mod index;
+mod lammps;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod lattice;
+mod npz;
 mod parser;
+mod schema;
+mod ser;
+#[cfg(feature = "arrow")]
+mod table;
 mod trajectory;
+mod vasp;
+mod writer;
 // 10e3ae82 ends here
 
 // [[file:../extxyz.note::bf78776e][bf78776e]]
@@ -68,7 +91,10 @@ pub struct RawAtom<'s> {
     pub element: &'s str,
     /// The Cartesian coordinates
     pub position: [f64; 3],
-    /// Any rest input other than above
+    /// Any rest input other than above. If element and position are not
+    /// the leading columns according to `Properties`, it is the whole
+    /// atom line, and the columns for them are skipped when parsing
+    /// extra columns with `Info`.
     pub extra: &'s str,
 }
 
@@ -87,5 +113,27 @@ pub struct RawAtoms<'s> {
 // [[file:../extxyz.note::c3a71075][c3a71075]]
 pub use crate::trajectory::*;
 
-pub use crate::parser::extxyz::Info;
+pub use crate::ase::{format_ase_json, parse_ase_json, read_ase_json, write_ase_json};
+pub use crate::column::{Column, Columns};
+pub use crate::compression::Compression;
+pub use crate::de::{from_frame, from_str};
+pub use crate::ser::{to_frame, to_string, FrameSerializer};
+pub use crate::error::{ParseError, ParseErrorKind};
+pub use crate::filter::{filter_xyz_frames, CompareOp, Filter};
+pub use crate::frame::Frame;
+pub use crate::index::{read_xyz_frames_indexed, try_read_xyz_frames_indexed, FrameIndex};
+pub use crate::lammps::{read_lammps_data, read_lammps_dump, read_lammps_dump_from, write_lammps_data, write_lammps_dump, write_lammps_dump_to};
+#[cfg(feature = "mmap")]
+pub use crate::mmap::MmapTrajectory;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::{par_parse_frames, par_parse_frames_ordered};
+pub use crate::lattice::Lattice;
+pub use crate::npz::{write_npz, write_npz_to};
+pub use crate::options::{ParseOptions, ParseWarning};
+pub use crate::parser::extxyz::{Info, PropertyValue, PropertyValueType};
+pub use crate::schema::{Schema, SchemaField};
+#[cfg(feature = "arrow")]
+pub use crate::table::{to_record_batch, write_parquet, write_parquet_to, TableLayout};
+pub use crate::vasp::{format_xdatcar, parse_xdatcar, read_poscar, read_xdatcar, write_poscar, write_xdatcar};
+pub use crate::writer::{format_extxyz_frame, write_xyz_frames};
 // c3a71075 ends here
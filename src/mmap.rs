@@ -0,0 +1,102 @@
+// [[file:../extxyz.note::d5852b48][d5852b48]]
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::*;
+use memmap2::Mmap;
+
+use crate::index::scan_frames;
+use crate::{Compression, RawAtoms};
+// d5852b48 ends here
+
+// [[file:../extxyz.note::5db508ac][5db508ac]]
+/// Represents a trajectory file in xyz/extxyz format mapped into
+/// memory. Frame boundaries are found once on opening, and the frames
+/// are handed out as string slices borrowing directly from the mapped
+/// file, without any per-frame allocation.
+///
+/// # NOTE
+/// * The first line in `xyz` frame should be the real number of atom
+///   lines. That is, `VEC` atom line specifications are not supported.
+/// * The file should not be modified by others while it is mapped.
+pub struct MmapTrajectory {
+    mmap: Mmap,
+    // byte range of each frame
+    frames: Vec<(usize, usize)>,
+}
+
+impl MmapTrajectory {
+    /// Map trajectory file in `path` into memory, and locate all frames.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        ensure!(!Compression::detect(path)?.is_compressed(), "compressed file {path:?} can not be memory mapped");
+        let f = File::open(path).with_context(|| format!("Failed to open file {path:?}"))?;
+        // SAFETY: the mapped file is only read, and it is the caller's
+        // responsibility not to modify it when it is mapped.
+        let mmap = unsafe { Mmap::map(&f)? };
+
+        let offsets = scan_frames(&mmap[..])?;
+        let ends = offsets.iter().skip(1).map(|x| x.0 as usize).chain(std::iter::once(mmap.len()));
+        let frames = offsets.iter().map(|x| x.0 as usize).zip(ends).collect();
+        Ok(Self { mmap, frames })
+    }
+
+    /// Return the number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Return true if there is no frame.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Return the text of frame `i` borrowed from mapped file.
+    pub fn frame_text(&self, i: usize) -> Result<&str> {
+        let &(start, end) = self.frames.get(i).with_context(|| format!("frame {i} out of range"))?;
+        let s = std::str::from_utf8(&self.mmap[start..end]).with_context(|| format!("invalid UTF-8 data in frame {i}"))?;
+        Ok(s)
+    }
+
+    /// Parse frame `i` as `RawAtoms` borrowing from mapped file.
+    pub fn raw_atoms(&self, i: usize) -> Result<RawAtoms<'_>> {
+        RawAtoms::parse_from(self.frame_text(i)?).map_err(|e| crate::error::with_frame(e, i))
+    }
+
+    /// Return an iterator over the text of selected frames. Out of range
+    /// indices are ignored.
+    pub fn select<'a>(&'a self, selection: impl IntoIterator<Item = usize> + 'a) -> impl Iterator<Item = Result<&'a str>> + 'a {
+        selection.into_iter().filter(|&i| i < self.len()).map(|i| self.frame_text(i))
+    }
+
+    /// Return an iterator over the text of all frames.
+    pub fn iter(&self) -> impl Iterator<Item = Result<&str>> {
+        self.select(0..self.len())
+    }
+}
+// 5db508ac ends here
+
+// [[file:../extxyz.note::0ec8f62e][0ec8f62e]]
+#[test]
+fn test_mmap_trajectory() -> Result<()> {
+    let f = "tests/files/water.xyz";
+    let traj = MmapTrajectory::open(f)?;
+    assert_eq!(traj.len(), 3);
+
+    let expected: Vec<_> = crate::read_xyz_frames_direct(f)?.collect();
+    let frames: Vec<_> = traj.iter().collect::<Result<_>>()?;
+    assert_eq!(frames, expected);
+
+    let atoms = traj.raw_atoms(1)?;
+    assert_eq!(atoms.natoms, 4);
+    assert_eq!(atoms.atoms[3].position, [-10.0, -11.0, -12.0]);
+    // zero copy: the element symbol points into mapped file
+    let text = traj.frame_text(1)?;
+    assert!(text.as_bytes().as_ptr_range().contains(&atoms.atoms[0].element.as_ptr()));
+
+    let frames: Vec<_> = traj.select([2, 5, 0]).collect::<Result<_>>()?;
+    assert_eq!(frames, [&expected[2], &expected[0]]);
+
+    Ok(())
+}
+// 0ec8f62e ends here
@@ -0,0 +1,384 @@
+// [[file:../extxyz.note::b1f7e3a0][b1f7e3a0]]
+use std::borrow::Borrow;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::*;
+use serde_json::Value;
+
+use crate::element::atomic_number;
+use crate::{Column, Frame, PropertyValueType};
+// b1f7e3a0 ends here
+
+// [[file:../extxyz.note::4a9e2c67][4a9e2c67]]
+// Typed data of array in npy format
+#[derive(Debug, Clone, PartialEq)]
+enum NpyData {
+    F64(Vec<f64>),
+    I64(Vec<i64>),
+    Bool(Vec<bool>),
+    Str(Vec<String>),
+}
+
+// An n-dimensional array in C order
+#[derive(Debug, Clone, PartialEq)]
+struct NpyArray {
+    shape: Vec<usize>,
+    data: NpyData,
+}
+
+impl NpyArray {
+    fn new(shape: Vec<usize>, data: NpyData) -> Self {
+        Self { shape, data }
+    }
+
+    // The data type in numpy array protocol
+    fn descr(&self) -> String {
+        match &self.data {
+            NpyData::F64(_) => "<f8".into(),
+            NpyData::I64(_) => "<i8".into(),
+            NpyData::Bool(_) => "|b1".into(),
+            NpyData::Str(v) => format!("<U{}", v.iter().map(|s| s.chars().count()).max().unwrap_or(0).max(1)),
+        }
+    }
+
+    /// Format as bytes in npy format version 1.0.
+    fn to_npy(&self) -> Vec<u8> {
+        let shape = match &self.shape[..] {
+            [n] => format!("({n},)"),
+            shape => format!("({})", shape.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")),
+        };
+        let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {shape}, }}", self.descr());
+        // pad header with spaces for alignment of data in 64 bytes
+        let total = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - total % 64) % 64));
+        header.push('\n');
+
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        match &self.data {
+            NpyData::F64(v) => v.iter().for_each(|x| bytes.extend(x.to_le_bytes())),
+            NpyData::I64(v) => v.iter().for_each(|x| bytes.extend(x.to_le_bytes())),
+            NpyData::Bool(v) => bytes.extend(v.iter().map(|&x| x as u8)),
+            NpyData::Str(v) => {
+                // fixed width strings in UTF-32, padded with zeros
+                let width = v.iter().map(|s| s.chars().count()).max().unwrap_or(0).max(1);
+                for s in v {
+                    let n = s.chars().count();
+                    s.chars().for_each(|c| bytes.extend((c as u32).to_le_bytes()));
+                    bytes.extend(std::iter::repeat_n(0, (width - n) * 4));
+                }
+            }
+        }
+        bytes
+    }
+}
+
+// Write `entries` of file name and content into zip archive without
+// compression, as numpy does in `np.savez`.
+fn write_zip(mut writer: impl Write, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    // 1980-01-01 00:00:00 in MS-DOS format
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    let mut offset = 0u32;
+    let mut central = vec![];
+    for (name, data) in entries {
+        let size = u32::try_from(data.len()).ok().with_context(|| format!("array {name:?} is too large for npz"))?;
+        let crc = crc32fast::hash(data);
+        let name = name.as_bytes();
+        let mut local = vec![];
+        local.extend(0x04034b50u32.to_le_bytes());
+        for x in [20, 0, 0, DOS_TIME, DOS_DATE] {
+            local.extend(u16::to_le_bytes(x));
+        }
+        for x in [crc, size, size] {
+            local.extend(x.to_le_bytes());
+        }
+        local.extend((name.len() as u16).to_le_bytes());
+        local.extend(0u16.to_le_bytes());
+        local.extend(name);
+
+        central.extend(0x02014b50u32.to_le_bytes());
+        for x in [20, 20, 0, 0, DOS_TIME, DOS_DATE] {
+            central.extend(u16::to_le_bytes(x));
+        }
+        for x in [crc, size, size] {
+            central.extend(x.to_le_bytes());
+        }
+        for x in [name.len() as u16, 0, 0, 0, 0] {
+            central.extend(x.to_le_bytes());
+        }
+        central.extend(0u32.to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(name);
+
+        writer.write_all(&local)?;
+        writer.write_all(data)?;
+        offset = (local.len() as u64 + offset as u64 + size as u64)
+            .try_into()
+            .ok()
+            .context("npz file is too large")?;
+    }
+
+    let n = entries.len() as u16;
+    let mut end = vec![];
+    end.extend(0x06054b50u32.to_le_bytes());
+    for x in [0, 0, n, n] {
+        end.extend(u16::to_le_bytes(x));
+    }
+    end.extend((central.len() as u32).to_le_bytes());
+    end.extend(offset.to_le_bytes());
+    end.extend(0u16.to_le_bytes());
+    writer.write_all(&central)?;
+    writer.write_all(&end)?;
+    writer.flush()?;
+    Ok(())
+}
+// 4a9e2c67 ends here
+
+// [[file:../extxyz.note::d07c5b8e][d07c5b8e]]
+// Stack the same kind of values from all frames, such as `energy` or
+// `stress` in comment line. Return None if the values are missing or in
+// different types or shapes.
+fn stack_values(values: &[Option<&Value>]) -> Option<NpyArray> {
+    let values: Vec<&Value> = values.iter().copied().collect::<Option<_>>()?;
+    let nframes = values.len();
+    let (width, scalars): (Option<usize>, Vec<&Value>) = match values.first()? {
+        Value::Array(row) => {
+            let width = row.len();
+            let mut scalars = vec![];
+            for v in &values {
+                scalars.extend(v.as_array().filter(|row| row.len() == width)?);
+            }
+            (Some(width), scalars)
+        }
+        _ => (None, values),
+    };
+    let shape = match width {
+        Some(w) => vec![nframes, w],
+        None => vec![nframes],
+    };
+
+    let data = if let Some(v) = scalars.iter().map(|x| x.as_bool()).collect::<Option<Vec<_>>>() {
+        NpyData::Bool(v)
+    } else if let Some(v) = scalars.iter().map(|x| x.as_i64()).collect::<Option<Vec<_>>>() {
+        NpyData::I64(v)
+    } else if let Some(v) = scalars.iter().map(|x| x.as_f64()).collect::<Option<Vec<_>>>() {
+        NpyData::F64(v)
+    } else {
+        NpyData::Str(scalars.iter().map(|x| x.as_str().map(String::from)).collect::<Option<_>>()?)
+    };
+    Some(NpyArray::new(shape, data))
+}
+
+// Concatenate per-atom property in `columns` of all frames. Return
+// None if the columns are missing or in different types or widths.
+fn concat_columns(columns: &[Option<&Column>]) -> Option<NpyData> {
+    let columns: Vec<&Column> = columns.iter().copied().collect::<Option<_>>()?;
+    let first = columns.first()?;
+    if columns.iter().any(|c| c.value_type() != first.value_type() || c.width() != first.width()) {
+        return None;
+    }
+    let data = match first.value_type() {
+        PropertyValueType::Real => NpyData::F64(
+            columns
+                .iter()
+                .flat_map(|c| match c {
+                    Column::Real(v) | Column::RealN(_, v) => v.clone(),
+                    Column::Real3(v) => v.iter().flatten().copied().collect(),
+                    _ => unreachable!(),
+                })
+                .collect(),
+        ),
+        PropertyValueType::Integer => NpyData::I64(
+            columns
+                .iter()
+                .flat_map(|c| match c {
+                    Column::Integer(v) | Column::IntegerN(_, v) => v.clone(),
+                    _ => unreachable!(),
+                })
+                .collect(),
+        ),
+        PropertyValueType::Logical => NpyData::Bool(
+            columns
+                .iter()
+                .flat_map(|c| match c {
+                    Column::Logical(v) | Column::LogicalN(_, v) => v.clone(),
+                    _ => unreachable!(),
+                })
+                .collect(),
+        ),
+        PropertyValueType::String => NpyData::Str(columns.iter().flat_map(|c| c.iter_strings().map(String::from)).collect()),
+    };
+    Some(data)
+}
+
+// Collect stacked arrays from `frames` in order
+fn npz_arrays(frames: &[&Frame]) -> Result<Vec<(String, NpyArray)>> {
+    let nframes = frames.len();
+    let natoms: Vec<usize> = frames.iter().map(|f| f.symbols.len()).collect();
+    for (i, frame) in frames.iter().enumerate() {
+        ensure!(frame.positions.len() == natoms[i], "inconsistent number of positions in frame {i}");
+    }
+    let total: usize = natoms.iter().sum();
+    // shape of per-atom array with `width` columns
+    let uniform = natoms.windows(2).all(|w| w[0] == w[1]);
+    let atom_shape = |width: Option<usize>| -> Vec<usize> {
+        let mut shape = if uniform { vec![nframes, natoms.first().copied().unwrap_or(0)] } else { vec![total] };
+        shape.extend(width);
+        shape
+    };
+
+    let mut arrays = vec![];
+    arrays.push(("natoms".to_string(), NpyArray::new(vec![nframes], NpyData::I64(natoms.iter().map(|&n| n as i64).collect()))));
+    if !uniform {
+        let offsets = std::iter::once(0).chain(natoms.iter().scan(0, |acc, &n| {
+            *acc += n as i64;
+            Some(*acc)
+        }));
+        arrays.push(("offsets".to_string(), NpyArray::new(vec![nframes + 1], NpyData::I64(offsets.collect()))));
+    }
+
+    let symbols: Vec<String> = frames.iter().flat_map(|f| f.symbols.iter().cloned()).collect();
+    if let Some(numbers) = symbols.iter().map(|s| atomic_number(s)).collect::<Option<Vec<_>>>() {
+        arrays.push(("numbers".to_string(), NpyArray::new(atom_shape(None), NpyData::I64(numbers))));
+    }
+    arrays.push(("symbols".to_string(), NpyArray::new(atom_shape(None), NpyData::Str(symbols))));
+    let positions = frames.iter().flat_map(|f| f.positions.iter().flatten().copied()).collect();
+    arrays.push(("positions".to_string(), NpyArray::new(atom_shape(Some(3)), NpyData::F64(positions))));
+    if frames.iter().any(|f| f.lattice.is_some()) {
+        let cell = frames.iter().flat_map(|f| f.lattice.unwrap_or_default().into_iter().flatten()).collect();
+        arrays.push(("cell".to_string(), NpyArray::new(vec![nframes, 3, 3], NpyData::F64(cell))));
+        let pbc = frames.iter().flat_map(|f| f.pbc.unwrap_or([f.lattice.is_some(); 3])).collect();
+        arrays.push(("pbc".to_string(), NpyArray::new(vec![nframes, 3], NpyData::Bool(pbc))));
+    }
+
+    // per-frame values in comment line
+    if let Some(first) = frames.first() {
+        for key in first.info.raw_map().keys() {
+            if arrays.iter().any(|(k, _)| k == key) {
+                continue;
+            }
+            let values: Vec<_> = frames.iter().map(|f| f.info.get(key)).collect();
+            if let Some(array) = stack_values(&values) {
+                arrays.push((key.clone(), array));
+            }
+        }
+    }
+
+    // per-atom properties in `Properties`
+    if let Some(first) = frames.first() {
+        for (name, column) in &first.arrays {
+            let columns: Vec<_> = frames.iter().map(|f| f.arrays.get(name)).collect();
+            if let Some(data) = concat_columns(&columns) {
+                let width = column.width();
+                let shape = atom_shape((width > 1 || matches!(column, Column::Real3(_))).then_some(width));
+                let key = if arrays.iter().any(|(k, _)| k == name) { format!("{name}_atoms") } else { name.clone() };
+                arrays.push((key, NpyArray::new(shape, data)));
+            }
+        }
+    }
+    Ok(arrays)
+}
+
+/// Write `frames` into `writer` in numpy `.npz` format, with arrays
+/// stacked over frames:
+///
+/// * `natoms[nframes]`, and `numbers` (if all element symbols are
+///   valid), `symbols` and `positions` of atoms
+/// * `cell[nframes, 3, 3]` and `pbc[nframes, 3]`, if any frame has
+///   `Lattice`. The cell is zero for frames without `Lattice`.
+/// * values in comment line present in all frames in the same type and
+///   shape, such as `energy[nframes]` or `stress[nframes, 9]`
+/// * per-atom properties in `Properties` present in all frames in the
+///   same type and width, such as `forces`. A property having the same
+///   name as a value in comment line is named with suffix `_atoms`.
+///
+/// Per-atom arrays are in shape of `[nframes, natoms, ...]` if all frames
+/// have the same number of atoms. Otherwise, they are concatenated in
+/// shape of `[total_atoms, ...]`, and atoms of frame `i` are in range
+/// `offsets[i]..offsets[i+1]`.
+pub fn write_npz_to<F: Borrow<Frame>>(writer: impl Write, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let frames: Vec<F> = frames.into_iter().collect();
+    let frames: Vec<&Frame> = frames.iter().map(|f| f.borrow()).collect();
+    let entries: Vec<_> = npz_arrays(&frames)?
+        .into_iter()
+        .map(|(name, array)| (format!("{name}.npy"), array.to_npy()))
+        .collect();
+    write_zip(writer, &entries)
+}
+
+/// Write `frames` into file in `path` in numpy `.npz` format. See
+/// `write_npz_to` for the arrays.
+pub fn write_npz<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).with_context(|| format!("Failed to create file {path:?}"))?;
+    write_npz_to(std::io::BufWriter::new(f), frames)
+}
+// d07c5b8e ends here
+
+// [[file:../extxyz.note::8e61a4f2][8e61a4f2]]
+#[test]
+fn test_npy_format() {
+    let array = NpyArray::new(vec![2, 3], NpyData::I64(vec![1, 2, 3, 4, 5, 6]));
+    let bytes = array.to_npy();
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    assert!(header.starts_with("{'descr': '<i8', 'fortran_order': False, 'shape': (2, 3), }"));
+    assert!(header.ends_with('\n'));
+    assert_eq!(bytes.len(), 10 + header_len + 6 * 8);
+    assert_eq!(&bytes[10 + header_len..10 + header_len + 8], 1i64.to_le_bytes());
+
+    let array = NpyArray::new(vec![2], NpyData::Str(vec!["H".into(), "Cu".into()]));
+    assert_eq!(array.descr(), "<U2");
+    let bytes = array.to_npy();
+    assert_eq!(&bytes[bytes.len() - 16..], [72, 0, 0, 0, 0, 0, 0, 0, 67, 0, 0, 0, 117, 0, 0, 0]);
+}
+
+#[test]
+fn test_npz_arrays() -> Result<()> {
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:energy:R:1 energy=-1.5 config_type=bulk stress="1 2 3 4 5 6 7 8 9"
+Si 0.0 0.0 0.0 0.1 0.2 0.3 -0.7
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 -0.8
+"#;
+    let frame = Frame::parse(input)?;
+    let mut other = frame.clone();
+    other.info.raw_map_mut().insert("energy".into(), (-2.5).into());
+    other.info.raw_map_mut().remove("config_type");
+
+    let arrays = npz_arrays(&[&frame, &other])?;
+    let get = |arrays: &[(String, NpyArray)], k: &str| arrays.iter().find(|(name, _)| name == k).map(|(_, a)| a.clone());
+    assert_eq!(get(&arrays, "positions").unwrap().shape, [2, 2, 3]);
+    assert_eq!(get(&arrays, "numbers").unwrap().data, NpyData::I64(vec![14; 4]));
+    assert_eq!(get(&arrays, "cell").unwrap().shape, [2, 3, 3]);
+    assert_eq!(get(&arrays, "energy").unwrap().data, NpyData::F64(vec![-1.5, -2.5]));
+    assert_eq!(get(&arrays, "stress").unwrap().shape, [2, 9]);
+    assert_eq!(get(&arrays, "forces").unwrap().shape, [2, 2, 3]);
+    assert_eq!(get(&arrays, "energy_atoms").unwrap().shape, [2, 2]);
+    // missing in one frame
+    assert!(get(&arrays, "config_type").is_none());
+    assert!(get(&arrays, "offsets").is_none());
+
+    // ragged frames
+    let small = Frame::parse("1\nenergy=-0.5\nH 0.0 0.0 0.0")?;
+    let arrays = npz_arrays(&[&frame, &small])?;
+    assert_eq!(get(&arrays, "offsets").unwrap().data, NpyData::I64(vec![0, 2, 3]));
+    assert_eq!(get(&arrays, "positions").unwrap().shape, [3, 3]);
+    assert_eq!(get(&arrays, "symbols").unwrap().shape, [3]);
+    assert_eq!(get(&arrays, "pbc").unwrap().data, NpyData::Bool(vec![true, true, true, false, false, false]));
+    assert!(get(&arrays, "forces").is_none());
+
+    // zip archive
+    let mut buf = vec![];
+    write_npz_to(&mut buf, [&frame, &small])?;
+    assert_eq!(&buf[..4], b"PK\x03\x04");
+    assert_eq!(&buf[buf.len() - 22..buf.len() - 18], b"PK\x05\x06");
+
+    Ok(())
+}
+// 8e61a4f2 ends here
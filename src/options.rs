@@ -0,0 +1,109 @@
+// [[file:../extxyz.note::3d7a5c21][3d7a5c21]]
+use anyhow::*;
+
+use crate::{ParseError, ParseErrorKind};
+// 3d7a5c21 ends here
+
+// [[file:../extxyz.note::9f2b6e48][9f2b6e48]]
+/// Options controlling how malformed input is handled by the `*_with`
+/// parse functions, such as `Frame::parse_with`.
+///
+/// * In strict mode, the number of atom lines must match the natoms
+///   line, all property columns must be consumed without trailing
+///   columns, and any invalid entry is an error, which could be
+///   downcast to `ParseError` to match on its `ParseErrorKind`.
+/// * In lenient mode (the default), these issues are collected as
+///   `ParseWarning`s, and the offending data is skipped where possible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    strict: bool,
+}
+
+impl ParseOptions {
+    /// Options for strict mode.
+    pub fn strict() -> Self {
+        Self { strict: true }
+    }
+
+    /// Options for lenient mode.
+    pub fn lenient() -> Self {
+        Self { strict: false }
+    }
+
+    /// Return true if in strict mode.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+}
+
+/// Represents a non-fatal issue found when parsing in lenient mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// The kind of issue, as in `ParseError` for strict mode
+    pub kind: ParseErrorKind,
+    /// The line number in frame (counting from 1), if applicable
+    pub line: Option<usize>,
+    /// Description of the issue
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Collect issues as warnings, or fail on them in strict mode.
+pub(crate) struct Diagnostics<'a> {
+    options: ParseOptions,
+    warnings: Vec<ParseWarning>,
+    // the text where issues are located by line
+    input: &'a str,
+}
+
+impl<'a> Diagnostics<'a> {
+    pub fn new(options: &ParseOptions) -> Self {
+        Self {
+            options: *options,
+            warnings: vec![],
+            input: "",
+        }
+    }
+
+    /// Return the options for reporting issues.
+    pub fn options(&self) -> ParseOptions {
+        self.options
+    }
+
+    /// Locate issues by line in `input` text, such as the whole frame.
+    pub fn with_input(mut self, input: &'a str) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Report an issue of `kind` with `message` at `line`, which is a
+    /// `ParseError` in strict mode. Issues without line are located at
+    /// the first line.
+    pub fn report(&mut self, kind: ParseErrorKind, line: Option<usize>, message: impl Into<String>) -> Result<()> {
+        let message = message.into();
+        if self.options.strict {
+            return Err(ParseError::at_line(kind, message, self.input, line.unwrap_or(1)).into());
+        }
+        self.warnings.push(ParseWarning { kind, line, message });
+        Ok(())
+    }
+
+    /// Add `warnings` reported elsewhere, located at `line` if they
+    /// have no line number.
+    pub fn extend(&mut self, warnings: Vec<ParseWarning>, line: Option<usize>) {
+        self.warnings.extend(warnings.into_iter().map(|w| ParseWarning { line: w.line.or(line), ..w }));
+    }
+
+    pub fn finish(self) -> Vec<ParseWarning> {
+        self.warnings
+    }
+}
+// 9f2b6e48 ends here
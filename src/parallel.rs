@@ -0,0 +1,66 @@
+// [[file:../extxyz.note::2a3ed1e7][2a3ed1e7]]
+use anyhow::*;
+use rayon::prelude::*;
+
+use crate::Frame;
+// 2a3ed1e7 ends here
+
+// [[file:../extxyz.note::0a79c950][0a79c950]]
+// parse frame `i`, with frame index recorded in error
+fn parse_frame(i: usize, s: &str) -> Result<Frame> {
+    Frame::parse(s).map_err(|e| crate::error::with_frame(e, i))
+}
+
+/// Parse raw `frames` in a thread pool. The frame text is pulled from
+/// `frames` sequentially, for example from `read_xyz_frames`, and parsed
+/// into `Frame` in parallel. The results are yielded in any order, along
+/// with the index of the frame in `frames`.
+pub fn par_parse_frames<I>(frames: I) -> impl ParallelIterator<Item = (usize, Result<Frame>)>
+where
+    I: Iterator<Item = String> + Send,
+{
+    frames.enumerate().par_bridge().map(|(i, s)| (i, parse_frame(i, &s)))
+}
+
+/// Parse raw `frames` in a thread pool, like `par_parse_frames`, but
+/// yield the results in original order. Frames are parsed in batches of
+/// `batch_size` frames, so that memory usage is bounded for large
+/// trajectories.
+pub fn par_parse_frames_ordered<I>(frames: I, batch_size: usize) -> impl Iterator<Item = Result<Frame>>
+where
+    I: Iterator<Item = String>,
+{
+    let mut frames = frames.enumerate().fuse();
+    let batch_size = batch_size.max(1);
+    std::iter::from_fn(move || {
+        let batch: Vec<_> = frames.by_ref().take(batch_size).collect();
+        if batch.is_empty() {
+            None
+        } else {
+            let parsed: Vec<_> = batch.par_iter().map(|(i, s)| parse_frame(*i, s)).collect();
+            Some(parsed)
+        }
+    })
+    .flatten()
+}
+// 0a79c950 ends here
+
+// [[file:../extxyz.note::151f357d][151f357d]]
+#[test]
+fn test_par_parse_frames() -> Result<()> {
+    let f = "tests/files/water.xyz";
+    let expected: Vec<_> = crate::read_xyz_frames_direct(f)?.map(|s| Frame::parse(&s)).collect::<Result<_>>()?;
+
+    let frames: Vec<_> = par_parse_frames_ordered(crate::read_xyz_frames_direct(f)?, 2).collect::<Result<_>>()?;
+    assert_eq!(frames, expected);
+
+    let mut frames: Vec<_> = par_parse_frames(crate::read_xyz_frames_direct(f)?).collect();
+    frames.sort_by_key(|x| x.0);
+    assert_eq!(frames.len(), 3);
+    for ((i, frame), expected) in frames.into_iter().zip(&expected) {
+        assert_eq!(&frame?, expected, "frame {i}");
+    }
+
+    Ok(())
+}
+// 151f357d ends here
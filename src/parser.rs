@@ -1,17 +1,9 @@
 // [[file:../extxyz.note::51ad662c][51ad662c]]
 // #![deny(warnings)]
 
-use super::{RawAtom, RawAtoms};
-
-use winnow::ascii::{alphanumeric1, space0, space1};
 use winnow::combinator::alt;
-use winnow::combinator::cut_err;
-use winnow::combinator::{delimited, separated};
-use winnow::combinator::{not, opt, repeat, todo};
+use winnow::combinator::opt;
 use winnow::prelude::*;
-use winnow::stream::AsChar;
-use winnow::token::take_till;
-use winnow::token::take_while;
 // 51ad662c ends here
 
 // [[file:../extxyz.note::a270017d][a270017d]]
@@ -31,10 +23,10 @@ fn label(s: &'static str) -> StrContext {
 
 // [[file:../extxyz.note::1a36024d][1a36024d]]
 /// Recognize one or more decimal digits
+#[allow(dead_code)]
 pub fn recognize_unsigned_integer<'i>(input: &mut Stream<'i>) -> PResult<&'i str> {
     use winnow::ascii::digit1;
     use winnow::combinator::cut_err;
-    use winnow::token::one_of;
 
     let r = cut_err(digit1).recognize().parse_next(input)?;
     Ok(r)
@@ -61,9 +53,7 @@ pub fn recognize_integer<'i>(input: &mut Stream<'i>) -> PResult<&'i str> {
 /// (exponential) notation is also supported.
 pub fn recognize_sci_float<'i>(input: &mut Stream<'i>) -> PResult<String> {
     use winnow::ascii::digit1;
-    use winnow::combinator::cut_err;
     use winnow::combinator::preceded;
-    use winnow::stream::Located;
     use winnow::token::one_of;
 
     // e.g. -1.34D+8
@@ -77,7 +67,7 @@ pub fn recognize_sci_float<'i>(input: &mut Stream<'i>) -> PResult<String> {
     let float_s = if let Some(exponent) = opt(preceded(one_of(['e', 'E', 'D', 'd']), recognize_integer)).parse_next(input)? {
         format!("{pre_exponent}E{exponent}")
     } else {
-        format!("{pre_exponent}")
+        pre_exponent.to_string()
     };
     Ok(float_s)
 }
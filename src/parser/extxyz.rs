@@ -1,13 +1,8 @@
 // [[file:../../extxyz.note::bf987e7c][bf987e7c]]
-use anyhow::anyhow;
-use serde_json::json;
-
-use super::{label, recognize_boolean, recognize_integer, recognize_sci_float, Stream};
-use crate::{RawAtom, RawAtoms};
+use super::{recognize_boolean, recognize_sci_float, Stream};
 
 use winnow::ascii::{space0, space1};
 use winnow::combinator::alt;
-use winnow::combinator::cut_err;
 use winnow::combinator::separated;
 use winnow::PResult;
 use winnow::Parser;
@@ -21,7 +16,7 @@ use serde_json::Value;
 /// Represents the data parsed from extxyz comment line.
 ///
 /// Example input: Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3 Time=0.0
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq)]
 pub struct Info {
     /// A heterogeneous map (dict) for parsed key-value pairs
     dict: serde_json::Map<String, Value>,
@@ -31,15 +26,18 @@ pub struct Info {
 /// defines the columns in the subsequent lines in the frame.
 ///
 /// Example input: Properties=species:S:1:pos:R:3
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct PropertyValue {
+    /// The name of the property, such as `forces`
     pub name: String,
+    /// The value type in the column(s)
     pub r#type: PropertyValueType,
+    /// The number of consecutive columns for the property
     pub num_columns: usize,
 }
 
 /// Represents the column value type in `Properties` values
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PropertyValueType {
     /// S
     String,
@@ -51,131 +49,255 @@ pub enum PropertyValueType {
     Real,
 }
 
-impl PropertyValueType {
-    fn new(t: char) -> Self {
+impl TryFrom<char> for PropertyValueType {
+    type Error = anyhow::Error;
+
+    fn try_from(t: char) -> Result<Self, Self::Error> {
         match t {
-            'S' => Self::String,
-            'I' => Self::Integer,
-            'R' => Self::Real,
-            'L' => Self::Logical,
-            _ => todo!(),
+            'S' => Ok(Self::String),
+            'I' => Ok(Self::Integer),
+            'R' => Ok(Self::Real),
+            'L' => Ok(Self::Logical),
+            _ => anyhow::bail!("invalid property type {t:?}: expect one of S, I, R, L"),
         }
     }
 }
+
+impl PropertyValueType {
+    /// Return the type letter used in `Properties` values.
+    pub fn to_char(self) -> char {
+        match self {
+            Self::String => 'S',
+            Self::Integer => 'I',
+            Self::Real => 'R',
+            Self::Logical => 'L',
+        }
+    }
+}
+
+impl std::fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.r#type.to_char(), self.num_columns)
+    }
+}
 // 823b4ece ends here
 
 // [[file:../../extxyz.note::9a7ccb4b][9a7ccb4b]]
-fn property_value<'i>(input: &mut Stream<'i>) -> PResult<PropertyValue> {
-    use winnow::ascii::alphanumeric1;
-    use winnow::ascii::digit1;
-    use winnow::combinator::seq;
-    use winnow::combinator::terminated;
-    use winnow::token::one_of;
+// property names are bare strings without `:` separator
+fn is_property_name_char(chr: char) -> bool {
+    is_bare_char(chr) && chr != ':'
+}
+
+fn property_value(name: &str, type_code: &str, num_columns: &str) -> anyhow::Result<PropertyValue> {
+    use anyhow::Context;
 
     // names the column(s)
-    let name = alphanumeric1;
+    anyhow::ensure!(
+        !name.is_empty() && name.chars().all(is_property_name_char),
+        "invalid property name {name:?}"
+    );
     // indicates the type in the column
-    let t_columns = one_of(['S', 'I', 'R', 'L']);
+    let mut chars = type_code.chars();
+    let r#type = match (chars.next(), chars.next()) {
+        (Some(t), None) => PropertyValueType::try_from(t).with_context(|| format!("invalid type of property {name:?}"))?,
+        _ => anyhow::bail!("invalid type {type_code:?} of property {name:?}: expect one of S, I, R, L"),
+    };
     // specifying how many consecutive columns are being referred to
-    let n_columns = digit1;
-    let (name, type_char, num) = (terminated(name, ":"), terminated(t_columns, ":"), n_columns).parse_next(input)?;
+    let num_columns = num_columns
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+        .with_context(|| format!("invalid number of columns {num_columns:?} of property {name:?}: expect a positive integer"))?;
+
     Ok(PropertyValue {
         name: name.to_string(),
-        r#type: PropertyValueType::new(type_char),
-        num_columns: num.parse().unwrap(),
+        r#type,
+        num_columns,
     })
 }
 
-fn parse_property_values<'i>(input: &mut Stream<'i>) -> PResult<Vec<PropertyValue>> {
-    separated(1.., property_value, ":").parse_next(input)
+/// Parse `Properties` value in `input`, which is a list of name:type:columns
+/// triples separated by `:`.
+fn parse_property_values(input: &str) -> anyhow::Result<Vec<PropertyValue>> {
+    let fields: Vec<_> = input.split(':').collect();
+    anyhow::ensure!(
+        fields.len() % 3 == 0,
+        "Properties should be name:type:columns triples, but found {} fields",
+        fields.len()
+    );
+
+    let mut properties: Vec<PropertyValue> = vec![];
+    for triple in fields.chunks(3) {
+        let property = property_value(triple[0], triple[1], triple[2])?;
+        anyhow::ensure!(
+            properties.iter().all(|p| p.name != property.name),
+            "duplicate property {:?}",
+            property.name
+        );
+        properties.push(property);
+    }
+    Ok(properties)
 }
 
 #[test]
-fn test_parse_properties() -> PResult<()> {
+fn test_parse_properties() -> anyhow::Result<()> {
     let input = "species:S:1:pos:R:3";
-    let (_, properties) = parse_property_values.parse_peek(input)?;
+    let properties = parse_property_values(input)?;
     assert_eq!(properties.len(), 2);
 
+    let properties = parse_property_values("species:S:1:pos:R:3:initial_magmoms:R:1:some-label:L:2")?;
+    assert_eq!(properties[2].name, "initial_magmoms");
+    assert_eq!(properties[3].num_columns, 2);
+
+    for (input, error) in [
+        ("species:S:1:pos:X:3", "invalid type of property \"pos\""),
+        ("species:S:1:pos:RR:3", "invalid type \"RR\""),
+        ("species:S:1:pos:R:0", "invalid number of columns \"0\""),
+        ("species:S:1:pos:R:x", "invalid number of columns \"x\""),
+        ("species:S:1:pos:R", "found 5 fields"),
+        ("species:S:1:species:S:1", "duplicate property"),
+        ("species:S:1::R:3", "invalid property name"),
+    ] {
+        let e = parse_property_values(input).unwrap_err();
+        assert!(e.to_string().contains(error), "{input}: {e}");
+    }
+
     Ok(())
 }
 // 9a7ccb4b ends here
 
 // [[file:../../extxyz.note::9ecc3cf5][9ecc3cf5]]
 // extract "Lattice" entry and apply semantic conversions
-// 9ecc3cf5 ends here
-
-// [[file:../../extxyz.note::78659ab1][78659ab1]]
-fn parse_extra_atom_data(input: &str, info: &Info) -> anyhow::Result<serde_json::Map<String, Value>> {
-    use winnow::combinator::preceded;
-    use winnow::error::ContextError;
-    use winnow::error::ErrMode;
-
-    use PropertyValueType::*;
-
-    let mut map = serde_json::Map::new();
-    let mut s = input.trim();
-    let atom_properties = info.get_properties()?;
-    let e_any = |e: ErrMode<ContextError>| anyhow!(e.to_string());
-    for col in atom_properties {
-        let mut real_value = preceded(space0, recognize_sci_float).try_map(|x| x.parse::<f64>());
-        let mut bool_value = preceded(space0, recognize_boolean).try_map(|x| x.parse::<bool>());
-        let mut isize_value = preceded(space0, recognize_integer).try_map(|x| x.parse::<isize>());
-        let value = match (&col.name[..], col.r#type, col.num_columns) {
-            // ignore element and positions columns
-            ("species" | "pos", _, _) => Value::Null,
-            (_, Real, 1) => json!(real_value.parse_next(&mut s).map_err(e_any)?),
-            (_, Integer, 1) => json!(isize_value.parse_next(&mut s).map_err(e_any)?),
-            (_, Logical, 1) => json!(bool_value.parse_next(&mut s).map_err(e_any)?),
-            (_, String, 1) => json!(bare_string.parse_next(&mut s).map_err(e_any)?),
-            (_, Real, num_col) if num_col > 1 => {
-                let v: Vec<_> = separated(num_col, real_value, space1).parse_next(&mut s).map_err(e_any)?;
-                json!(v)
-            }
-            (_, Integer, num_col) if num_col > 1 => {
-                let v: Vec<_> = separated(num_col, isize_value, space1).parse_next(&mut s).map_err(e_any)?;
-                json!(v)
-            }
-            (_, Logical, num_col) if num_col > 1 => {
-                let v: Vec<_> = separated(num_col, bool_value, space1).parse_next(&mut s).map_err(e_any)?;
-                json!(v)
+fn flatten_values<T>(value: &Value, f: impl Fn(&Value) -> Option<T>) -> Option<Vec<T>> {
+    let mut values = vec![];
+    for item in value.as_array()? {
+        match item {
+            // nested array in new style, such as [[1, 0, 0], [0, 1, 0], [0, 0, 1]]
+            Value::Array(row) => {
+                for x in row {
+                    values.push(f(x)?);
+                }
             }
-            (_, String, num_col) if num_col > 1 => {
-                let v: Vec<_> = separated(num_col, bare_string, space1).parse_next(&mut s).map_err(e_any)?;
-                json!(v)
-            }
-            _ => anyhow::bail!("invalid column data ({s:?}) or column info: {info:?}"),
+            _ => values.push(f(item)?),
+        }
+    }
+    Some(values)
+}
+
+impl Info {
+    /// Return lattice vectors in rows from `Lattice` entry. Return
+    /// None if it is absent or invalid.
+    pub fn lattice(&self) -> Option<[[f64; 3]; 3]> {
+        self.try_lattice().ok().flatten()
+    }
+
+    /// Return periodic boundary conditions from `pbc` entry. According
+    /// to the spec, it defaults to `T T T` if `Lattice` entry is present.
+    /// Return None if both are absent or any of them is invalid.
+    pub fn pbc(&self) -> Option<[bool; 3]> {
+        self.try_pbc().ok().flatten()
+    }
+
+    /// Return lattice vectors from `Lattice` entry, or an error if it
+    /// is not 9 real numbers.
+    pub fn try_lattice(&self) -> anyhow::Result<Option<[[f64; 3]; 3]>> {
+        let Some(value) = self.dict.get("Lattice") else {
+            return Ok(None);
         };
-        if value != Value::Null {
-            map.insert(col.name.into(), value);
+        let values = flatten_values(value, |x| x.as_f64()).unwrap_or_default();
+        anyhow::ensure!(values.len() == 9, "Lattice entry should have 9 real numbers, but found: {value}");
+
+        let mut lattice = [[0.0; 3]; 3];
+        for (row, chunk) in lattice.iter_mut().zip(values.chunks(3)) {
+            row.copy_from_slice(chunk);
         }
+        Ok(Some(lattice))
     }
 
-    Ok(map)
+    /// Return periodic boundary conditions, or an error if `pbc` entry
+    /// is not 3 logical values.
+    pub fn try_pbc(&self) -> anyhow::Result<Option<[bool; 3]>> {
+        let Some(value) = self.dict.get("pbc") else {
+            let pbc = self.try_lattice()?.map(|_| [true; 3]);
+            return Ok(pbc);
+        };
+        let values = flatten_values(value, |x| x.as_bool()).unwrap_or_default();
+        anyhow::ensure!(values.len() == 3, "pbc entry should have 3 logical values, but found: {value}");
+        Ok(Some([values[0], values[1], values[2]]))
+    }
 }
-// 78659ab1 ends here
+
+#[test]
+fn test_info_lattice() -> anyhow::Result<()> {
+    let info: Info = r#"Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Time=0.0"#.parse()?;
+    assert_eq!(info.lattice(), Some([[5.44, 0.0, 0.0], [0.0, 5.44, 0.0], [0.0, 0.0, 5.44]]));
+    assert_eq!(info.pbc(), Some([true; 3]));
+
+    let info: Info = r#"Lattice=[[5.44,0,0],[0,5.44,0],[0,0,5.44]] pbc="T F T""#.parse()?;
+    assert_eq!(info.lattice().unwrap()[1][1], 5.44);
+    assert_eq!(info.pbc(), Some([true, false, true]));
+
+    let info: Info = "Time=0.0".parse()?;
+    assert_eq!(info.lattice(), None);
+    assert_eq!(info.pbc(), None);
+
+    let info: Info = r#"Lattice="5.44 0.0 0.0" pbc="T T""#.parse()?;
+    assert!(info.try_lattice().is_err());
+    assert!(info.try_pbc().is_err());
+    assert_eq!(info.lattice(), None);
+
+    Ok(())
+}
+// 9ecc3cf5 ends here
 
 // [[file:../../extxyz.note::ce5ca27d][ce5ca27d]]
+use winnow::ascii::multispace0;
+use winnow::combinator::cut_err;
 use winnow::combinator::delimited;
 use winnow::combinator::opt;
+use winnow::combinator::preceded;
+use winnow::combinator::repeat;
+use winnow::error::{ContextError, ErrMode};
 use winnow::stream::AsChar;
+use winnow::token::any;
 use winnow::token::take_till;
 use winnow::token::take_while;
 
-fn bare_string<'s>(input: &mut Stream<'s>) -> PResult<Stream<'s>> {
-    take_while(1.., ('a'..='z', 'A'..='z', '0'..='9', '_', '-')).parse_next(input)
+// bare strings contain no whitespace, quotes, equals signs, commas,
+// square or curly brackets, or backslashes
+pub(crate) fn is_bare_char(chr: char) -> bool {
+    !(chr.is_space() || chr.is_newline() || matches!(chr, '"' | '=' | ',' | '[' | ']' | '{' | '}' | '\\'))
+}
+
+/// Parse bare (unquoted) string for key or value
+fn bare_token<'s>(input: &mut Stream<'s>) -> PResult<Stream<'s>> {
+    take_while(1.., is_bare_char).parse_next(input)
 }
 
 // one key=value pair on second comment line
-fn key_value<'s>(i: &mut Stream<'s>) -> PResult<(Stream<'s>, Stream<'s>)> {
+fn key_value(i: &mut Stream) -> PResult<(String, Value)> {
     // Key: bare or quoted string
-    let key = alt((quoted_string, bare_string)).parse_next(i)?;
+    let key = alt((quoted_string, bare_token.map(|s: &str| s.to_string()))).parse_next(i)?;
     // spaces are allowed around = sign, which do not become part of the key or value.
-    let _ = (opt(space0), "=", opt(space0)).parse_next(i)?;
-    let mut normal_value = take_while(0.., not_whitespace);
-    let val = alt((quoted_string, normal_value)).parse_next(i)?;
+    // A bare key without value is a logical flag, such as `is_true`.
+    if opt((space0, "=")).parse_next(i)?.is_none() {
+        return Ok((key, Value::Bool(true)));
+    }
+    let val = preceded(space0, cut_err(extxyz_value)).parse_next(i)?;
     Ok((key, val))
 }
 
+fn extxyz_value(input: &mut Stream) -> PResult<Value> {
+    alt((
+        quoted_string.map(|s| parse_quoted_value(&s)),
+        new_style_array,
+        old_style_curly_array,
+        json_value,
+        bare_token.map(parse_plain_value),
+    ))
+    .parse_next(input)
+}
+
 /// Parse a non-empty block of text that doesn't include \ or "
 fn parse_string<'s>(input: &mut Stream<'s>) -> PResult<Stream<'s>> {
     let not_quote_slash = take_till(1.., ['"', '\\']);
@@ -184,20 +306,29 @@ fn parse_string<'s>(input: &mut Stream<'s>) -> PResult<Stream<'s>> {
     Ok(s)
 }
 
-fn not_whitespace(chr: char) -> bool {
-    !(chr.is_space() || chr.is_newline())
+/// Parse escaped character after backslash: `\n` is newline, `\"` and
+/// `\\` are the quote and the backslash. Other backslashes are kept.
+fn escaped_char(input: &mut Stream) -> PResult<String> {
+    let c = preceded('\\', any).parse_next(input)?;
+    let s = match c {
+        'n' => "\n".to_string(),
+        '"' | '\\' => c.to_string(),
+        _ => format!("\\{c}"),
+    };
+    Ok(s)
 }
 
-/// quoted string (starting and ending with double quote and containing only allowed characters)
-fn quoted_string<'s>(input: &mut Stream<'s>) -> PResult<Stream<'s>> {
-    let r = delimited('"', parse_string, '"').parse_next(input)?;
-    Ok(r)
+/// quoted string (starting and ending with double quote and containing
+/// only allowed characters), with backslash escapes resolved.
+pub(crate) fn quoted_string(input: &mut Stream) -> PResult<String> {
+    let chunk = alt((parse_string.map(|s: &str| s.to_string()), escaped_char));
+    let chunks: Vec<String> = delimited('"', repeat(0.., chunk), '"').parse_next(input)?;
+    Ok(chunks.concat())
 }
 
 /// Parse key=value pairs in extxyz comment line
-pub fn parse_key_value_pairs<'s>(input: &mut Stream<'s>) -> PResult<Vec<(Stream<'s>, Stream<'s>)>> {
-    let r = separated(0.., key_value, space1).parse_next(input)?;
-    Ok(r)
+pub fn parse_key_value_pairs(input: &mut Stream) -> PResult<Vec<(String, Value)>> {
+    delimited(multispace0, separated(0.., key_value, space1), multispace0).parse_next(input)
 }
 
 #[test]
@@ -213,10 +344,129 @@ fn test_key_value() -> PResult<()> {
     let s = r#""real quoted"="3.14" array_complex="1 2 3" nested="[[1], [2], [3]]""#;
     let (_, r) = parse_key_value_pairs.parse_peek(s)?;
     assert_eq!(r.len(), 3);
+
+    let s = r#""with \"escaped\" quote\\ and\nnewline""#;
+    let (_, r) = quoted_string.parse_peek(s)?;
+    assert_eq!(r, "with \"escaped\" quote\\ and\nnewline");
+
+    // flags and whitespace around `=`
+    let s = " is_true a = 1 b =[1, 2] ";
+    let (rest, r) = parse_key_value_pairs.parse_peek(s)?;
+    assert!(rest.is_empty());
+    assert_eq!(r.len(), 3);
+    assert_eq!(r[0], ("is_true".to_string(), Value::Bool(true)));
     Ok(())
 }
 // ce5ca27d ends here
 
+// [[file:../../extxyz.note::5e1c0b7a][5e1c0b7a]]
+/// Check type homogeneity of array `values`: all elements should be
+/// logicals, strings or numbers. Integers are promoted to reals if
+/// mixed with reals.
+fn homogeneous_array(values: Vec<Value>) -> Option<Vec<Value>> {
+    let all = |f: fn(&Value) -> bool| values.iter().all(f);
+    if all(Value::is_boolean) || all(Value::is_string) || all(|x| x.is_i64() || x.is_u64()) {
+        Some(values)
+    } else if all(Value::is_number) {
+        values.iter().map(|x| x.as_f64().map(Value::from)).collect()
+    } else {
+        None
+    }
+}
+
+/// Check type homogeneity of 2-D array in `rows`, which should have the
+/// same length.
+fn homogeneous_2d_array(rows: Vec<Vec<Value>>) -> Option<Value> {
+    let n = rows.first()?.len();
+    if n == 0 || rows.iter().any(|row| row.len() != n) {
+        return None;
+    }
+    let values = homogeneous_array(rows.concat())?;
+    Some(values.chunks(n).map(|row| Value::Array(row.to_vec())).collect())
+}
+
+/// Parse 1-D array in new style, such as [1, 2, 3] or ["a", "b"]
+fn one_d_array(input: &mut Stream) -> PResult<Vec<Value>> {
+    let element = alt((quoted_string.map(Value::String), bare_token.map(parse_plain_value)));
+    let sep = (space0, ',', space0);
+    delimited(('[', space0), separated(0.., element, sep), (space0, ']')).parse_next(input)
+}
+
+/// Parse 1-D or 2-D array in new style, such as [[1, 2], [3, 4]]
+fn new_style_array(input: &mut Stream) -> PResult<Value> {
+    let sep = (space0, ',', space0);
+    let two_d = delimited(('[', space0), separated(1.., one_d_array, sep), (space0, ']'));
+    let one_d = one_d_array.verify_map(homogeneous_array).map(Value::Array);
+    alt((two_d.verify_map(homogeneous_2d_array), one_d)).parse_next(input)
+}
+
+/// Parse 1-D array in old style quoted with curly braces, such as {1 2 3}
+fn old_style_curly_array(input: &mut Stream) -> PResult<Value> {
+    delimited('{', take_till(0.., ['{', '}', '"']), '}')
+        .verify_map(|s: &str| {
+            let values: Vec<_> = s.split(|c: char| c.is_whitespace() || c == ',').filter(|x| !x.is_empty()).map(parse_plain_value).collect();
+            if values.is_empty() {
+                None
+            } else {
+                homogeneous_array(values)
+            }
+        })
+        .map(Value::Array)
+        .parse_next(input)
+}
+
+// 1-D or 2-D arrays of scalars are covered by the spec, which should be
+// homogeneous.
+fn is_spec_array(value: &Value) -> bool {
+    let is_scalar = |x: &Value| matches!(x, Value::Bool(_) | Value::Number(_) | Value::String(_));
+    match value {
+        Value::Array(rows) => rows.iter().all(|x| is_scalar(x) || x.as_array().is_some_and(|row| row.iter().all(is_scalar))),
+        _ => false,
+    }
+}
+
+/// Parse JSON array or object, which is beyond the spec but used for
+/// nested data not representable otherwise.
+fn json_value(input: &mut Stream) -> PResult<Value> {
+    let mut stream = serde_json::Deserializer::from_str(input).into_iter::<Value>();
+    match stream.next() {
+        Some(Ok(v @ (Value::Array(_) | Value::Object(_)))) if !is_spec_array(&v) => {
+            *input = &input[stream.byte_offset()..];
+            Ok(v)
+        }
+        _ => Err(ErrMode::Backtrack(ContextError::new())),
+    }
+}
+
+#[test]
+fn test_new_style_array() -> PResult<()> {
+    let (_, x) = new_style_array.parse_peek("[1, 2,3]")?;
+    assert_eq!(x, serde_json::json!([1, 2, 3]));
+    // promoted to reals
+    let (_, x) = new_style_array.parse_peek("[1, 2.5]")?;
+    assert_eq!(x, serde_json::json!([1.0, 2.5]));
+    let (_, x) = new_style_array.parse_peek(r#"[[1, 0], [0, 1.0]]"#)?;
+    assert_eq!(x, serde_json::json!([[1.0, 0.0], [0.0, 1.0]]));
+    let (_, x) = new_style_array.parse_peek(r#"[T, F, True]"#)?;
+    assert_eq!(x, serde_json::json!([true, false, true]));
+    let (_, x) = new_style_array.parse_peek(r#"["a b", c]"#)?;
+    assert_eq!(x, serde_json::json!(["a b", "c"]));
+    // not homogeneous
+    assert!(new_style_array.parse_peek("[1, T]").is_err());
+    assert!(new_style_array.parse_peek("[[1, 2], [3]]").is_err());
+    assert!(new_style_array.parse_peek("[[1, 2], [T, F]]").is_err());
+
+    let (_, x) = old_style_curly_array.parse_peek("{1 2 3}")?;
+    assert_eq!(x, serde_json::json!([1, 2, 3]));
+    let (rest, x) = json_value.parse_peek(r#"{"a":[1,2]} b=1"#)?;
+    assert_eq!(x["a"][1], 2);
+    assert_eq!(rest, " b=1");
+    assert!(json_value.parse_peek("[[1, 2], [3]]").is_err());
+
+    Ok(())
+}
+// 5e1c0b7a ends here
+
 // [[file:../../extxyz.note::dd9bed2f][dd9bed2f]]
 fn recognize_old_one_d_array<'i>(input: &mut Stream<'i>) -> PResult<Vec<String>> {
     use winnow::ascii::space1;
@@ -228,7 +478,7 @@ fn recognize_old_one_d_array<'i>(input: &mut Stream<'i>) -> PResult<Vec<String>>
     let sep = alt((space1, comma));
     let boolean = recognize_boolean.map(|s| s.to_string());
     let string = parse_string.map(|s| s.to_string());
-    let mut list_values = separated(2.., alt((recognize_sci_float, boolean, string)), sep);
+    let list_values = separated(2.., alt((recognize_sci_float, boolean, string)), sep);
     let values = delimited(opt(one_of(['[', '{'])), list_values, opt(one_of([']', '}']))).parse_next(input)?;
     Ok(values)
 }
@@ -241,6 +491,7 @@ fn test_parse_1d_array() -> PResult<()> {
 
     let input = "[5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44]";
     let (_, x) = recognize_old_one_d_array.parse_peek(input)?;
+    assert_eq!(x.len(), 9);
 
     let input = "{5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44}";
     let (_, x) = recognize_old_one_d_array.parse_peek(input)?;
@@ -290,7 +541,7 @@ fn reformat_extxyz_value(input: &str) -> String {
     }
 
     if let Ok(recognized) = reformat_old_style_array(input) {
-        return recognized.into();
+        return recognized;
     }
 
     input.to_string()
@@ -319,29 +570,40 @@ fn test_extxyz_value() -> PResult<()> {
 // 1e59b3a0 ends here
 
 // [[file:../../extxyz.note::68a854b3][68a854b3]]
+/// Interpret `s` as a number, a logical, an old style 1-D array
+/// separated by whitespace, or as a string otherwise.
+pub(crate) fn parse_plain_value(s: &str) -> Value {
+    match reformat_extxyz_value(s).parse::<Value>() {
+        // Special case: not-list="1.2 2 T"
+        Ok(Value::Array(values)) => homogeneous_array(values).map(Value::Array).unwrap_or_else(|| s.into()),
+        Ok(v @ (Value::Number(_) | Value::Bool(_))) => v,
+        _ => s.into(),
+    }
+}
+
+/// Interpret the content `s` of quoted string.
+pub(crate) fn parse_quoted_value(s: &str) -> Value {
+    // ASE style: user-data="_JSON [1, 2, 3]"
+    if let Some(json) = s.strip_prefix("_JSON ") {
+        return json.parse().unwrap_or_else(|_| s.into());
+    }
+    let mut array = delimited(multispace0, alt((new_style_array, old_style_curly_array, json_value)), multispace0);
+    array.parse(s).unwrap_or_else(|_| parse_plain_value(s))
+}
+
 fn parse_extxyz_title<'s>(title: &mut Stream<'s>) -> PResult<Info> {
     let kv_pairs = parse_key_value_pairs.parse_next(title)?;
 
     let mut info = Info::default();
     for (k, v) in kv_pairs {
-        let mut v = reformat_extxyz_value(v);
-        let k = k.to_string();
-        // ASE style: user-data="_JSON [1, 2, 3]"
-        if v.starts_with("_JSON ") {
-            v = v[5..].to_string();
-        }
-        // convert value in string to json typed `Value`
-        if let Ok(v_parsed) = v.parse::<Value>() {
-            info.dict.insert(k, v_parsed);
-        } else {
-            info.dict.insert(k, v.into());
-        }
+        info.dict.insert(k, v);
     }
 
     Ok(info)
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_parse_extxyz_title() -> PResult<()> {
     let mut s = r#"Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3 Time=0.0 pbc="T T T""#;
     let info = parse_extxyz_title(&mut s)?;
@@ -362,8 +624,8 @@ fn test_parse_extxyz_title() -> PResult<()> {
     assert_eq!(info.dict["real quoted"], 3.14);
     assert_eq!(info.dict["array"][0], 1.2);
     assert_eq!(info.dict["nested"][0][0], 1);
-    assert_eq!(info.dict["special"][0], 1);
-    assert_eq!(info.dict["special"][2], true);
+    // mixed types are not allowed in array
+    assert_eq!(info.dict["special"], "1 2 T");
 
     let mut s = r#"Lattice="10.83 0.0 0.0 0.0 10.83 0.0 0.0 0.0 10.83" Properties=forces:R:3:energies:R:1 user-data="_JSON [1, 2, 3]" energy=0.634"#;
     let info = parse_extxyz_title(&mut s)?;
@@ -374,18 +636,40 @@ fn test_parse_extxyz_title() -> PResult<()> {
 // 68a854b3 ends here
 
 // [[file:../../extxyz.note::a15396a3][a15396a3]]
+fn comment_error(e: winnow::error::ParseError<&str, ContextError>) -> crate::ParseError {
+    let message = match e.inner().to_string() {
+        m if m.is_empty() => "expect key=value pairs".to_string(),
+        m => m,
+    };
+    crate::ParseError::new(crate::ParseErrorKind::InvalidComment, message, e.input(), e.offset())
+}
+
 impl std::str::FromStr for Info {
     type Err = anyhow::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        parse_extxyz_title.parse(input).map_err(|e| {
-            let error = e.to_string();
-            anyhow::anyhow!("Parse extxyz comment line failure:\n{error}\ninput={input:?}")
-        })
+        let info = parse_extxyz_title.parse(input).map_err(comment_error)?;
+        Ok(info)
     }
 }
 
 impl Info {
+    /// Parse extxyz comment line in `input` with `options`. Duplicated
+    /// keys are errors in strict mode; in lenient mode the last value
+    /// wins with a warning.
+    pub fn parse_with(input: &str, options: &crate::ParseOptions) -> anyhow::Result<(Self, Vec<crate::ParseWarning>)> {
+        let pairs = parse_key_value_pairs.parse(input).map_err(comment_error)?;
+
+        let mut diag = crate::options::Diagnostics::new(options).with_input(input);
+        let mut info = Info::default();
+        for (k, v) in pairs {
+            if info.dict.insert(k.clone(), v).is_some() {
+                diag.report(crate::ParseErrorKind::DuplicateKey, None, format!("duplicate key {k:?} in comment line"))?;
+            }
+        }
+        Ok((info, diag.finish()))
+    }
+
     /// Returns the associated value of `key`.
     pub fn get(&self, key: &str) -> Option<&Value> {
         self.dict.get(key)
@@ -396,19 +680,16 @@ impl Info {
         self.dict.remove(key)
     }
 
-    /// Return parsed per-atom properties
-    fn get_properties(&self) -> anyhow::Result<Vec<PropertyValue>> {
-        let properties = if let Some(Value::String(properties)) = self.dict.get("Properties") {
-            properties
-        } else {
-            "species:S:1:pos:R:3"
+    /// Return per-atom properties parsed from `Properties` entry, which
+    /// defines the columns in atom lines. According to the spec, it
+    /// defaults to `species:S:1:pos:R:3` if absent.
+    pub fn properties(&self) -> anyhow::Result<Vec<PropertyValue>> {
+        let properties = match self.dict.get("Properties") {
+            Some(Value::String(properties)) => properties,
+            Some(value) => anyhow::bail!("Properties entry should be a string, but found: {value}"),
+            None => "species:S:1:pos:R:3",
         };
-        let property_values = parse_property_values.parse(properties).map_err(|e| {
-            let error = e.to_string();
-            anyhow::anyhow!("failed to parse extxyz properties:\n{error}\ninput={properties:?}")
-        })?;
-
-        Ok(property_values)
+        parse_property_values(properties).map_err(|e| anyhow::anyhow!("failed to parse extxyz properties: {e}\ninput={properties:?}"))
     }
 
     /// Return reference to inner `Map`
@@ -423,9 +704,13 @@ impl Info {
 }
 
 impl Info {
-    /// Parse atom properties from extra columns in `extra`.
+    /// Parse atom properties from extra columns in `extra`. If element
+    /// and positions are not the leading columns, `extra` is the whole
+    /// atom line as in `RawAtom`. This is `parse_extra_columns_with` in
+    /// strict mode, so trailing columns are not allowed.
     pub fn parse_extra_columns(&self, extra: &str) -> anyhow::Result<serde_json::Map<String, Value>> {
-        parse_extra_atom_data(extra, &self)
+        let (map, _) = self.parse_extra_columns_with(extra, &crate::ParseOptions::strict())?;
+        Ok(map)
     }
 }
 // a15396a3 ends here
@@ -443,8 +728,15 @@ fn test_extxyz_info() -> anyhow::Result<()> {
     assert_eq!(atom_properties["forces"][0], 0.03244218);
     assert_eq!(atom_properties["freeze"], false);
 
-    let s = r#"Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:freeze:L:1 Time=0.0"#;
+    let e = "a=1 b=[1, T]".parse::<Info>().unwrap_err().downcast::<crate::ParseError>().unwrap();
+    assert_eq!(e.kind, crate::ParseErrorKind::InvalidComment);
+    assert_eq!(e.column, 7);
+
     let info: Info = "Properties=species:S:1:pos:R:3:Z:I:1:masses:R:1".parse()?;
+    let properties = info.properties()?;
+    assert_eq!(properties[2].name, "Z");
+    assert_eq!(properties[2].r#type, PropertyValueType::Integer);
+    assert_eq!(properties[1].to_string(), "pos:R:3");
     let extra = "1 1.00800000";
     let atom_properties = info.parse_extra_columns(extra)?;
     assert_eq!(atom_properties.len(), 2);
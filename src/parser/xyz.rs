@@ -2,7 +2,7 @@
 use super::{label, recognize_sci_float};
 use crate::{RawAtom, RawAtoms};
 
-use winnow::ascii::{space0, space1};
+use winnow::ascii::space1;
 use winnow::combinator::alt;
 use winnow::combinator::cut_err;
 use winnow::combinator::separated;
@@ -17,11 +17,9 @@ type Stream<'i> = &'i str;
 fn parse_xyz_line<'s>(frame_text: &mut Stream<'s>) -> PResult<RawAtom<'s>> {
     use winnow::ascii::alpha1;
     use winnow::ascii::digit1;
-    use winnow::ascii::line_ending;
     use winnow::ascii::space0;
     use winnow::ascii::till_line_ending;
     use winnow::combinator::delimited;
-    use winnow::combinator::terminated;
 
     // element symbol or number
     let sym_or_num = alt((alpha1, digit1));
@@ -40,29 +38,26 @@ fn parse_xyz_line<'s>(frame_text: &mut Stream<'s>) -> PResult<RawAtom<'s>> {
     Ok(atom)
 }
 
-// num_of_atoms, comment_line, atoms_list
-fn parse_xyz_frame<'s>(frame_text: &mut Stream<'s>) -> PResult<(usize, &'s str, Vec<RawAtom<'s>>)> {
-    use winnow::ascii::alpha1;
-    use winnow::ascii::digit1;
+// num_of_atoms, comment_line, atom_lines
+pub(crate) fn split_xyz_frame(input: &str) -> Result<(usize, &str, &str), ParseError> {
+    use ParseErrorKind::*;
+
+    let missing_atoms = || ParseError::new(MissingAtoms, "expect natoms line, comment line and atom lines", input, input.len());
+    let (natoms_line, rest) = input.split_once('\n').ok_or_else(missing_atoms)?;
+    let token = natoms_line.trim();
+    let natoms = token.parse().map_err(|e| ParseError::at_token(InvalidNatoms, format!("{e}: {token:?}"), input, token))?;
+    let (comment, atom_lines) = rest.split_once('\n').ok_or_else(missing_atoms)?;
+    Ok((natoms, comment.trim_end_matches('\r'), atom_lines))
+}
+
+// atoms_list with element and positions in leading columns
+fn parse_xyz_atoms<'s>(frame_text: &mut Stream<'s>) -> PResult<Vec<RawAtom<'s>>> {
     use winnow::ascii::line_ending;
-    use winnow::ascii::space0;
-    use winnow::ascii::till_line_ending;
-    use winnow::combinator::delimited;
-    use winnow::combinator::terminated;
 
-    let natoms = delimited(space0, digit1, space0).try_map(|s: &str| s.parse::<usize>());
-    let natoms = cut_err(terminated(natoms, line_ending))
-        .context(label("num of atoms"))
-        .parse_next(frame_text)?;
-    let comment = cut_err(terminated(till_line_ending, line_ending))
-        .context(label("comment line"))
-        .parse_next(frame_text)?;
     // NOTE: We supposed that there is no line ending in the end of `frame_text`
-    let atoms: Vec<_> = separated(1.., cut_err(parse_xyz_line), line_ending)
+    separated(1.., cut_err(parse_xyz_line), line_ending)
         .context(label("atom list"))
-        .parse_next(frame_text)?;
-
-    Ok((natoms, comment, atoms))
+        .parse_next(frame_text)
 }
 // 739400bd ends here
 
@@ -70,29 +65,181 @@ fn parse_xyz_frame<'s>(frame_text: &mut Stream<'s>) -> PResult<(usize, &'s str,
 impl<'s> RawAtom<'s> {
     /// Parse `RawAtom` from xyz line `input` in xyz format.
     pub fn parse_from(input: &'s str) -> anyhow::Result<Self> {
-        use anyhow::anyhow;
-
+        let input = input.trim_end();
         let atom = parse_xyz_line
-            .parse(input.trim_end())
-            .map_err(|e| anyhow!("parse xyz atom errors:\n{:}\ninput={input:?}", e.to_string()))?;
+            .parse(input)
+            .map_err(|e| ParseError::new(ParseErrorKind::InvalidAtomLine, e.inner().to_string(), input, e.offset()))?;
         Ok(atom)
     }
 }
 
 impl<'s> RawAtoms<'s> {
     /// Parse `RawAtoms` from a complete xyz frame `input` in xyz format.
+    ///
+    /// If the comment line is in extxyz format, the element and
+    /// position columns are located according to `Properties` entry.
+    /// This is the same as `parse_with` in lenient mode, ignoring
+    /// warnings.
     pub fn parse_from(input: &'s str) -> anyhow::Result<Self> {
-        use anyhow::anyhow;
+        let (atoms, _) = Self::parse_with(input, &ParseOptions::lenient())?;
+        Ok(atoms)
+    }
 
-        let (natoms, comment, atoms) = parse_xyz_frame
-            // Remove the trailing new lines, so that do not break `separated` parser
-            .parse(input.trim_end())
-            .map_err(|e| anyhow!("parse xyz atoms error:\n{}\ninput={input:?}", e.to_string()))?;
-        Ok(Self { natoms, comment, atoms })
+    /// Parse `RawAtoms` from a complete xyz frame `input` with
+    /// `options`. In strict mode, the number of atom lines must match
+    /// the natoms line, and `Properties` entry must be valid.
+    pub fn parse_with(input: &'s str, options: &ParseOptions) -> anyhow::Result<(Self, Vec<ParseWarning>)> {
+        let (_, comment, _) = split_xyz_frame(input.trim_end())?;
+        Self::parse_with_info(input, extxyz_info(comment).as_ref(), options)
+    }
+
+    /// Parse `RawAtoms` like `parse_with`, using `info` already parsed
+    /// from the comment line, or None if it is not in extxyz format.
+    pub(crate) fn parse_with_info(input: &'s str, info: Option<&Info>, options: &ParseOptions) -> anyhow::Result<(Self, Vec<ParseWarning>)> {
+        let mut diag = Diagnostics::new(options).with_input(input);
+        // Remove the trailing new lines, so that do not break `separated` parser
+        let (natoms, comment, s) = split_xyz_frame(input.trim_end())?;
+        let layout = match info {
+            Some(info) => AtomLayout::from_info(info)
+                .map(Some)
+                .or_else(|e| diag.report(ParseErrorKind::InvalidProperties, Some(2), format!("{e:#}")).map(|_| None))?,
+            None => None,
+        };
+        let atoms: Vec<_> = match layout {
+            Some(layout) if !layout.is_standard() => s
+                .lines()
+                .map(|line| layout.parse_atom_line(input, line))
+                .collect::<Result<_, _>>()?,
+            _ => parse_xyz_atoms.parse(s).map_err(|e| {
+                // locate the error in the whole frame
+                let offset = s.as_ptr() as usize - input.as_ptr() as usize + e.offset();
+                ParseError::new(ParseErrorKind::InvalidAtomLine, e.inner().to_string(), input, offset)
+            })?,
+        };
+        if atoms.len() != natoms {
+            diag.report(ParseErrorKind::NatomsMismatch, Some(1), format!("expect {natoms} atom lines, but found {}", atoms.len()))?;
+        }
+        Ok((Self { natoms, comment, atoms }, diag.finish()))
     }
 }
 // 690b8cfd ends here
 
+// [[file:../../extxyz.note::5b8e07c4][5b8e07c4]]
+use crate::error::{ParseError, ParseErrorKind};
+use crate::options::Diagnostics;
+use crate::parser::extxyz::{PropertyValue, PropertyValueType};
+use crate::{Info, ParseOptions, ParseWarning, Schema};
+
+// The column of element in atom line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ElementColumn {
+    // `species:S:1`
+    Symbol(usize),
+    // `Z:I:1`, used when `species` is absent
+    Number(usize),
+}
+
+/// Return `Info` parsed from `comment`, or None if it is not in extxyz
+/// format.
+pub(crate) fn extxyz_info(comment: &str) -> Option<Info> {
+    comment.contains('=').then(|| comment.parse().ok()).flatten()
+}
+
+/// Represents where the element and position columns are in atom line,
+/// according to `Properties` entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AtomLayout {
+    element: ElementColumn,
+    pos: usize,
+}
+
+impl AtomLayout {
+    fn new(schema: &Schema) -> anyhow::Result<Self> {
+        use anyhow::{bail, Context};
+        use PropertyValueType::*;
+
+        let column = |name: &str, t: PropertyValueType, width: usize| -> anyhow::Result<Option<usize>> {
+            match schema.get(name) {
+                Some(f) if f.r#type == t && f.width == width => Ok(Some(f.start)),
+                Some(f) => bail!("invalid property {name}:{}:{}, expect {name}:{}:{width}", f.r#type.to_char(), f.width, t.to_char()),
+                None => Ok(None),
+            }
+        };
+        let element = match (column("species", String, 1)?, column("Z", Integer, 1)?) {
+            (Some(i), _) => ElementColumn::Symbol(i),
+            (None, Some(i)) => ElementColumn::Number(i),
+            (None, None) => bail!("no species or Z column in Properties: {schema}"),
+        };
+        let pos = column("pos", Real, 3)?.with_context(|| format!("no pos column in Properties: {schema}"))?;
+        Ok(Self { element, pos })
+    }
+
+    /// Return the layout defined in `Properties` entry of `info`.
+    fn from_info(info: &Info) -> anyhow::Result<Self> {
+        Self::new(&info.schema()?)
+    }
+
+    /// Return the layout defined by parsed `properties` if element and
+    /// positions are not the leading columns, in which case `extra` of
+    /// `RawAtom` is the whole atom line.
+    pub(crate) fn reordered(properties: &[PropertyValue]) -> Option<Self> {
+        Self::new(&Schema::new(properties.iter().cloned())).ok().filter(|layout| !layout.is_standard())
+    }
+
+    /// Return true if element and positions are the leading columns,
+    /// which is the same as in plain xyz format.
+    fn is_standard(&self) -> bool {
+        self.element == ElementColumn::Symbol(0) && self.pos == 1
+    }
+
+    // Return true if column `i` is used for element symbol or positions
+    fn is_used(&self, i: usize) -> bool {
+        self.element == ElementColumn::Symbol(i) || (self.pos..self.pos + 3).contains(&i)
+    }
+
+    /// Return the extra columns in atom `line`, excluding the columns
+    /// for element symbol and positions.
+    pub(crate) fn extra_columns(self, line: &str) -> impl Iterator<Item = &str> {
+        line.split_ascii_whitespace().enumerate().filter(move |(i, _)| !self.is_used(*i)).map(|(_, t)| t)
+    }
+
+    // Parse atom `line` from frame text `input`
+    fn parse_atom_line<'s>(&self, input: &str, line: &'s str) -> Result<RawAtom<'s>, ParseError> {
+        use ParseErrorKind::InvalidAtomLine;
+
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        let token = |i: usize| {
+            tokens
+                .get(i)
+                .copied()
+                .ok_or_else(|| ParseError::at_token(InvalidAtomLine, format!("missing column {}", i + 1), input, &line[line.len()..]))
+        };
+        let element = match self.element {
+            ElementColumn::Symbol(i) => token(i)?,
+            ElementColumn::Number(i) => {
+                let z = token(i)?;
+                z.parse()
+                    .ok()
+                    .and_then(crate::element::element_symbol)
+                    .ok_or_else(|| ParseError::at_token(InvalidAtomLine, format!("invalid atomic number {z:?}"), input, z))?
+            }
+        };
+        let mut position = [0.0; 3];
+        for (k, x) in position.iter_mut().enumerate() {
+            let t = token(self.pos + k)?;
+            *x = crate::column::parse_real(t).map_err(|e| ParseError::at_token(InvalidAtomLine, format!("invalid position: {e}"), input, t))?;
+        }
+        // the columns for element symbol and positions are skipped later
+        // with the same layout
+        Ok(RawAtom {
+            element,
+            position,
+            extra: line.trim(),
+        })
+    }
+}
+// 5b8e07c4 ends here
+
 // [[file:../../extxyz.note::1978c77e][1978c77e]]
 #[test]
 fn test_parse_extxyz_frame() -> anyhow::Result<()> {
@@ -114,6 +261,62 @@ C        2.66063880       4.93469322       3.30108500
     assert_eq!(atoms.natoms, 128);
     assert_eq!(atoms.atoms.len(), 3);
 
+    // positions before species
+    let input = "2
+Properties=pos:R:3:tag:I:1:species:S:1:q:R:1
+ 0.0 0.0 1.0 7 Cu 0.5
+ 1.0 2D0 3.0 8 O -0.5
+";
+    let atoms = RawAtoms::parse_from(input)?;
+    assert_eq!(atoms.atoms[1].element, "O");
+    assert_eq!(atoms.atoms[1].position, [1.0, 2.0, 3.0]);
+    // the whole line is kept for extra columns
+    assert_eq!(atoms.atoms[1].extra, "1.0 2D0 3.0 8 O -0.5");
+    let info: Info = atoms.comment.parse()?;
+    let properties = info.parse_extra_columns(atoms.atoms[1].extra)?;
+    assert_eq!((&properties["tag"], &properties["q"]), (&8.into(), &(-0.5).into()));
+
+    // atomic numbers instead of species
+    let input = "1
+Properties=Z:I:1:pos:R:3
+29 0.0 0.0 1.0
+";
+    let atoms = RawAtoms::parse_from(input)?;
+    assert_eq!(atoms.atoms[0].element, "Cu");
+    // Z column is kept as a property
+    assert_eq!(atoms.atoms[0].extra, "29 0.0 0.0 1.0");
+    let info: Info = atoms.comment.parse()?;
+    assert_eq!(info.parse_atom_columns(&atoms.atoms)?["Z"], crate::Column::Integer(vec![29]));
+    assert!(RawAtoms::parse_from("1\nProperties=species:S:1:pos:R:2\nH 0.0 0.0\n").is_err());
+
+    // natoms mismatch
+    let input = "2\ncomment\nH 0.0 0.0 0.0\n";
+    let (atoms, warnings) = RawAtoms::parse_with(input, &ParseOptions::lenient())?;
+    assert_eq!(atoms.atoms.len(), 1);
+    assert_eq!(warnings[0].line, Some(1));
+    assert!(RawAtoms::parse_with(input, &ParseOptions::strict()).is_err());
+
+    // errors located in frame
+    let input = "2\ncomment\nH 0.0 0.0 0.0\nO 1.0 abc 0.0\n";
+    let e = RawAtoms::parse_from(input).unwrap_err();
+    let e = e.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(e.kind, ParseErrorKind::InvalidAtomLine);
+    assert_eq!((e.line, e.column), (4, 7));
+    assert_eq!(e.source_line, "O 1.0 abc 0.0");
+    let input = "2\nProperties=pos:R:3:species:S:1\n0.0 0.0 0.0 H\n1.0 x 0.0 O\n";
+    let e = RawAtoms::parse_from(input).unwrap_err().downcast::<ParseError>().unwrap();
+    assert_eq!((e.line, e.column), (4, 5));
+    let e = RawAtoms::parse_from(" 2x\ncomment\nH 0.0 0.0 0.0").unwrap_err().downcast::<ParseError>().unwrap();
+    assert_eq!((e.kind, e.line, e.column), (ParseErrorKind::InvalidNatoms, 1, 2));
+    let e = RawAtoms::parse_from("2\ncomment\n").unwrap_err().downcast::<ParseError>().unwrap();
+    assert_eq!(e.kind, ParseErrorKind::MissingAtoms);
+
+    // invalid layout
+    let input = "1\nProperties=species:S:1:pos:R:2:x:R:1\nH 0.0 0.0 0.0\n";
+    let (_, warnings) = RawAtoms::parse_with(input, &ParseOptions::lenient())?;
+    assert_eq!(warnings.len(), 1);
+    assert!(RawAtoms::parse_with(input, &ParseOptions::strict()).is_err());
+
     Ok(())
 }
 // 1978c77e ends here
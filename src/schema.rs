@@ -0,0 +1,155 @@
+// [[file:../extxyz.note::4f0c2d17][4f0c2d17]]
+use std::ops::Range;
+
+use anyhow::*;
+use serde::Serialize;
+
+use crate::parser::extxyz::{PropertyValue, PropertyValueType};
+use crate::Info;
+// 4f0c2d17 ends here
+
+// [[file:../extxyz.note::a83e5b90][a83e5b90]]
+/// Represents one property in the `Properties` schema, along with its
+/// location in atom line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaField {
+    /// The name of the property, such as `forces`
+    pub name: String,
+    /// The value type in the column(s)
+    pub r#type: PropertyValueType,
+    /// The number of consecutive columns for the property
+    pub width: usize,
+    /// The index of the first column in atom line, counting from zero
+    pub start: usize,
+}
+
+impl SchemaField {
+    /// Return the range of column indices in atom line.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.width
+    }
+}
+
+/// Represents the layout of columns in atom lines, as defined by the
+/// `Properties` entry in extxyz comment line.
+///
+/// # Example
+///
+/// ```
+/// let info: extxyz::Info = "Properties=species:S:1:pos:R:3:forces:R:3".parse().unwrap();
+/// let schema = info.schema().unwrap();
+/// assert_eq!(schema.column_range("forces"), Some(4..7));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Schema {
+    fields: Vec<SchemaField>,
+}
+
+impl Schema {
+    /// Construct `Schema` from parsed `properties` in order.
+    pub fn new(properties: impl IntoIterator<Item = PropertyValue>) -> Self {
+        let mut start = 0;
+        let fields = properties
+            .into_iter()
+            .map(|p| {
+                let field = SchemaField {
+                    name: p.name,
+                    r#type: p.r#type,
+                    width: p.num_columns,
+                    start,
+                };
+                start += p.num_columns;
+                field
+            })
+            .collect();
+        Self { fields }
+    }
+
+    /// Return all properties in the order of columns.
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+
+    /// Return the property with `name`.
+    pub fn get(&self, name: &str) -> Option<&SchemaField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Return the range of column indices of property `name` in atom
+    /// line, such as `4..7` for `forces` in
+    /// `species:S:1:pos:R:3:forces:R:3`.
+    pub fn column_range(&self, name: &str) -> Option<Range<usize>> {
+        self.get(name).map(|f| f.range())
+    }
+
+    /// Return the total number of columns in atom line.
+    pub fn num_columns(&self) -> usize {
+        self.fields.last().map(|f| f.start + f.width).unwrap_or(0)
+    }
+
+    /// Return the number of properties.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Return true if there is no property.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Return properties in `PropertyValue` format.
+    pub fn properties(&self) -> Vec<PropertyValue> {
+        self.fields
+            .iter()
+            .map(|f| PropertyValue {
+                name: f.name.clone(),
+                r#type: f.r#type,
+                num_columns: f.width,
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Schema {
+    /// Format as the value of `Properties` entry.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let items: Vec<_> = self.properties().iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", items.join(":"))
+    }
+}
+
+impl Info {
+    /// Return the columns layout in atom lines, according to the
+    /// `Properties` entry.
+    pub fn schema(&self) -> Result<Schema> {
+        Ok(Schema::new(self.properties()?))
+    }
+}
+// a83e5b90 ends here
+
+// [[file:../extxyz.note::e27c6d41][e27c6d41]]
+#[test]
+fn test_schema() -> Result<()> {
+    let info: Info = "Properties=species:S:1:pos:R:3:forces:R:3:freeze:L:1:m:R:2".parse()?;
+    let schema = info.schema()?;
+    assert_eq!(schema.len(), 5);
+    assert_eq!(schema.num_columns(), 10);
+    assert_eq!(schema.column_range("species"), Some(0..1));
+    assert_eq!(schema.column_range("pos"), Some(1..4));
+    assert_eq!(schema.column_range("forces"), Some(4..7));
+    assert_eq!(schema.column_range("m"), Some(8..10));
+    assert_eq!(schema.column_range("energy"), None);
+    assert_eq!(schema.get("freeze").unwrap().r#type, PropertyValueType::Logical);
+    assert_eq!(schema.to_string(), "species:S:1:pos:R:3:forces:R:3:freeze:L:1:m:R:2");
+
+    // pull columns from atom line directly
+    let tokens: Vec<_> = "Si 0.0 0.0 0.0 0.1 0.2 0.3 T 1.0 2.0".split_whitespace().collect();
+    assert_eq!(tokens[schema.column_range("forces").unwrap()], ["0.1", "0.2", "0.3"]);
+
+    // default schema
+    let info: Info = "energy=1.0".parse()?;
+    assert_eq!(info.schema()?.to_string(), "species:S:1:pos:R:3");
+
+    Ok(())
+}
+// e27c6d41 ends here
@@ -0,0 +1,550 @@
+// [[file:../extxyz.note::7a4e19c3][7a4e19c3]]
+use serde::de::value::Error;
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeStruct};
+use serde_json::Value;
+
+use crate::{Column, Frame, Info};
+// 7a4e19c3 ends here
+
+// [[file:../extxyz.note::d25b8e70][d25b8e70]]
+/// A `serde::Serializer` which turns a struct or map into `Frame`.
+///
+/// The entries are mapped as follows:
+/// * `symbols` (or `species`) and `positions` (or `pos`) are required
+/// * `lattice` (or `Lattice`) in 3x3 or 9 values, and `pbc` if present
+/// * `natoms` is ignored, which is always the number of symbols
+/// * entries in `info` or `arrays` map, if present, are put into frame
+///   info or arrays respectively
+/// * any sequence (such as `Vec`) with one item per atom is taken as
+///   per-atom property, with the column type inferred as `S`, `I`, `R`
+///   or `L`. An item could be an array for multiple columns, such as
+///   `[f64; 3]`.
+/// * any other entry goes to comment line, and `None` is skipped. Fixed
+///   size arrays and tuples, such as `dipole: [f64; 3]`, are always
+///   taken as per-frame values, even if the length equals the number of
+///   atoms.
+pub struct FrameSerializer;
+
+fn error(msg: impl std::fmt::Display) -> Error {
+    ser::Error::custom(msg)
+}
+
+fn not_a_frame() -> Error {
+    error("expect a struct or map for frame")
+}
+
+macro_rules! not_a_frame {
+    ($($method:ident($($t:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $t),*) -> Result<Frame, Error> {
+                Err(not_a_frame())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for FrameSerializer {
+    type Ok = Frame;
+    type Error = Error;
+    type SerializeSeq = Impossible<Frame, Error>;
+    type SerializeTuple = Impossible<Frame, Error>;
+    type SerializeTupleStruct = Impossible<Frame, Error>;
+    type SerializeTupleVariant = Impossible<Frame, Error>;
+    type SerializeMap = FrameBuilder;
+    type SerializeStruct = FrameBuilder;
+    type SerializeStructVariant = Impossible<Frame, Error>;
+
+    not_a_frame! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Frame, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<Frame, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<Frame, Error> {
+        Err(not_a_frame())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(not_a_frame())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(not_a_frame())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(not_a_frame())
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(not_a_frame())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(FrameBuilder::default())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Ok(FrameBuilder::default())
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Error> {
+        Err(not_a_frame())
+    }
+}
+// d25b8e70 ends here
+
+// [[file:../extxyz.note::4e1d7b0a][4e1d7b0a]]
+// A serializer which only tells if a value is a sequence (such as `Vec`),
+// which could be per-atom values, or a fixed size array or tuple, which
+// is a per-frame value. The items are not serialized.
+struct IsSeq;
+
+macro_rules! not_seq {
+    ($($method:ident($($t:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $t),*) -> Result<bool, Error> {
+                Ok(false)
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for IsSeq {
+    type Ok = bool;
+    type Error = Error;
+    type SerializeSeq = Skip;
+    type SerializeTuple = Skip;
+    type SerializeTupleStruct = Skip;
+    type SerializeTupleVariant = Skip;
+    type SerializeMap = Skip;
+    type SerializeStruct = Skip;
+    type SerializeStructVariant = Skip;
+
+    not_seq! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<bool, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<bool, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Skip, Error> {
+        Ok(Skip(true))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Skip, Error> {
+        Ok(Skip(false))
+    }
+}
+
+// Ignore all items of a compound value
+struct Skip(bool);
+
+macro_rules! impl_skip {
+    ($($trait:ident::$method:ident),* $(,)?) => {
+        $(
+            impl ser::$trait for Skip {
+                type Ok = bool;
+                type Error = Error;
+
+                fn $method<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<(), Error> {
+                    Ok(())
+                }
+
+                fn end(self) -> Result<bool, Error> {
+                    Ok(self.0)
+                }
+            }
+        )*
+    };
+}
+
+impl_skip! {
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field,
+}
+
+impl ser::SerializeMap for Skip {
+    type Ok = bool;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, _key: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, Error> {
+        Ok(self.0)
+    }
+}
+
+impl ser::SerializeStruct for Skip {
+    type Ok = bool;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, _value: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, Error> {
+        Ok(self.0)
+    }
+}
+
+impl ser::SerializeStructVariant for Skip {
+    type Ok = bool;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, _value: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, Error> {
+        Ok(self.0)
+    }
+}
+
+// Return true if `value` is serialized as a sequence
+fn is_seq<T: ?Sized + Serialize>(value: &T) -> bool {
+    value.serialize(IsSeq).unwrap_or(false)
+}
+// 4e1d7b0a ends here
+
+// [[file:../extxyz.note::f8c3a2d6][f8c3a2d6]]
+/// Collect entries of struct or map, and build `Frame` at the end.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct FrameBuilder {
+    // key, value, and if the value is a sequence
+    entries: Vec<(String, Value, bool)>,
+    key: Option<String>,
+}
+
+impl FrameBuilder {
+    fn push<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let seq = is_seq(value);
+        let value = serde_json::to_value(value).map_err(|e| error(format!("invalid value for {key:?}: {e}")))?;
+        self.entries.push((key, value, seq));
+        Ok(())
+    }
+
+    // remove the first non-null entry with any of `names`
+    fn take(&mut self, names: &[&str]) -> Option<(String, Value)> {
+        let i = self.entries.iter().position(|(k, v, _)| names.contains(&k.as_str()) && !v.is_null())?;
+        let (k, v, _) = self.entries.remove(i);
+        Some((k, v))
+    }
+
+    fn take_as<T: serde::de::DeserializeOwned>(&mut self, names: &[&str]) -> Result<Option<T>, Error> {
+        match self.take(names) {
+            Some((k, v)) => serde_json::from_value(v).map(Some).map_err(|e| error(format!("invalid value for {k:?}: {e}"))),
+            None => Ok(None),
+        }
+    }
+
+    fn build(mut self) -> Result<Frame, Error> {
+        let symbols: Vec<String> = self.take_as(&["symbols", "species"])?.ok_or_else(|| error("missing field `symbols`"))?;
+        let positions: Vec<[f64; 3]> = self.take_as(&["positions", "pos"])?.ok_or_else(|| error("missing field `positions`"))?;
+        let natoms = symbols.len();
+        if positions.len() != natoms {
+            return Err(error("inconsistent number of positions"));
+        }
+        self.take(&["natoms"]);
+
+        let lattice = match self.take(&["lattice", "Lattice"]) {
+            Some((_, v)) => {
+                let lattice = serde_json::from_value::<[[f64; 3]; 3]>(v.clone())
+                    .or_else(|_| serde_json::from_value::<[f64; 9]>(v).map(|x| [[x[0], x[1], x[2]], [x[3], x[4], x[5]], [x[6], x[7], x[8]]]))
+                    .map_err(|_| error("invalid lattice: expect 3x3 or 9 real values"))?;
+                Some(lattice)
+            }
+            None => None,
+        };
+        let pbc = self.take_as(&["pbc"])?;
+
+        let mut info = Info::default();
+        let mut arrays = crate::Columns::new();
+        if let Some((_, v)) = self.take(&["info"]) {
+            // `Info` itself, or a plain map
+            let map = match serde_json::from_value::<Info>(v.clone()) {
+                Ok(info) => info.raw_map().clone(),
+                Err(_) => serde_json::from_value(v).map_err(|_| error("invalid info: expect a map"))?,
+            };
+            info.raw_map_mut().extend(map);
+        }
+        if let Some((_, v)) = self.take(&["arrays"]) {
+            let Value::Object(map) = v else {
+                return Err(error("invalid arrays: expect a map"));
+            };
+            for (k, v) in map {
+                // `Column` itself, or per-atom values
+                let column = match &v {
+                    Value::Array(values) => Column::from_values(values),
+                    _ => serde_json::from_value(v).ok(),
+                };
+                let column = column.ok_or_else(|| error(format!("invalid per-atom values for {k:?}")))?;
+                if column.len() != natoms {
+                    return Err(error(format!("inconsistent number of atoms in property {k:?}")));
+                }
+                arrays.insert(k, column);
+            }
+        }
+
+        for (k, v, seq) in self.entries {
+            match v {
+                Value::Null => {}
+                Value::Array(values) if seq && values.len() == natoms && natoms > 0 => match Column::from_values(&values) {
+                    Some(column) => {
+                        arrays.insert(k, column);
+                    }
+                    None => {
+                        info.raw_map_mut().insert(k, Value::Array(values));
+                    }
+                },
+                v => {
+                    info.raw_map_mut().insert(k, v);
+                }
+            }
+        }
+
+        Ok(Frame {
+            natoms,
+            symbols,
+            positions,
+            lattice,
+            pbc,
+            info,
+            arrays,
+        })
+    }
+}
+
+impl SerializeStruct for FrameBuilder {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.push(key.into(), value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.build()
+    }
+}
+
+impl SerializeMap for FrameBuilder {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        match serde_json::to_value(key) {
+            Ok(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(error("expect string keys for frame")),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().ok_or_else(|| error("key is missing"))?;
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.build()
+    }
+}
+// f8c3a2d6 ends here
+
+// [[file:../extxyz.note::3b96e0f1][3b96e0f1]]
+/// Convert `value` into `Frame` using `FrameSerializer`.
+pub fn to_frame<T: ?Sized + Serialize>(value: &T) -> anyhow::Result<Frame> {
+    let frame = value.serialize(FrameSerializer)?;
+    Ok(frame)
+}
+
+/// Serialize `value` as a frame in extxyz format. See `FrameSerializer`
+/// for how the entries are mapped.
+///
+/// # Example
+///
+/// ```
+/// #[derive(serde::Serialize)]
+/// struct Structure {
+///     symbols: Vec<String>,
+///     positions: Vec<[f64; 3]>,
+///     energy: f64,
+///     forces: Vec<[f64; 3]>,
+/// }
+///
+/// let structure = Structure {
+///     symbols: vec!["Cu".into()],
+///     positions: vec![[0.0; 3]],
+///     energy: -1.5,
+///     forces: vec![[0.1, 0.2, 0.3]],
+/// };
+/// let s = extxyz::to_string(&structure).unwrap();
+/// assert!(s.contains("Properties=species:S:1:pos:R:3:forces:R:3"));
+/// assert!(s.contains("energy=-1.5"));
+/// ```
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> anyhow::Result<String> {
+    to_frame(value)?.to_extxyz()
+}
+// 3b96e0f1 ends here
+
+// [[file:../extxyz.note::c0e7d4a9][c0e7d4a9]]
+#[test]
+fn test_to_string() -> anyhow::Result<()> {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Structure {
+        symbols: Vec<String>,
+        positions: Vec<[f64; 3]>,
+        lattice: Option<[[f64; 3]; 3]>,
+        energy: f64,
+        stress: [f64; 6],
+        config_type: String,
+        comment: Option<String>,
+        forces: Vec<[f64; 3]>,
+        fixed: Vec<bool>,
+        tag: Vec<i64>,
+        label: Vec<String>,
+        m: Vec<[f64; 2]>,
+    }
+
+    let structure = Structure {
+        symbols: vec!["Si".into(), "Si".into()],
+        positions: vec![[0.0; 3], [1.36; 3]],
+        lattice: Some([[5.44, 0.0, 0.0], [0.0, 5.44, 0.0], [0.0, 0.0, 5.44]]),
+        energy: -1.5,
+        stress: [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        config_type: "bulk phase".into(),
+        comment: None,
+        forces: vec![[0.1, 0.2, 0.3], [-0.1, -0.2, -0.3]],
+        fixed: vec![true, false],
+        tag: vec![1, 2],
+        label: vec!["a".into(), "b".into()],
+        m: vec![[1.0, 2.0], [3.0, 4.0]],
+    };
+    let frame = to_frame(&structure)?;
+    assert_eq!(frame.natoms, 2);
+    assert_eq!(frame.arrays.len(), 5);
+    assert_eq!(frame.arrays["fixed"], Column::Logical(vec![true, false]));
+    assert_eq!(frame.arrays["tag"], Column::Integer(vec![1, 2]));
+    assert_eq!(frame.arrays["m"], Column::RealN(2, vec![1.0, 2.0, 3.0, 4.0]));
+    assert!(frame.info.get("comment").is_none());
+
+    let s = to_string(&structure)?;
+    assert!(s.contains("Properties=species:S:1:pos:R:3:forces:R:3:fixed:L:1:tag:I:1:label:S:1:m:R:2"), "{s}");
+    // round trip
+    let parsed: Structure = crate::from_str(&s)?;
+    assert_eq!(parsed, structure);
+
+    // `Frame` itself
+    let frame = Frame::parse(&s)?;
+    assert_eq!(to_frame(&frame)?, frame);
+
+    // invalid input
+    assert!(to_string(&1.0).is_err());
+    assert!(to_string(&serde_json::json!({"symbols": ["H"]})).is_err());
+    let e = to_string(&serde_json::json!({"symbols": ["H"], "positions": [[0.0, 0.0, 0.0]], "lattice": [1.0]})).unwrap_err();
+    assert!(e.to_string().contains("invalid lattice"), "{e}");
+
+    // fixed size array as per-frame value, with the same length as atoms
+    #[derive(Serialize)]
+    struct Water {
+        symbols: Vec<&'static str>,
+        positions: Vec<[f64; 3]>,
+        dipole: [f64; 3],
+        charges: Vec<f64>,
+    }
+    let water = Water {
+        symbols: vec!["O", "H", "H"],
+        positions: vec![[0.0; 3], [0.0, 0.8, 0.6], [0.0, -0.8, 0.6]],
+        dipole: [0.0, 0.0, 1.8],
+        charges: vec![-0.8, 0.4, 0.4],
+    };
+    let frame = to_frame(&water)?;
+    assert_eq!(frame.info.get("dipole"), Some(&serde_json::json!([0.0, 0.0, 1.8])));
+    assert!(!frame.arrays.contains_key("dipole"));
+    assert_eq!(frame.arrays["charges"], Column::Real(vec![-0.8, 0.4, 0.4]));
+
+    Ok(())
+}
+// c0e7d4a9 ends here
@@ -0,0 +1,635 @@
+// [[file:../extxyz.note::8f3d6a21][8f3d6a21]]
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ::arrow::array::{ArrayRef, BooleanArray, FixedSizeListArray, Float64Array, Int64Array, ListArray, StringArray};
+use ::arrow::buffer::{NullBuffer, OffsetBuffer};
+use ::arrow::datatypes::{DataType, Field, FieldRef, Schema, SchemaRef};
+use ::arrow::record_batch::RecordBatch;
+use anyhow::*;
+use serde_json::Value;
+
+use crate::{Column, Frame, PropertyValueType};
+// 8f3d6a21 ends here
+
+// [[file:../extxyz.note::2c9b4e07][2c9b4e07]]
+/// The layout of rows in Arrow record batch converted from frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableLayout {
+    /// One row per frame, with columns `natoms`, `symbols`, `positions`,
+    /// `lattice`, `pbc`, values in comment line, and per-atom properties
+    /// in list columns.
+    #[default]
+    Frames,
+    /// One row per atom, with columns `frame` (the frame index),
+    /// `symbol`, `position` and per-atom properties.
+    Atoms,
+}
+
+// The scalar value type in arrow array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    Int,
+    Float,
+    Str,
+}
+
+impl Kind {
+    fn from_property(t: PropertyValueType) -> Self {
+        match t {
+            PropertyValueType::Logical => Self::Bool,
+            PropertyValueType::Integer => Self::Int,
+            PropertyValueType::Real => Self::Float,
+            PropertyValueType::String => Self::Str,
+        }
+    }
+
+    fn from_value(v: &Value) -> Option<Self> {
+        match v {
+            Value::Bool(_) => Some(Self::Bool),
+            Value::Number(x) if x.is_i64() => Some(Self::Int),
+            Value::Number(_) => Some(Self::Float),
+            Value::String(_) => Some(Self::Str),
+            _ => None,
+        }
+    }
+
+    fn from_data_type(t: &DataType) -> Option<Self> {
+        match t {
+            DataType::Boolean => Some(Self::Bool),
+            DataType::Int64 => Some(Self::Int),
+            DataType::Float64 => Some(Self::Float),
+            DataType::Utf8 => Some(Self::Str),
+            _ => None,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Self::Bool => DataType::Boolean,
+            Self::Int => DataType::Int64,
+            Self::Float => DataType::Float64,
+            Self::Str => DataType::Utf8,
+        }
+    }
+
+    // The common kind of `self` and `other`, with integers promoted to
+    // floats
+    fn merge(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Self::Int, Self::Float) | (Self::Float, Self::Int) => Some(Self::Float),
+            _ => None,
+        }
+    }
+}
+
+// Flattened values for arrow array
+enum Leaf {
+    Bool(Vec<bool>),
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Str(Vec<String>),
+}
+
+impl Leaf {
+    fn new(kind: Kind) -> Self {
+        match kind {
+            Kind::Bool => Self::Bool(vec![]),
+            Kind::Int => Self::Int(vec![]),
+            Kind::Float => Self::Float(vec![]),
+            Kind::Str => Self::Str(vec![]),
+        }
+    }
+
+    // Push `n` placeholder values for null entries
+    fn push_default(&mut self, n: usize) {
+        match self {
+            Self::Bool(v) => v.resize(v.len() + n, false),
+            Self::Int(v) => v.resize(v.len() + n, 0),
+            Self::Float(v) => v.resize(v.len() + n, 0.0),
+            Self::Str(v) => v.resize(v.len() + n, String::new()),
+        }
+    }
+
+    // Push value in comment line. Non-string values are formatted as
+    // json for string array.
+    fn push_value(&mut self, value: &Value) -> Result<()> {
+        match self {
+            Self::Bool(v) => v.push(value.as_bool().with_context(|| format!("expect a logical value: {value}"))?),
+            Self::Int(v) => v.push(value.as_i64().with_context(|| format!("expect an integer value: {value}"))?),
+            Self::Float(v) => v.push(value.as_f64().with_context(|| format!("expect a real value: {value}"))?),
+            Self::Str(v) => v.push(value.as_str().map(String::from).unwrap_or_else(|| value.to_string())),
+        }
+        Ok(())
+    }
+
+    // Push all values in per-atom `column`
+    fn extend_column(&mut self, column: &Column) -> Result<()> {
+        match (self, column) {
+            (Self::Bool(v), Column::Logical(x) | Column::LogicalN(_, x)) => v.extend(x),
+            (Self::Int(v), Column::Integer(x) | Column::IntegerN(_, x)) => v.extend(x),
+            (Self::Float(v), Column::Real(x) | Column::RealN(_, x)) => v.extend(x),
+            (Self::Float(v), Column::Real3(x)) => v.extend(x.iter().flatten()),
+            (Self::Float(v), Column::Integer(x) | Column::IntegerN(_, x)) => v.extend(x.iter().map(|&x| x as f64)),
+            (Self::Str(v), Column::String(x) | Column::StringN(_, x)) => v.extend(x.iter().cloned()),
+            _ => bail!("inconsistent type of property column: {:?}", column.value_type()),
+        }
+        Ok(())
+    }
+
+    fn into_array(self) -> ArrayRef {
+        match self {
+            Self::Bool(v) => Arc::new(BooleanArray::from(v)),
+            Self::Int(v) => Arc::new(Int64Array::from(v)),
+            Self::Float(v) => Arc::new(Float64Array::from(v)),
+            Self::Str(v) => Arc::new(StringArray::from(v)),
+        }
+    }
+}
+
+fn item_field(t: DataType) -> FieldRef {
+    Arc::new(Field::new("item", t, true))
+}
+
+fn list_type(t: DataType) -> DataType {
+    DataType::List(item_field(t))
+}
+
+fn fixed_list_type(t: DataType, width: usize) -> DataType {
+    DataType::FixedSizeList(item_field(t), width as i32)
+}
+
+// The data type of per-atom property with `width` columns
+fn property_type(kind: Kind, width: usize) -> DataType {
+    match width {
+        1 => kind.data_type(),
+        w => fixed_list_type(kind.data_type(), w),
+    }
+}
+
+fn null_buffer(valid: Vec<bool>) -> Option<NullBuffer> {
+    (!valid.iter().all(|&x| x)).then(|| NullBuffer::from(valid))
+}
+
+fn fixed_list(values: ArrayRef, width: usize, nulls: Option<NullBuffer>) -> Result<ArrayRef> {
+    let item = item_field(values.data_type().clone());
+    Ok(Arc::new(FixedSizeListArray::try_new(item, width as i32, values, nulls)?))
+}
+
+fn list(values: ArrayRef, lengths: Vec<usize>, nulls: Option<NullBuffer>) -> Result<ArrayRef> {
+    let item = item_field(values.data_type().clone());
+    Ok(Arc::new(ListArray::try_new(item, OffsetBuffer::from_lengths(lengths), values, nulls)?))
+}
+
+// Wrap flat `leaf` values of per-atom property in `width` columns
+fn property_array(leaf: Leaf, width: usize, nulls: Option<NullBuffer>) -> Result<ArrayRef> {
+    match width {
+        1 => match nulls {
+            // rebuild with nulls for missing atoms
+            Some(nulls) => Ok(::arrow::array::make_array(leaf.into_array().to_data().into_builder().nulls(Some(nulls)).build()?)),
+            None => Ok(leaf.into_array()),
+        },
+        w => fixed_list(leaf.into_array(), w, nulls),
+    }
+}
+// 2c9b4e07 ends here
+
+// [[file:../extxyz.note::a7e05d3c][a7e05d3c]]
+// The keys in field metadata for the source of column
+const SOURCE: &str = "extxyz:source";
+const KEY: &str = "extxyz:key";
+
+fn field(name: &str, t: DataType, source: &str, key: &str) -> Field {
+    let metadata = HashMap::from([(SOURCE.to_string(), source.to_string()), (KEY.to_string(), key.to_string())]);
+    Field::new(name, t, true).with_metadata(metadata)
+}
+
+// Return the common kind of values in comment line, and true if values
+// are arrays. Values in mixed types will be stored as json strings.
+fn infer_value_kind<'a>(values: impl Iterator<Item = &'a Value>) -> (Kind, bool) {
+    let mut kind: Option<Kind> = None;
+    let mut is_list = None;
+    for v in values {
+        let (k, list) = match v {
+            Value::Array(items) => {
+                let k = items.iter().map(Kind::from_value).try_fold(None, |acc: Option<Kind>, k| match (acc, k?) {
+                    (None, k) => Some(Some(k)),
+                    (Some(a), b) => a.merge(b).map(Some),
+                });
+                match k {
+                    // empty array of unknown type
+                    Some(None) => continue,
+                    Some(Some(k)) => (Some(k), true),
+                    None => (None, true),
+                }
+            }
+            v => (Kind::from_value(v), false),
+        };
+        let merged = match (kind, k) {
+            (None, Some(k)) => Some(k),
+            (Some(a), Some(b)) => a.merge(b),
+            _ => None,
+        };
+        if merged.is_none() || *is_list.get_or_insert(list) != list {
+            return (Kind::Str, false);
+        }
+        kind = merged;
+    }
+    (kind.unwrap_or(Kind::Str), is_list.unwrap_or(false))
+}
+
+/// Infer Arrow schema for `frames` in `layout`. The column types are
+/// derived from `PropertyValueType` of per-atom properties and the
+/// types of values in comment line.
+fn infer_schema(frames: &[&Frame], layout: TableLayout) -> Result<Schema> {
+    let mut fields = match layout {
+        TableLayout::Frames => vec![
+            field("natoms", DataType::Int64, "natoms", "natoms"),
+            field("symbols", list_type(DataType::Utf8), "symbols", "symbols"),
+            field("positions", list_type(fixed_list_type(DataType::Float64, 3)), "positions", "positions"),
+            field("lattice", fixed_list_type(DataType::Float64, 9), "lattice", "Lattice"),
+            field("pbc", fixed_list_type(DataType::Boolean, 3), "pbc", "pbc"),
+        ],
+        TableLayout::Atoms => vec![
+            field("frame", DataType::Int64, "frame", "frame"),
+            field("symbol", DataType::Utf8, "symbols", "symbols"),
+            field("position", fixed_list_type(DataType::Float64, 3), "positions", "positions"),
+        ],
+    };
+
+    // values in comment line, in the order of first appearance
+    if layout == TableLayout::Frames {
+        let mut keys: Vec<&String> = vec![];
+        for frame in frames {
+            for k in frame.info.raw_map().keys() {
+                if !keys.contains(&k) {
+                    keys.push(k);
+                }
+            }
+        }
+        for key in keys {
+            if fields.iter().any(|f| f.name() == key) {
+                continue;
+            }
+            let (kind, is_list) = infer_value_kind(frames.iter().filter_map(|f| f.info.get(key)));
+            let t = if is_list { list_type(kind.data_type()) } else { kind.data_type() };
+            fields.push(field(key, t, "info", key));
+        }
+    }
+
+    // per-atom properties
+    let mut properties: Vec<(&String, Kind, usize)> = vec![];
+    for frame in frames {
+        for (name, column) in &frame.arrays {
+            let kind = Kind::from_property(column.value_type());
+            match properties.iter_mut().find(|(k, ..)| *k == name) {
+                Some((_, k, width)) => {
+                    ensure!(*width == column.width(), "inconsistent width of property {name:?}");
+                    *k = k.merge(kind).with_context(|| format!("inconsistent type of property {name:?}"))?;
+                }
+                None => properties.push((name, kind, column.width())),
+            }
+        }
+    }
+    for (name, kind, width) in properties {
+        let t = property_type(kind, width);
+        let t = if layout == TableLayout::Frames { list_type(t) } else { t };
+        let column_name = if fields.iter().any(|f| f.name() == name) { format!("{name}_atoms") } else { name.clone() };
+        fields.push(field(&column_name, t, "atoms", name));
+    }
+
+    Ok(Schema::new(fields))
+}
+
+// The kind and width of per-atom property from data type in schema
+fn property_kind(t: &DataType) -> Result<(Kind, usize)> {
+    let r = match t {
+        DataType::FixedSizeList(item, w) => Kind::from_data_type(item.data_type()).map(|k| (k, *w as usize)),
+        t => Kind::from_data_type(t).map(|k| (k, 1)),
+    };
+    r.with_context(|| format!("unsupported data type for property: {t}"))
+}
+
+// Build column for `field` in one row per frame layout
+fn frames_column(frames: &[&Frame], field: &Field) -> Result<ArrayRef> {
+    let natoms: Vec<usize> = frames.iter().map(|f| f.symbols.len()).collect();
+    let key = field.metadata().get(KEY).map(|s| s.as_str()).unwrap_or(field.name());
+    let source = field.metadata().get(SOURCE).map(|s| s.as_str()).unwrap_or("info");
+    let array = match source {
+        "natoms" => Arc::new(Int64Array::from_iter_values(natoms.iter().map(|&n| n as i64))),
+        "symbols" => {
+            let leaf = Leaf::Str(frames.iter().flat_map(|f| f.symbols.iter().cloned()).collect());
+            list(leaf.into_array(), natoms, None)?
+        }
+        "positions" => {
+            let leaf = Leaf::Float(frames.iter().flat_map(|f| f.positions.iter().flatten().copied()).collect());
+            list(fixed_list(leaf.into_array(), 3, None)?, natoms, None)?
+        }
+        "lattice" => {
+            let leaf = Leaf::Float(frames.iter().flat_map(|f| f.lattice.unwrap_or_default().into_iter().flatten()).collect());
+            fixed_list(leaf.into_array(), 9, null_buffer(frames.iter().map(|f| f.lattice.is_some()).collect()))?
+        }
+        "pbc" => {
+            let pbc: Vec<_> = frames.iter().map(|f| f.pbc.or(f.lattice.map(|_| [true; 3]))).collect();
+            let leaf = Leaf::Bool(pbc.iter().flat_map(|x| x.unwrap_or_default()).collect());
+            fixed_list(leaf.into_array(), 3, null_buffer(pbc.iter().map(|x| x.is_some()).collect()))?
+        }
+        "info" => {
+            let values: Vec<_> = frames.iter().map(|f| f.info.get(key)).collect();
+            let valid: Vec<_> = values.iter().map(|v| v.is_some()).collect();
+            match field.data_type() {
+                DataType::List(item) => {
+                    let kind = Kind::from_data_type(item.data_type()).with_context(|| format!("unsupported data type for {key:?}"))?;
+                    let mut leaf = Leaf::new(kind);
+                    let mut lengths = vec![];
+                    for v in &values {
+                        let items = match v {
+                            Some(Value::Array(items)) => &items[..],
+                            Some(v) => bail!("expect an array for {key:?}: {v}"),
+                            None => &[],
+                        };
+                        for x in items {
+                            leaf.push_value(x).with_context(|| format!("invalid value for {key:?}"))?;
+                        }
+                        lengths.push(items.len());
+                    }
+                    list(leaf.into_array(), lengths, null_buffer(valid))?
+                }
+                t => {
+                    let kind = Kind::from_data_type(t).with_context(|| format!("unsupported data type for {key:?}"))?;
+                    let mut leaf = Leaf::new(kind);
+                    for v in &values {
+                        match v {
+                            Some(v) => leaf.push_value(v).with_context(|| format!("invalid value for {key:?}"))?,
+                            None => leaf.push_default(1),
+                        }
+                    }
+                    property_array(leaf, 1, null_buffer(valid))?
+                }
+            }
+        }
+        "atoms" => {
+            let DataType::List(item) = field.data_type() else {
+                bail!("expect a list type for property {key:?}");
+            };
+            let (kind, width) = property_kind(item.data_type())?;
+            let mut leaf = Leaf::new(kind);
+            let mut lengths = vec![];
+            let mut valid = vec![];
+            for frame in frames {
+                let column = frame.arrays.get(key).filter(|c| c.width() == width);
+                if let Some(column) = column {
+                    leaf.extend_column(column).with_context(|| format!("invalid property {key:?}"))?;
+                }
+                lengths.push(column.map(|c| c.len()).unwrap_or(0));
+                valid.push(column.is_some());
+            }
+            list(property_array(leaf, width, None)?, lengths, null_buffer(valid))?
+        }
+        s => bail!("unknown source of column: {s:?}"),
+    };
+    Ok(array)
+}
+
+// Build column for `field` in one row per atom layout
+fn atoms_column(frames: &[&Frame], field: &Field, first_frame: usize) -> Result<ArrayRef> {
+    let key = field.metadata().get(KEY).map(|s| s.as_str()).unwrap_or(field.name());
+    let source = field.metadata().get(SOURCE).map(|s| s.as_str()).unwrap_or("atoms");
+    let array = match source {
+        "frame" => {
+            let ids = frames.iter().enumerate().flat_map(|(i, f)| std::iter::repeat_n((first_frame + i) as i64, f.symbols.len()));
+            Arc::new(Int64Array::from_iter_values(ids))
+        }
+        "symbols" => Leaf::Str(frames.iter().flat_map(|f| f.symbols.iter().cloned()).collect()).into_array(),
+        "positions" => {
+            let leaf = Leaf::Float(frames.iter().flat_map(|f| f.positions.iter().flatten().copied()).collect());
+            fixed_list(leaf.into_array(), 3, None)?
+        }
+        "atoms" => {
+            let (kind, width) = property_kind(field.data_type())?;
+            let mut leaf = Leaf::new(kind);
+            let mut valid = vec![];
+            for frame in frames {
+                let n = frame.symbols.len();
+                match frame.arrays.get(key).filter(|c| c.width() == width) {
+                    Some(column) => {
+                        ensure!(column.len() == n, "inconsistent number of atoms in property {key:?}");
+                        leaf.extend_column(column).with_context(|| format!("invalid property {key:?}"))?;
+                        valid.extend(std::iter::repeat_n(true, n));
+                    }
+                    None => {
+                        leaf.push_default(n * width);
+                        valid.extend(std::iter::repeat_n(false, n));
+                    }
+                }
+            }
+            property_array(leaf, width, null_buffer(valid))?
+        }
+        s => bail!("unknown source of column: {s:?}"),
+    };
+    Ok(array)
+}
+
+fn build_record_batch(frames: &[&Frame], layout: TableLayout, schema: SchemaRef, first_frame: usize) -> Result<RecordBatch> {
+    for (i, frame) in frames.iter().enumerate() {
+        ensure!(frame.positions.len() == frame.symbols.len(), "inconsistent number of positions in frame {}", first_frame + i);
+    }
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match layout {
+            TableLayout::Frames => frames_column(frames, field),
+            TableLayout::Atoms => atoms_column(frames, field, first_frame),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Convert `frames` into Arrow record batch in `layout`.
+///
+/// The column types are derived from `PropertyValueType` of per-atom
+/// properties, and from types of values in comment line, which are
+/// stored as json strings if in mixed types. Entries missing in some
+/// frames are null. A per-atom property having the same name as another
+/// column is named with suffix `_atoms`.
+pub fn to_record_batch<F: Borrow<Frame>>(frames: &[F], layout: TableLayout) -> Result<RecordBatch> {
+    let frames: Vec<&Frame> = frames.iter().map(|f| f.borrow()).collect();
+    let schema = Arc::new(infer_schema(&frames, layout)?);
+    build_record_batch(&frames, layout, schema, 0)
+}
+// a7e05d3c ends here
+
+// [[file:../extxyz.note::6b1e8d94][6b1e8d94]]
+// The number of frames in each record batch written into Parquet file
+const BATCH_SIZE: usize = 1000;
+
+// Promote integer values in comment line to reals, which are compatible
+// with values in later frames.
+fn promote_info_integers(schema: Schema) -> Schema {
+    let fields: Vec<_> = schema
+        .fields()
+        .iter()
+        .map(|f| {
+            if f.metadata().get(SOURCE).map(|s| s.as_str()) != Some("info") {
+                return f.clone();
+            }
+            let t = match f.data_type() {
+                DataType::Int64 => DataType::Float64,
+                DataType::List(item) if item.data_type() == &DataType::Int64 => list_type(DataType::Float64),
+                t => t.clone(),
+            };
+            Arc::new(f.as_ref().clone().with_data_type(t))
+        })
+        .collect();
+    Schema::new(fields)
+}
+
+// Check if all entries in `frames` are found in `schema`
+fn check_schema(frames: &[&Frame], schema: &Schema, layout: TableLayout, first_frame: usize) -> Result<()> {
+    let found = |source: &str, key: &str| {
+        schema
+            .fields()
+            .iter()
+            .any(|f| f.metadata().get(SOURCE).is_some_and(|s| s == source) && f.metadata().get(KEY).is_some_and(|k| k == key))
+    };
+    for (i, frame) in frames.iter().enumerate() {
+        let i = first_frame + i;
+        if layout == TableLayout::Frames {
+            for key in frame.info.raw_map().keys() {
+                ensure!(found("info", key) || schema.fields().iter().any(|f| f.name() == key), "key {key:?} in frame {i} is not found in the first {BATCH_SIZE} frames");
+            }
+        }
+        for name in frame.arrays.keys() {
+            ensure!(found("atoms", name), "property {name:?} in frame {i} is not found in the first {BATCH_SIZE} frames");
+        }
+    }
+    Ok(())
+}
+
+/// Write `frames` into Parquet file in `path` in `layout`, as record
+/// batches converted by `to_record_batch`. Frames are converted in
+/// batches, so large trajectories can be written as they are read.
+///
+/// # NOTE
+/// * The schema is inferred from the first batch of frames. Integer
+///   values in comment line are stored as Float64, since the same key
+///   could be real in later frames, such as `energy=0` and `energy=-1.5`.
+/// * It is an error if an entry in later frames is not found in the
+///   first batch of frames.
+pub fn write_parquet<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>, layout: TableLayout) -> Result<()> {
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).with_context(|| format!("Failed to create file {path:?}"))?;
+    write_parquet_to(f, frames, layout)
+}
+
+/// Write `frames` into `writer` in Parquet format. See `write_parquet`.
+pub fn write_parquet_to<F: Borrow<Frame>>(writer: impl std::io::Write + Send, frames: impl IntoIterator<Item = F>, layout: TableLayout) -> Result<()> {
+    let mut frames = frames.into_iter().peekable();
+    let mut writer_schema: Option<(parquet::arrow::ArrowWriter<_>, SchemaRef)> = None;
+    let mut writer = Some(writer);
+    let mut first_frame = 0;
+    loop {
+        let batch: Vec<F> = frames.by_ref().take(BATCH_SIZE).collect();
+        if batch.is_empty() && writer_schema.is_some() {
+            break;
+        }
+        let batch: Vec<&Frame> = batch.iter().map(|f| f.borrow()).collect();
+        let (w, schema) = match &mut writer_schema {
+            Some(x) => x,
+            None => {
+                let schema = Arc::new(promote_info_integers(infer_schema(&batch, layout)?));
+                let w = parquet::arrow::ArrowWriter::try_new(writer.take().expect("writer"), schema.clone(), None)?;
+                writer_schema.insert((w, schema))
+            }
+        };
+        check_schema(&batch, schema, layout, first_frame)?;
+        let record_batch = build_record_batch(&batch, layout, schema.clone(), first_frame)?;
+        w.write(&record_batch)?;
+        first_frame += batch.len();
+        if frames.peek().is_none() {
+            break;
+        }
+    }
+    if let Some((w, _)) = writer_schema {
+        w.close()?;
+    }
+    Ok(())
+}
+// 6b1e8d94 ends here
+
+// [[file:../extxyz.note::e95a0c3f][e95a0c3f]]
+#[test]
+fn test_record_batch() -> Result<()> {
+    use ::arrow::array::{Array, AsArray};
+    use ::arrow::datatypes::Float64Type;
+
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:energy:R:1:tag:I:1 energy=-1.5 config_type=bulk stress="1 2 3 4 5 6 7 8 9"
+Si 0.0 0.0 0.0 0.1 0.2 0.3 -0.7 1
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 -0.8 2
+"#;
+    let frame = Frame::parse(input)?;
+    let small = Frame::parse("1\nenergy=-1 config_type=3\nH 0.0 0.0 0.0")?;
+    let frames = [frame, small];
+
+    let batch = to_record_batch(&frames, TableLayout::Frames)?;
+    assert_eq!(batch.num_rows(), 2);
+    let schema = batch.schema();
+    assert_eq!(schema.field_with_name("energy")?.data_type(), &DataType::Float64);
+    // mixed types in json strings
+    assert_eq!(schema.field_with_name("config_type")?.data_type(), &DataType::Utf8);
+    assert_eq!(schema.field_with_name("stress")?.data_type(), &list_type(DataType::Int64));
+    assert_eq!(schema.field_with_name("forces")?.data_type(), &list_type(fixed_list_type(DataType::Float64, 3)));
+    assert_eq!(schema.field_with_name("energy_atoms")?.data_type(), &list_type(DataType::Float64));
+    assert_eq!(schema.field_with_name("tag")?.data_type(), &list_type(DataType::Int64));
+
+    let config_type = batch.column_by_name("config_type").unwrap().as_string::<i32>();
+    assert_eq!(config_type.value(0), "bulk");
+    assert_eq!(config_type.value(1), "3");
+    let lattice = batch.column_by_name("lattice").unwrap();
+    assert!(lattice.is_valid(0) && lattice.is_null(1));
+    let forces = batch.column_by_name("forces").unwrap().as_list::<i32>();
+    assert!(forces.is_null(1));
+    let row = forces.value(0);
+    let row = row.as_fixed_size_list();
+    assert_eq!(row.value(1).as_primitive::<Float64Type>().values(), &[-0.1, -0.2, -0.3]);
+
+    let batch = to_record_batch(&frames, TableLayout::Atoms)?;
+    assert_eq!(batch.num_rows(), 3);
+    assert_eq!(batch.num_columns(), 6);
+    let ids = batch.column_by_name("frame").unwrap().as_primitive::<::arrow::datatypes::Int64Type>();
+    assert_eq!(ids.values(), &[0, 0, 1]);
+    let energy = batch.column_by_name("energy").unwrap();
+    assert_eq!(energy.null_count(), 1);
+    assert_eq!(energy.as_primitive::<Float64Type>().value(1), -0.8);
+
+    // Parquet file
+    let path = std::env::temp_dir().join(format!("extxyz-test-{}.parquet", std::process::id()));
+    write_parquet(&path, &frames, TableLayout::Frames)?;
+    let f = std::fs::File::open(&path)?;
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReader::try_new(f, 1024)?;
+    let batches: Vec<_> = reader.collect::<std::result::Result<_, _>>()?;
+    assert_eq!(batches[0].num_rows(), 2);
+    assert_eq!(batches[0].column_by_name("forces").unwrap().as_list::<i32>().value(0).len(), 2);
+
+    // integers in comment line could be real in later batches
+    let mut frames: Vec<_> = (0..BATCH_SIZE + 1).map(|_| Frame::parse("1\nenergy=0\nH 0.0 0.0 0.0")).collect::<Result<_>>()?;
+    frames[BATCH_SIZE].info.raw_map_mut().insert("energy".into(), (-1.5).into());
+    write_parquet(&path, &frames, TableLayout::Frames)?;
+    let f = std::fs::File::open(&path)?;
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReader::try_new(f, 2 * BATCH_SIZE)?;
+    let batches: Vec<_> = reader.collect::<std::result::Result<_, _>>()?;
+    let energy = batches[0].column_by_name("energy").unwrap().as_primitive::<Float64Type>();
+    assert_eq!((energy.value(0), energy.value(BATCH_SIZE)), (0.0, -1.5));
+    // new keys in later batches are errors
+    frames[BATCH_SIZE].info.raw_map_mut().insert("new".into(), 1.into());
+    assert!(write_parquet(&path, &frames, TableLayout::Frames).is_err());
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+// e95a0c3f ends here
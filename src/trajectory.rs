@@ -7,44 +7,116 @@ use std::path::Path;
 
 use anyhow::*;
 use grep_reader::GrepReader;
+
+use crate::Compression;
 // 7d01bbbd ends here
 
+// [[file:../extxyz.note::62f4caaf][62f4caaf]]
+/// Represents an error in reading frames from trajectory.
+#[derive(Debug)]
+pub struct ReadError {
+    /// The index of the frame in which the error occurs
+    pub frame: usize,
+    /// The byte offset of the line where the error occurs. For
+    /// compressed trajectory, it is the offset in decompressed data.
+    pub offset: u64,
+    /// The line number (starting from 1) where the error occurs, or 0
+    /// if it is not known, such as for frames located by byte offsets.
+    pub line: usize,
+    /// The cause of the error
+    pub kind: ReadErrorKind,
+}
+
+/// Represents the cause of `ReadError`.
+#[derive(Debug)]
+pub enum ReadErrorKind {
+    /// I/O error, or invalid UTF-8 data
+    Io(std::io::Error),
+    /// The first line of frame is not the number of atoms
+    InvalidNatoms(String),
+    /// The stream ends before all atom lines of the frame are read
+    Truncated {
+        /// The number of atoms specified in the first line
+        natoms: usize,
+        /// The number of atom lines found
+        found: usize,
+    },
+    /// The index of selected frame is not in ascending order, which is
+    /// required for reading compressed trajectory
+    NotAscending,
+    /// The index of selected frame is out of range of frame index
+    OutOfRange {
+        /// The number of frames in trajectory
+        nframes: usize,
+    },
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let frame = self.frame;
+        match &self.kind {
+            ReadErrorKind::Io(e) => write!(f, "failed to read frame {frame}: {e}")?,
+            ReadErrorKind::InvalidNatoms(s) => write!(f, "invalid number of atoms in frame {frame}: {s:?}")?,
+            ReadErrorKind::Truncated { natoms, found } => write!(f, "truncated frame {frame}: expect {natoms} atoms, but found {found}")?,
+            ReadErrorKind::NotAscending => write!(f, "frame {frame} is selected out of ascending order")?,
+            ReadErrorKind::OutOfRange { nframes } => write!(f, "frame {frame} out of range: only {nframes} frames")?,
+        }
+        match self.line {
+            0 => write!(f, " (byte offset {})", self.offset),
+            line => write!(f, " (line {line}, byte offset {})", self.offset),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ReadErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+// 62f4caaf ends here
+
 // [[file:../extxyz.note::55fa400b][55fa400b]]
-mod reader {
+pub(crate) mod reader {
     // #![deny(warnings)]
     // #![deny(clippy::all)]
     // #![deny(missing_docs)]
     
     use anyhow::*;
 
-    use std::fs::File;
     use std::io::BufRead;
     use std::io::BufReader;
     use std::io::Read;
     use std::path::Path;
     use std::result::Result::Ok;
 
-    type FileReader = BufReader<File>;
-
-    fn text_file_reader<P: AsRef<Path>>(p: P) -> Result<FileReader> {
-        let p = p.as_ref();
-        let f = File::open(p).with_context(|| format!("Failed to open file {:?}", p))?;
-
-        let reader = BufReader::new(f);
-        Ok(reader)
-    }
+    use super::{ReadError, ReadErrorKind};
+    use crate::compression::{open_text_file, DynReader};
 
-    #[derive(Debug)]
     /// A stream reader for large text file
     pub struct TextReader<R> {
         inner: R,
+        // the number of bytes read
+        offset: u64,
+        // the number of lines read
+        line: usize,
+        // the line read ahead, which is the first line of next frame
+        next_line: String,
     }
 
-    impl TextReader<FileReader> {
-        /// Build a text reader for file from path `p`.
+    impl TextReader<DynReader> {
+        /// Build a text reader for file from path `p`. Compressed file
+        /// will be decompressed transparently.
         pub fn try_from_path(p: &Path) -> Result<Self> {
-            let reader = text_file_reader(p)?;
-            let parser = Self { inner: reader };
+            let reader = open_text_file(p)?;
+            let parser = Self {
+                inner: reader,
+                offset: 0,
+                line: 0,
+                next_line: String::new(),
+            };
             Ok(parser)
         }
     }
@@ -52,39 +124,124 @@ mod reader {
     impl<R: Read> TextReader<BufReader<R>> {
         /// Build a text reader from a struct implementing Read trait.
         pub fn new(r: R) -> Self {
-            Self { inner: BufReader::new(r) }
+            Self {
+                inner: BufReader::new(r),
+                offset: 0,
+                line: 0,
+                next_line: String::new(),
+            }
         }
     }
 
     impl<R: BufRead> TextReader<R> {
-        /// Read a new line into buf.
-        ///
-        /// # NOTE
-        /// - This function will return the total number of bytes read.
-        /// - If this function returns None, the stream has reached EOF or encountered any error.
-        pub fn read_line(&mut self, buf: &mut String) -> Option<usize> {
-            match self.inner.read_line(buf) {
-                Ok(0) => None,
-                Ok(n) => Some(n),
-                Err(_) => {
-                    // discard any read in buf
-                    return None;
-                }
+        /// Read a new line into buf, and return the total number of bytes
+        /// read. I/O error or invalid UTF-8 data will be reported.
+        pub fn try_read_line(&mut self, buf: &mut String) -> std::io::Result<usize> {
+            let n = self.inner.read_line(buf)?;
+            if n > 0 {
+                self.offset += n as u64;
+                self.line += 1;
             }
+            Ok(n)
         }
 
-        /// Returns an iterator over the lines of this reader. Each string returned
-        /// will not have a line ending.
-        pub fn lines(self) -> impl Iterator<Item = String> {
-            // silently ignore UTF-8 error
-            self.inner.lines().filter_map(|s| if let Ok(line) = s { Some(line) } else { None })
+        /// Return the byte offset and the line number of next line.
+        pub fn position(&self) -> (u64, usize) {
+            (self.offset, self.line + 1)
         }
 
-        /// Read all text into string `buf` (Note: out of memory issue for large
-        /// file)
-        pub fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
-            let n = self.inner.read_to_string(buf)?;
-            Ok(n)
+        /// Read the `i`-th frame in xyz format into `buf`, using the
+        /// number of atoms in the first line. Blank lines before the
+        /// frame are ignored. Return the number of atoms, or None if the
+        /// stream has reached EOF.
+        pub fn try_read_frame(&mut self, buf: &mut String, i: usize) -> Result<Option<usize>, ReadError> {
+            let m = buf.len();
+            let natoms = loop {
+                let (offset, line) = (self.offset, self.line + 1);
+                let error = |kind| ReadError { frame: i, offset, line, kind };
+                match self.try_read_line(buf) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => {}
+                    Err(e) => return Err(error(ReadErrorKind::Io(e))),
+                }
+                let s = buf[m..].trim();
+                if s.is_empty() {
+                    buf.truncate(m);
+                    continue;
+                }
+                match s.parse::<usize>() {
+                    Ok(n) => break n,
+                    Err(_) => return Err(error(ReadErrorKind::InvalidNatoms(s.to_string()))),
+                }
+            };
+
+            // the comment line and atom lines
+            for k in 0..natoms + 1 {
+                let (offset, line) = (self.offset, self.line + 1);
+                let error = |kind| ReadError { frame: i, offset, line, kind };
+                match self.try_read_line(buf) {
+                    Ok(0) => {
+                        let kind = ReadErrorKind::Truncated {
+                            natoms,
+                            found: k.saturating_sub(1),
+                        };
+                        return Err(error(kind));
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Err(error(ReadErrorKind::Io(e))),
+                }
+            }
+            Ok(Some(natoms))
+        }
+
+        /// Read the `i`-th frame in xyz format into `buf`, from a line
+        /// with only an integer to the line before the next one, in the
+        /// same way as `read_xyz_frames_two_pass`. The number of atom
+        /// lines could be different from the integer, as in `VEC` lines.
+        /// Lines before the first frame are ignored. Return the integer,
+        /// or None if the stream has reached EOF.
+        pub fn try_read_marked_frame(&mut self, buf: &mut String, i: usize) -> Result<Option<usize>, ReadError> {
+            let is_marker = |s: &str| {
+                let s = s.trim();
+                !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+            };
+
+            let mut s = std::mem::take(&mut self.next_line);
+            loop {
+                if s.is_empty() {
+                    let (offset, line) = self.position();
+                    match self.try_read_line(&mut s) {
+                        Ok(0) => return Ok(None),
+                        Ok(_) => {}
+                        Err(e) => return Err(ReadError { frame: i, offset, line, kind: ReadErrorKind::Io(e) }),
+                    }
+                }
+                if is_marker(&s) {
+                    break;
+                }
+                s.clear();
+            }
+            let Ok(natoms) = s.trim().parse() else {
+                // the marker line is the last line read
+                let (offset, line) = (self.offset - s.len() as u64, self.line);
+                return Err(ReadError { frame: i, offset, line, kind: ReadErrorKind::InvalidNatoms(s.trim().to_string()) });
+            };
+            buf.push_str(&s);
+
+            // the following lines until the next marker line
+            loop {
+                s.clear();
+                let (offset, line) = self.position();
+                match self.try_read_line(&mut s) {
+                    Ok(0) => return Ok(Some(natoms)),
+                    Ok(_) if is_marker(&s) => {
+                        self.next_line = s;
+                        return Ok(Some(natoms));
+                    }
+                    Ok(_) => buf.push_str(&s),
+                    Err(e) => return Err(ReadError { frame: i, offset, line, kind: ReadErrorKind::Io(e) }),
+                }
+            }
         }
     }
 }
@@ -99,38 +256,286 @@ mod reader {
 /// * The first line (an integer) in `xyz` frame is used as a frame
 ///   separator. The following atom lines can be more or fewer than
 ///   specified. That is, `VEC` atom line specification is well supported.
+/// * Compressed trajectory (see `Compression`) is read sequentially in
+///   one pass, so the indices in `selection` should be in strictly
+///   ascending order. Otherwise it is an error of `NotAscending` kind.
+/// * Reading stops at the first error. Use `try_read_xyz_frames_two_pass`
+///   to have errors reported.
 ///
 /// # Parameters
 /// * `path`: path to the trajectory file
 /// * `selection`: an iterator over indices of selected frames
-pub fn read_xyz_frames_two_pass(path: impl AsRef<Path>, mut selection: impl Iterator<Item = usize>) -> Result<impl Iterator<Item = String>> {
-    let mut reader = GrepReader::try_from_path(path.as_ref())?;
+pub fn read_xyz_frames_two_pass(path: impl AsRef<Path>, selection: impl Iterator<Item = usize>) -> Result<impl Iterator<Item = String>> {
+    let frames = try_read_xyz_frames_two_pass(path, selection)?;
+    Ok(frames.map_while(|x| x.ok()))
+}
+
+/// Return an iterator over the selected frames in trajectory in `path`,
+/// like `read_xyz_frames_two_pass`, but reporting I/O errors as
+/// `ReadError` instead of silently stopping. The iterator stops after
+/// the first error.
+pub fn try_read_xyz_frames_two_pass(
+    path: impl AsRef<Path>,
+    mut selection: impl Iterator<Item = usize>,
+) -> Result<impl Iterator<Item = std::result::Result<String, ReadError>>> {
+    use std::result::Result::Ok;
+
+    let path = path.as_ref();
+    // compressed file can only be read sequentially
+    if Compression::detect(path)?.is_compressed() {
+        let reader = self::reader::TextReader::try_from_path(path)?;
+        let frames = try_read_frames_sequential_with(reader, selection, true, self::reader::TextReader::try_read_marked_frame);
+        return Ok(Frames::Sequential(frames));
+    }
+
+    let mut reader = GrepReader::try_from_path(path)?;
 
     // pass1: mark natoms lines using grep
     // allow whitespace before or after number
     let n = reader.mark(r"^\s*\d+\s*$", None)?;
 
     // pass2: read frames selectively
+    let mut failed = false;
     let frames = std::iter::from_fn(move || {
-        if reader.current_marker() <= n {
+        if failed || reader.current_marker() > n {
+            return None;
+        }
+        let j = selection.next()?;
+        if j >= n {
+            return None;
+        }
+        let mut offset = 0;
+        let mut buf = String::new();
+        let r = reader.goto_marker(j).and_then(|pos| {
+            offset = pos;
+            reader.read_until_next_marker(&mut buf)
+        });
+        match r {
+            Ok(_) => Some(Ok(buf)),
+            Err(e) => {
+                failed = true;
+                let kind = ReadErrorKind::Io(std::io::Error::other(e));
+                Some(Err(ReadError { frame: j, offset, line: 0, kind }))
+            }
+        }
+    });
+
+    Ok(Frames::Grep(frames))
+}
+
+// Frames read in different ways
+enum Frames<A, B> {
+    Grep(A),
+    Sequential(B),
+}
+
+impl<T, A, B> Iterator for Frames<A, B>
+where
+    A: Iterator<Item = T>,
+    B: Iterator<Item = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            Self::Grep(x) => x.next(),
+            Self::Sequential(x) => x.next(),
+        }
+    }
+}
+
+// Read selected frames one by one from `reader`. Frames not selected
+// are skipped. The indices in `selection` are expected in ascending
+// order, and indices of frames already passed are ignored. The iterator
+// stops after the first error.
+fn try_read_frames_sequential<R: std::io::BufRead>(
+    reader: self::reader::TextReader<R>,
+    selection: impl Iterator<Item = usize>,
+) -> impl Iterator<Item = std::result::Result<String, ReadError>> {
+    try_read_frames_sequential_with(reader, selection, false, self::reader::TextReader::try_read_frame)
+}
+
+// Read selected frames as `try_read_frames_sequential`, using
+// `read_frame` for frames in other formats, such as LAMMPS dump. If
+// `strict`, indices not in ascending order are errors instead of being
+// ignored.
+pub(crate) fn try_read_frames_sequential_with<R: std::io::BufRead>(
+    mut reader: self::reader::TextReader<R>,
+    mut selection: impl Iterator<Item = usize>,
+    strict: bool,
+    mut read_frame: impl FnMut(&mut self::reader::TextReader<R>, &mut String, usize) -> std::result::Result<Option<usize>, ReadError>,
+) -> impl Iterator<Item = std::result::Result<String, ReadError>> {
+    use std::result::Result::Ok;
+
+    // the index of next frame in stream
+    let mut i = 0;
+    let mut failed = false;
+    let mut skipped = String::new();
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let j = if strict {
             let j = selection.next()?;
-            if j < n {
-                reader.goto_marker(j).ok()?;
-                let mut buf = String::new();
-                reader.read_until_next_marker(&mut buf).ok()?;
-                Some(buf)
-            } else {
-                None
+            if j < i {
+                failed = true;
+                let (offset, line) = reader.position();
+                return Some(Err(ReadError { frame: j, offset, line, kind: ReadErrorKind::NotAscending }));
             }
+            j
         } else {
-            None
+            selection.find(|&j| j >= i)?
+        };
+        loop {
+            let mut buf = String::new();
+            let selected = i == j;
+            let r = if selected {
+                read_frame(&mut reader, &mut buf, i)
+            } else {
+                skipped.clear();
+                read_frame(&mut reader, &mut skipped, i)
+            };
+            match r {
+                Ok(Some(_)) => {
+                    i += 1;
+                    if selected {
+                        return Some(Ok(buf));
+                    }
+                }
+                Ok(None) => return None,
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(e));
+                }
+            }
         }
-    });
+    })
+}
 
-    Ok(frames)
+fn read_frames_sequential<R: std::io::BufRead>(
+    reader: self::reader::TextReader<R>,
+    selection: impl Iterator<Item = usize>,
+) -> impl Iterator<Item = String> {
+    try_read_frames_sequential(reader, selection).map_while(|x| x.ok())
 }
 // 48f5accb ends here
 
+// [[file:../extxyz.note::6e299b13][6e299b13]]
+/// Return an iterator that yields strings of the selected frames in the
+/// `xyz/extxyz` format from any `reader`, such as stdin, in-memory
+/// buffer or archive entry. Frames are read one by one, and frames not
+/// selected are skipped.
+///
+/// # NOTE
+/// * The first line in `xyz` frame should be the real number of atom
+///   lines. That is, `VEC` atom line specifications are not supported.
+/// * The indices in `selection` should be in ascending order. Indices of
+///   frames already passed are ignored.
+/// * Reading stops at the first error. Use `try_read_xyz_frames_from` to
+///   have errors reported.
+///
+/// # Example
+/// ```rust,no_run
+/// // read every 10th frame from stdin
+/// let frames = extxyz::read_xyz_frames_from(std::io::stdin(), (0..).step_by(10));
+/// for frame in frames {
+///     let atoms = extxyz::RawAtoms::parse_from(&frame).unwrap();
+/// }
+/// ```
+pub fn read_xyz_frames_from<R: std::io::Read>(reader: R, selection: impl Iterator<Item = usize>) -> impl Iterator<Item = String> {
+    let reader = self::reader::TextReader::new(reader);
+    read_frames_sequential(reader, selection)
+}
+
+#[test]
+fn test_read_xyz_frames_from() -> Result<()> {
+    let s = std::fs::read_to_string("tests/files/water.xyz")?;
+    let expected: Vec<_> = read_xyz_frames_direct("tests/files/water.xyz")?.collect();
+
+    let frames: Vec<_> = read_xyz_frames_from(s.as_bytes(), 0..).collect();
+    assert_eq!(frames, expected);
+    let frames: Vec<_> = read_xyz_frames_from(std::io::Cursor::new(&s), [1, 1, 0, 2].into_iter()).collect();
+    assert_eq!(frames, [expected[1].clone(), expected[2].clone()]);
+
+    Ok(())
+}
+// 6e299b13 ends here
+
+// [[file:../extxyz.note::950c59de][950c59de]]
+/// Return an iterator over the selected frames in trajectory in `path`,
+/// like `read_xyz_frames_direct`, but reporting I/O and format errors
+/// instead of silently stopping. A truncated frame, an invalid line for
+/// the number of atoms or invalid UTF-8 data will be reported as
+/// `ReadError` with the frame index, line number and byte offset. The
+/// iterator stops after the first error.
+///
+/// # NOTE
+/// * The first line in `xyz` frame should be the real number of atom
+///   lines. That is, `VEC` atom line specifications are not supported.
+/// * The indices in `selection` should be in ascending order. Indices of
+///   frames already passed are ignored.
+pub fn try_read_xyz_frames(
+    path: impl AsRef<Path>,
+    selection: impl Iterator<Item = usize>,
+) -> Result<impl Iterator<Item = std::result::Result<String, ReadError>>> {
+    let reader = self::reader::TextReader::try_from_path(path.as_ref())?;
+    Ok(try_read_frames_sequential(reader, selection))
+}
+
+/// Return an iterator over the selected frames from any `reader`, like
+/// `read_xyz_frames_from`, but reporting errors as `try_read_xyz_frames`.
+pub fn try_read_xyz_frames_from<R: std::io::Read>(
+    reader: R,
+    selection: impl Iterator<Item = usize>,
+) -> impl Iterator<Item = std::result::Result<String, ReadError>> {
+    let reader = self::reader::TextReader::new(reader);
+    try_read_frames_sequential(reader, selection)
+}
+
+#[test]
+fn test_try_read_xyz_frames() -> Result<()> {
+    let frames: Vec<_> = try_read_xyz_frames("tests/files/water.xyz", 0..)?.collect::<std::result::Result<_, _>>()?;
+    assert_eq!(frames.len(), 3);
+    let selected: Vec<_> = try_read_xyz_frames_two_pass("tests/files/water.xyz", [2, 0].into_iter())?.collect::<std::result::Result<_, _>>()?;
+    assert_eq!(selected, [frames[2].clone(), frames[0].clone()]);
+
+    // truncated frame
+    let s = "2\ncomment\nH 0 0 0\nH 0 0 1\n3\ncomment\nH 0 0 0\n";
+    let frames: Vec<_> = try_read_xyz_frames_from(s.as_bytes(), 0..).collect();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].is_ok());
+    let e = frames[1].as_ref().unwrap_err();
+    assert_eq!((e.frame, e.line, e.offset), (1, 8, 44));
+    assert!(matches!(e.kind, ReadErrorKind::Truncated { natoms: 3, found: 1 }));
+    // the error of skipped frames are also reported
+    let frames: Vec<_> = try_read_xyz_frames_from(s.as_bytes(), [2].into_iter()).collect();
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].is_err());
+
+    // invalid natoms line
+    let s = "1\ncomment\nH 0 0 0\nH 0 0 1\n";
+    let frames: Vec<_> = try_read_xyz_frames_from(s.as_bytes(), 0..).collect();
+    let e = frames[1].as_ref().unwrap_err();
+    assert_eq!((e.frame, e.line), (1, 4));
+    assert!(matches!(e.kind, ReadErrorKind::InvalidNatoms(_)));
+
+    // invalid UTF-8 data
+    let s = b"1\ncomment\nH 0 0 0\xff\n";
+    let frames: Vec<_> = try_read_xyz_frames_from(&s[..], 0..).collect();
+    let e = frames[0].as_ref().unwrap_err();
+    assert_eq!(e.line, 3);
+    assert!(matches!(e.kind, ReadErrorKind::Io(_)));
+
+    // blank lines at the end are fine
+    let s = "1\ncomment\nH 0 0 0\n\n\n";
+    let frames: Vec<_> = try_read_xyz_frames_from(s.as_bytes(), 0..).collect();
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].is_ok());
+
+    Ok(())
+}
+// 950c59de ends here
+
 // [[file:../extxyz.note::bc363bfe][bc363bfe]]
 pub use read_xyz_frames_two_pass as read_xyz_frames;
 // bc363bfe ends here
@@ -143,19 +548,13 @@ pub use read_xyz_frames_two_pass as read_xyz_frames;
 /// # NOTE
 /// * The first line in `xyz` frame should be the real number of atom
 ///   lines. That is, `VEC` atom line specifications are not supported.
+/// * Compressed trajectory (see `Compression`) is decompressed
+///   transparently.
+/// * Reading stops at the first error. Use `try_read_xyz_frames` to
+///   have errors reported.
 pub fn read_xyz_frames_direct(path: impl AsRef<Path>) -> Result<impl Iterator<Item = String>> {
-    let mut reader = self::reader::TextReader::try_from_path(path.as_ref())?;
-    let frames = std::iter::from_fn(move || {
-        let mut buf = String::new();
-        let _ = reader.read_line(&mut buf)?;
-        let n: usize = buf.trim().parse().ok()?;
-        let _ = reader.read_line(&mut buf)?;
-        for _ in 0..n {
-            reader.read_line(&mut buf)?;
-        }
-        Some(buf)
-    });
-
+    let reader = self::reader::TextReader::try_from_path(path.as_ref())?;
+    let frames = read_frames_sequential(reader, 0..);
     Ok(frames)
 }
 // d3eeabd9 ends here
@@ -0,0 +1,528 @@
+// [[file:../extxyz.note::5d2a9c71][5d2a9c71]]
+use std::borrow::Borrow;
+use std::path::Path;
+use std::result::Result::Ok;
+
+use anyhow::*;
+
+use crate::element::{atomic_number, element_symbol};
+use crate::{Column, Frame, Info, Lattice};
+// 5d2a9c71 ends here
+
+// [[file:../extxyz.note::c3e8b416][c3e8b416]]
+// The header of POSCAR, which is repeated in XDATCAR for variable cell
+struct Header {
+    comment: String,
+    lattice: [[f64; 3]; 3],
+    // scaling factors for Cartesian coordinates
+    scale: [f64; 3],
+    species: Vec<String>,
+    counts: Vec<usize>,
+}
+
+impl Header {
+    fn natoms(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    fn symbols(&self) -> Vec<String> {
+        let groups = self.species.iter().zip(&self.counts);
+        groups.flat_map(|(s, &n)| std::iter::repeat_n(s.clone(), n)).collect()
+    }
+}
+
+fn next_line<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<&'a str> {
+    lines.next().context("unexpected end of input")
+}
+
+fn parse_floats(line: &str, n: usize) -> Result<Vec<f64>> {
+    let values: Vec<f64> = line
+        .split_whitespace()
+        .take(n)
+        .map(|s| s.parse().with_context(|| format!("invalid number {s:?} in line {line:?}")))
+        .collect::<Result<_>>()?;
+    ensure!(values.len() == n, "expect {n} numbers in line {line:?}");
+    Ok(values)
+}
+
+fn parse_vector(line: &str) -> Result<[f64; 3]> {
+    let v = parse_floats(line, 3)?;
+    Ok([v[0], v[1], v[2]])
+}
+
+// The species name in POTCAR could have suffix, such as `Si_pv` or
+// `Si/c7d4a`
+fn species_name(s: &str) -> &str {
+    s.split(['_', '/']).next().unwrap_or(s)
+}
+
+fn parse_header<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<Header> {
+    let comment = next_line(lines)?.trim().to_string();
+    let line = next_line(lines)?;
+    let scale: Vec<f64> = line.split_whitespace().map(|s| s.parse()).collect::<std::result::Result<_, _>>().ok().unwrap_or_default();
+    ensure!(scale.len() == 1 || scale.len() == 3, "invalid scaling factor: {line:?}");
+    let mut lattice = [parse_vector(next_line(lines)?)?, parse_vector(next_line(lines)?)?, parse_vector(next_line(lines)?)?];
+    let scale = match scale[..] {
+        // negative value for the cell volume
+        [s] if s < 0.0 => [(-s / Lattice::new(lattice).volume()).cbrt(); 3],
+        [s] => [s; 3],
+        _ => [scale[0], scale[1], scale[2]],
+    };
+    for v in lattice.iter_mut() {
+        for k in 0..3 {
+            v[k] *= scale[k];
+        }
+    }
+
+    let line = next_line(lines)?;
+    let (species, line) = if line.split_whitespace().all(|s| s.parse::<usize>().is_ok()) {
+        // VASP 4 format without species line, which are taken from the
+        // leading words in comment line as in ASE
+        let n = line.split_whitespace().count();
+        let species: Vec<String> = comment.split_whitespace().take(n).map(String::from).collect();
+        ensure!(
+            species.len() == n && species.iter().all(|s| atomic_number(s).is_some()),
+            "missing species names in POSCAR"
+        );
+        (species, line)
+    } else {
+        let species = line.split_whitespace().map(|s| species_name(s).to_string()).collect();
+        (species, next_line(lines)?)
+    };
+    let counts: Vec<usize> = line
+        .split_whitespace()
+        .map(|s| s.parse().with_context(|| format!("invalid number of atoms: {line:?}")))
+        .collect::<Result<_>>()?;
+    ensure!(counts.len() == species.len(), "inconsistent numbers of species and counts: {line:?}");
+
+    Ok(Header {
+        comment,
+        lattice,
+        scale,
+        species,
+        counts,
+    })
+}
+
+// Cartesian mode starts with `C` or `K`, and other modes are for direct
+// (fractional) coordinates
+fn is_cartesian(line: &str) -> bool {
+    line.trim_start().starts_with(['C', 'c', 'K', 'k'])
+}
+
+// A line with only coordinates mode, such as `Cartesian` or `Direct`
+fn is_mode_line(line: &str) -> bool {
+    let s = line.trim();
+    s.split_whitespace().count() == 1 && s.starts_with(['C', 'c', 'K', 'k', 'D', 'd'])
+}
+
+fn parse_flag(s: &str) -> Result<bool> {
+    match s.trim_start_matches('.').chars().next() {
+        Some('T' | 't') => Ok(true),
+        Some('F' | 'f') => Ok(false),
+        _ => bail!("invalid selective dynamics flag: {s:?}"),
+    }
+}
+
+// Read `natoms` lines of coordinates in direct or cartesian `mode`, and
+// selective dynamics flags if `selective`
+fn parse_coordinates<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    header: &Header,
+    cartesian: bool,
+    selective: bool,
+) -> Result<(Vec<[f64; 3]>, Vec<bool>)> {
+    let lattice = Lattice::new(header.lattice);
+    let mut positions = vec![];
+    let mut flags = vec![];
+    for _ in 0..header.natoms() {
+        let line = next_line(lines)?;
+        let v = parse_vector(line)?;
+        let position = if cartesian { [0, 1, 2].map(|k| v[k] * header.scale[k]) } else { lattice.to_cart(v) };
+        positions.push(position);
+        if selective {
+            let items: Vec<_> = line.split_whitespace().skip(3).take(3).collect();
+            ensure!(items.len() == 3, "missing selective dynamics flags in line {line:?}");
+            for s in items {
+                flags.push(parse_flag(s)?);
+            }
+        }
+    }
+    Ok((positions, flags))
+}
+
+// Key-value pairs from comment line, which could be in extxyz format.
+// The leading species names (as in VASP 4 format) are skipped, instead
+// of being taken as logical flags.
+pub(crate) fn comment_info(comment: &str) -> Info {
+    let mut rest = comment.trim_start();
+    while let Some((word, tail)) = rest.split_once(char::is_whitespace) {
+        let tail = tail.trim_start();
+        if word.contains('=') || tail.starts_with('=') || atomic_number(word).is_none() {
+            break;
+        }
+        rest = tail;
+    }
+    let info = rest.contains('=').then(|| rest.parse::<Info>().ok()).flatten();
+    info.unwrap_or_else(|| {
+        let mut info = Info::default();
+        if !comment.is_empty() {
+            info.raw_map_mut().insert("comment".into(), comment.into());
+        }
+        info
+    })
+}
+// c3e8b416 ends here
+
+// [[file:../extxyz.note::7a0f4be2][7a0f4be2]]
+// Group atoms by consecutive element symbols, for species and counts
+// lines in POSCAR. Atomic numbers are converted into element symbols.
+fn species_groups(frame: &Frame) -> Result<Vec<(String, usize)>> {
+    let mut groups: Vec<(String, usize)> = vec![];
+    for s in &frame.symbols {
+        let s = match s.parse::<i64>() {
+            Ok(z) => element_symbol(z).with_context(|| format!("invalid atomic number: {z}"))?,
+            Err(_) => s.as_str(),
+        };
+        ensure!(!s.is_empty() && !s.contains(char::is_whitespace), "invalid element symbol: {s:?}");
+        match groups.last_mut() {
+            Some((last, n)) if last == s => *n += 1,
+            _ => groups.push((s.to_string(), 1)),
+        }
+    }
+    Ok(groups)
+}
+
+fn format_header(comment: &str, lattice: [[f64; 3]; 3], groups: &[(String, usize)]) -> String {
+    let mut lines = vec![comment.to_string(), "   1.0".to_string()];
+    for [x, y, z] in lattice {
+        lines.push(format!("  {x:21.16} {y:21.16} {z:21.16}"));
+    }
+    lines.push(groups.iter().map(|(s, _)| format!("{s:>5}")).collect());
+    lines.push(groups.iter().map(|(_, n)| format!("{n:>5}")).collect());
+    lines.join("\n") + "\n"
+}
+
+fn format_direct(frame: &Frame, lattice: &Lattice, flags: Option<&[bool]>) -> String {
+    let mut s = String::new();
+    for (i, &position) in frame.positions.iter().enumerate() {
+        let [x, y, z] = lattice.to_frac(position);
+        s.push_str(&format!("  {x:20.16} {y:20.16} {z:20.16}"));
+        if let Some(flags) = flags {
+            for &f in &flags[3 * i..3 * i + 3] {
+                s.push_str(if f { " T" } else { " F" });
+            }
+        }
+        s.push('\n');
+    }
+    s
+}
+
+// The comment line formatted from `info` of `frame`, which is `default`
+// if `info` is empty. This is the reverse of `comment_info`.
+pub(crate) fn comment_line(frame: &Frame, default: &str) -> String {
+    let map = frame.info.raw_map();
+    let line = match map.get("comment").and_then(|v| v.as_str()) {
+        Some(comment) if map.len() == 1 => comment.to_string(),
+        _ if map.is_empty() => default.to_string(),
+        _ => frame.info.format_comment_line(),
+    };
+    line.replace(['\n', '\r'], " ")
+}
+// 7a0f4be2 ends here
+
+// [[file:../extxyz.note::e6b3d058][e6b3d058]]
+impl Frame {
+    /// Parse `Frame` from text `s` in VASP POSCAR/CONTCAR format.
+    ///
+    /// The comment line is parsed as key-value pairs if in extxyz format,
+    /// and kept as `comment` otherwise. The selective dynamics flags are
+    /// mapped to per-atom property `move_mask` (L:3), and velocities in
+    /// CONTCAR to `velocities` (R:3, in Å/fs). Species names are taken
+    /// from the comment line for POSCAR in VASP 4 format.
+    pub fn from_poscar(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let mut header = parse_header(&mut lines)?;
+        let mut line = next_line(&mut lines)?;
+        let selective = line.trim_start().starts_with(['S', 's']);
+        if selective {
+            line = next_line(&mut lines)?;
+        }
+        let (positions, flags) = parse_coordinates(&mut lines, &header, is_cartesian(line), selective)?;
+
+        let mut arrays = crate::Columns::new();
+        if selective {
+            arrays.insert("move_mask".into(), Column::LogicalN(3, flags));
+        }
+        // optional velocities after a blank line, with an optional line
+        // for coordinates mode. Other trailing text is ignored.
+        let mut lines = lines.skip_while(|line| line.trim().is_empty()).peekable();
+        let mode = lines.next_if(|line| is_mode_line(line));
+        if mode.is_some() || lines.peek().is_some_and(|line| parse_vector(line).is_ok()) {
+            // velocities are not scaled
+            header.scale = [1.0; 3];
+            let cartesian = mode.is_none_or(is_cartesian);
+            let (velocities, _) = parse_coordinates(&mut lines, &header, cartesian, false).context("invalid velocities")?;
+            arrays.insert("velocities".into(), Column::Real3(velocities));
+        }
+        Ok(Self::from_header(header, positions, arrays))
+    }
+
+    fn from_header(header: Header, positions: Vec<[f64; 3]>, arrays: crate::Columns) -> Self {
+        Self {
+            natoms: positions.len(),
+            symbols: header.symbols(),
+            positions,
+            lattice: Some(header.lattice),
+            pbc: Some([true; 3]),
+            info: comment_info(&header.comment),
+            arrays,
+        }
+    }
+
+    /// Format `Frame` as text in VASP POSCAR format, with positions in
+    /// direct coordinates. Atoms are kept in order, so the same element
+    /// could appear in more than one group of species.
+    ///
+    /// The `info` is formatted in comment line in extxyz format, or as it
+    /// is if there is only `comment`. The per-atom properties `move_mask`
+    /// (L:1 or L:3) and `velocities` (R:3) are written as selective
+    /// dynamics flags and velocities, and other properties are ignored.
+    pub fn to_poscar(&self) -> Result<String> {
+        ensure!(self.positions.len() == self.symbols.len(), "inconsistent number of positions");
+        let lattice = self.cell().context("POSCAR requires lattice")?;
+        let groups = species_groups(self)?;
+        let species: Vec<_> = groups.iter().map(|(s, _)| s.as_str()).collect();
+        let mut s = format_header(&comment_line(self, &species.join(" ")), lattice.matrix(), &groups);
+
+        let flags = match self.arrays.get("move_mask") {
+            Some(Column::LogicalN(3, flags)) => Some(flags.clone()),
+            Some(Column::Logical(flags)) => Some(flags.iter().flat_map(|&f| [f; 3]).collect()),
+            Some(_) => bail!("invalid type of move_mask for selective dynamics"),
+            None => None,
+        };
+        ensure!(flags.as_ref().is_none_or(|f| f.len() == 3 * self.symbols.len()), "inconsistent number of atoms in move_mask");
+        if flags.is_some() {
+            s.push_str("Selective dynamics\n");
+        }
+        s.push_str("Direct\n");
+        s.push_str(&format_direct(self, &lattice, flags.as_deref()));
+
+        if let Some(column) = self.arrays.get("velocities") {
+            let velocities = column.as_real3().context("invalid type of velocities")?;
+            ensure!(velocities.len() == self.symbols.len(), "inconsistent number of atoms in velocities");
+            s.push('\n');
+            for [x, y, z] in velocities {
+                s.push_str(&format!("  {x:20.16} {y:20.16} {z:20.16}\n"));
+            }
+        }
+        Ok(s)
+    }
+}
+// e6b3d058 ends here
+
+// [[file:../extxyz.note::94f1c2a7][94f1c2a7]]
+// The line before coordinates of each frame in XDATCAR
+fn is_configuration_line(line: &str) -> bool {
+    let line = line.trim_start().to_lowercase();
+    line.starts_with("direct configuration") || line.starts_with("cartesian configuration")
+}
+
+/// Parse all frames in `s` in VASP XDATCAR format, for both fixed and
+/// variable cell. The system name in header is kept as `comment`, and
+/// the configuration number as `configuration` in `info`.
+pub fn parse_xdatcar(s: &str) -> Result<Vec<Frame>> {
+    let mut lines = s.lines().peekable();
+    let mut header = None;
+    let mut frames = vec![];
+    while let Some(&line) = lines.peek() {
+        if line.trim().is_empty() {
+            let _ = lines.next();
+            continue;
+        }
+        let i = frames.len();
+        // the header is repeated for variable cell
+        if !is_configuration_line(line) {
+            header = Some(parse_header(&mut lines).with_context(|| format!("invalid header for frame {i}"))?);
+        }
+        let header = header.as_ref().context("missing header in XDATCAR")?;
+        let line = next_line(&mut lines)?;
+        ensure!(is_configuration_line(line), "invalid configuration line for frame {i}: {line:?}");
+        let (positions, _) =
+            parse_coordinates(&mut lines, header, is_cartesian(line), false).with_context(|| format!("invalid coordinates for frame {i}"))?;
+
+        let mut info = Info::default();
+        if !header.comment.is_empty() {
+            info.raw_map_mut().insert("comment".into(), header.comment.as_str().into());
+        }
+        if let Some(n) = line.split('=').nth(1).and_then(|s| s.trim().parse::<i64>().ok()) {
+            info.raw_map_mut().insert("configuration".into(), n.into());
+        }
+        frames.push(Frame {
+            natoms: positions.len(),
+            symbols: header.symbols(),
+            positions,
+            lattice: Some(header.lattice),
+            pbc: Some([true; 3]),
+            info,
+            arrays: Default::default(),
+        });
+    }
+    Ok(frames)
+}
+
+/// Format `frames` as trajectory in VASP XDATCAR format, with positions
+/// in direct coordinates. All frames should have the same elements in
+/// the same order. The header is written once if all frames have the
+/// same lattice, and repeated for each frame otherwise.
+///
+/// The system name is taken from `comment` in `info` of the first frame,
+/// and the configuration numbers from `configuration`, counting from 1
+/// by default. Other data in frames are ignored.
+pub fn format_xdatcar<F: Borrow<Frame>>(frames: impl IntoIterator<Item = F>) -> Result<String> {
+    let frames: Vec<F> = frames.into_iter().collect();
+    let frames: Vec<&Frame> = frames.iter().map(|f| f.borrow()).collect();
+    let first = frames.first().context("no frame for XDATCAR")?;
+    let groups = species_groups(first)?;
+    let comment = match first.info.get("comment").and_then(|v| v.as_str()) {
+        Some(comment) => comment.replace(['\n', '\r'], " "),
+        None => groups.iter().map(|(s, _)| s.as_str()).collect::<Vec<_>>().join(" "),
+    };
+    let fixed_cell = frames.iter().all(|f| f.lattice == first.lattice);
+
+    let mut s = String::new();
+    for (i, frame) in frames.iter().enumerate() {
+        ensure!(frame.positions.len() == frame.symbols.len(), "inconsistent number of positions in frame {i}");
+        ensure!(frame.symbols == first.symbols, "inconsistent elements in frame {i}");
+        let lattice = frame.cell().with_context(|| format!("XDATCAR requires lattice in frame {i}"))?;
+        if i == 0 || !fixed_cell {
+            s.push_str(&format_header(&comment, lattice.matrix(), &groups));
+        }
+        let n = frame.info.get("configuration").and_then(|v| v.as_i64()).unwrap_or(i as i64 + 1);
+        s.push_str(&format!("Direct configuration= {n:>5}\n"));
+        s.push_str(&format_direct(frame, &lattice, None));
+    }
+    Ok(s)
+}
+
+/// Read `Frame` from file in `path` in VASP POSCAR/CONTCAR format.
+pub fn read_poscar(path: impl AsRef<Path>) -> Result<Frame> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("Failed to read file {path:?}"))?;
+    Frame::from_poscar(&s)
+}
+
+/// Write `frame` into file in `path` in VASP POSCAR format.
+pub fn write_poscar(path: impl AsRef<Path>, frame: &Frame) -> Result<()> {
+    let path = path.as_ref();
+    let s = frame.to_poscar()?;
+    std::fs::write(path, s).with_context(|| format!("Failed to write file {path:?}"))?;
+    Ok(())
+}
+
+/// Read all frames from file in `path` in VASP XDATCAR format.
+pub fn read_xdatcar(path: impl AsRef<Path>) -> Result<Vec<Frame>> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).with_context(|| format!("Failed to read file {path:?}"))?;
+    parse_xdatcar(&s)
+}
+
+/// Write `frames` into file in `path` in VASP XDATCAR format.
+pub fn write_xdatcar<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let path = path.as_ref();
+    let s = format_xdatcar(frames)?;
+    std::fs::write(path, s).with_context(|| format!("Failed to write file {path:?}"))?;
+    Ok(())
+}
+// 94f1c2a7 ends here
+
+// [[file:../extxyz.note::b28d7e05][b28d7e05]]
+#[test]
+fn test_vasp() -> Result<()> {
+    let poscar = "Si O  energy=-1.5
+  -40.0
+     2.0   0.0   0.0
+     0.0   4.0   0.0
+     0.0   0.0   5.0
+   Si_pv   O
+   1   2
+Selective dynamics
+Cartesian
+  0.0  0.0  0.0  F F F
+  0.5  1.0  1.5  T T F
+  1.0  2.0  2.5  T T T
+
+  0.1  0.2  0.3
+  0.0  0.0  0.0
+  0.0  0.0 -0.3
+";
+    let frame = Frame::from_poscar(poscar)?;
+    assert_eq!(frame.symbols, ["Si", "O", "O"]);
+    // scaled by volume
+    assert_eq!(frame.lattice, Some([[2.0, 0.0, 0.0], [0.0, 4.0, 0.0], [0.0, 0.0, 5.0]]));
+    assert_eq!(frame.positions[2], [1.0, 2.0, 2.5]);
+    assert_eq!(frame.info.get("energy"), Some(&(-1.5).into()));
+    assert_eq!(frame.info.raw_map().len(), 1);
+    let mask = frame.arrays["move_mask"].clone();
+    assert_eq!(mask, Column::LogicalN(3, vec![false, false, false, true, true, false, true, true, true]));
+    assert_eq!(frame.arrays["velocities"].as_real3().unwrap()[2], [0.0, 0.0, -0.3]);
+
+    // VASP 4 format with species in comment line
+    let frame_ = Frame::from_poscar("Cu\n3.6\n0 0.5 0.5\n0.5 0 0.5\n0.5 0.5 0\n1\nDirect\n0 0 0\n")?;
+    assert_eq!(frame_.symbols, ["Cu"]);
+    assert_eq!(frame_.lattice.unwrap()[0], [0.0, 1.8, 1.8]);
+    assert!(Frame::from_poscar("comment\n1.0\n1 0 0\n0 1 0\n0 0 1\n1\nDirect\n0 0 0\n").is_err());
+    // trailing text other than velocities
+    let frame_ = Frame::from_poscar("Cu\n3.6\n0 0.5 0.5\n0.5 0 0.5\n0.5 0.5 0\n1\nDirect\n0 0 0\n\nrelaxed by hand\n")?;
+    assert!(frame_.arrays.is_empty());
+    let frame_ = Frame::from_poscar("Cu\n3.6\n0 0.5 0.5\n0.5 0 0.5\n0.5 0.5 0\n1\nDirect\n0 0 0\nDirect\n0.1 0 0\n")?;
+    assert!((frame_.arrays["velocities"].as_real3().unwrap()[0][1] - 0.18).abs() < 1e-8);
+
+    // round trip in direct coordinates
+    let frame_ = Frame::from_poscar(&frame.to_poscar()?)?;
+    assert_eq!(frame_.symbols, frame.symbols);
+    assert_eq!(frame_.info, frame.info);
+    assert_eq!(frame_.arrays, frame.arrays);
+    for (a, b) in frame_.positions.iter().zip(&frame.positions) {
+        assert!((0..3).all(|k| (a[k] - b[k]).abs() < 1e-12));
+    }
+    // extxyz frame without lattice
+    let frame = Frame::parse("1\nenergy=1\nH 0 0 0")?;
+    assert!(frame.to_poscar().is_err());
+
+    // XDATCAR with fixed cell
+    let xdatcar = "Si2
+   1.000
+     5.0   0.0   0.0
+     0.0   5.0   0.0
+     0.0   0.0   5.0
+   Si
+   2
+Direct configuration=     1
+  0.0  0.0  0.0
+  0.5  0.5  0.5
+Direct configuration=     2
+  0.1  0.0  0.0
+  0.5  0.5  0.5
+";
+    let frames = parse_xdatcar(xdatcar)?;
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].positions[0], [0.5, 0.0, 0.0]);
+    assert_eq!(frames[1].info.get("configuration"), Some(&2.into()));
+    let s = format_xdatcar(&frames)?;
+    assert_eq!(s.matches("Direct configuration").count(), 2);
+    assert_eq!(s.matches("Si2").count(), 1);
+    assert_eq!(parse_xdatcar(&s)?, frames);
+
+    // variable cell with repeated header
+    let mut frames = frames;
+    frames[1].lattice = Some([[5.5, 0.0, 0.0], [0.0, 5.0, 0.0], [0.0, 0.0, 5.0]]);
+    let s = format_xdatcar(&frames)?;
+    assert_eq!(s.matches("Si2").count(), 2);
+    let frames_ = parse_xdatcar(&s)?;
+    assert_eq!(frames_[1].lattice, frames[1].lattice);
+    assert!((frames_[1].positions[0][0] - 0.5).abs() < 1e-12);
+
+    Ok(())
+}
+// b28d7e05 ends here
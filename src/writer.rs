@@ -0,0 +1,367 @@
+// [[file:../extxyz.note::2dee6459][2dee6459]]
+use std::borrow::{Borrow, Cow};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::*;
+use serde_json::{Map, Value};
+
+use crate::parser::extxyz::PropertyValueType;
+use crate::{Column, Frame, Info};
+// 2dee6459 ends here
+
+// [[file:../extxyz.note::3e3dc14c][3e3dc14c]]
+fn is_bare_string(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// string values in atom lines are separated by whitespace only, such
+// as `C.3` for atom type
+fn is_column_string(s: &str) -> bool {
+    !s.is_empty() && !s.contains(char::is_whitespace)
+}
+
+// quote `s` with backslash escapes
+fn quote(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+fn format_key(key: &str) -> String {
+    if is_bare_string(key) {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+pub(crate) fn format_real(x: f64) -> String {
+    // the shortest representation that could be parsed back exactly
+    format!("{x:?}")
+}
+
+fn format_number(n: &serde_json::Number) -> String {
+    match n.as_f64() {
+        Some(x) if n.is_f64() => format_real(x),
+        _ => n.to_string(),
+    }
+}
+
+fn format_bool(b: bool) -> &'static str {
+    if b {
+        "T"
+    } else {
+        "F"
+    }
+}
+
+// Format string `s` as is if possible, or quoted, or in ASE style JSON
+// if it looks like other types, such as "T" or "1.0".
+fn format_string(s: &str) -> String {
+    use crate::parser::extxyz::{is_bare_char, parse_plain_value, parse_quoted_value};
+
+    if !s.is_empty() && s.chars().all(is_bare_char) && parse_plain_value(s).is_string() {
+        s.to_string()
+    } else if parse_quoted_value(s).as_str() == Some(s) {
+        quote(s)
+    } else {
+        format_json(&Value::from(s))
+    }
+}
+
+// ASE style JSON, which could represent any value
+fn format_json(value: &Value) -> String {
+    quote(&format!("_JSON {value}"))
+}
+
+// Check if items in 1-D or 2-D `array` could be written in new style as
+// compact JSON, which requires the same type in all items.
+fn is_plain_array(array: &[Value]) -> bool {
+    let scalar = |x: &Value| match x {
+        // escapes other than `\n`, `\"` and `\\` are not supported in extxyz
+        Value::String(s) => !s.chars().any(|c| c.is_control() && c != '\n'),
+        Value::Array(_) | Value::Object(_) | Value::Null => false,
+        _ => true,
+    };
+    let same_kind = |values: &[&Value]| {
+        let all = |f: fn(&Value) -> bool| values.iter().all(|x| f(x));
+        all(Value::is_boolean) || all(Value::is_string) || all(|x| x.is_i64() || x.is_u64()) || all(Value::is_f64)
+    };
+    match array.first() {
+        Some(Value::Array(row)) => {
+            let n = row.len();
+            n > 0
+                && array.iter().all(|x| x.as_array().is_some_and(|row| row.len() == n && row.iter().all(scalar)))
+                && same_kind(&array.iter().flat_map(|x| x.as_array().unwrap()).collect::<Vec<_>>())
+        }
+        _ => array.iter().all(scalar) && same_kind(&array.iter().collect::<Vec<_>>()),
+    }
+}
+
+// Format `value` for extxyz comment line, according to its type
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => format_bool(*b).to_string(),
+        Value::Number(n) => format_number(n),
+        Value::String(s) => format_string(s),
+        // old style 1-D array of numbers or logicals: "1.0 2.0 3.0" or "T T T"
+        Value::Array(array) if array.len() > 1 && is_plain_array(array) && array.iter().all(|x| x.is_number() || x.is_boolean()) => {
+            let items: Vec<_> = array.iter().map(format_value).collect();
+            format!("\"{}\"", items.join(" "))
+        }
+        // new style array in compact JSON: [[1,2],[3,4]] or ["a","b"]
+        Value::Array(array) if is_plain_array(array) => value.to_string(),
+        // objects, null, or arrays of mixed types
+        _ => format_json(value),
+    }
+}
+
+fn format_column_value(name: &str, t: PropertyValueType, value: &Value) -> Result<String> {
+    use PropertyValueType::*;
+
+    let s = match (t, value) {
+        (Real, Value::Number(n)) => format_real(n.as_f64().unwrap()),
+        (Integer, Value::Number(n)) if !n.is_f64() => n.to_string(),
+        (Logical, Value::Bool(b)) => format_bool(*b).to_string(),
+        (String, Value::String(s)) if is_column_string(s) => s.to_string(),
+        _ => bail!("invalid value for property {name:?} of type {t:?}: {value}"),
+    };
+    Ok(s)
+}
+
+impl Info {
+    /// Format key-value pairs as extxyz comment line. Strings and
+    /// arrays will be quoted when necessary.
+    pub fn format_comment_line(&self) -> String {
+        let items: Vec<_> = self
+            .raw_map()
+            .iter()
+            .map(|(k, v)| format!("{}={}", format_key(k), format_value(v)))
+            .collect();
+        items.join(" ")
+    }
+
+    /// Format per-atom `properties` as extra columns in atom line,
+    /// according to the `Properties` entry. This is the reverse of
+    /// `parse_extra_columns`.
+    pub fn format_extra_columns(&self, properties: &Map<String, Value>) -> Result<String> {
+        let mut columns = vec![];
+        for col in self.properties()? {
+            // element and positions columns are formatted elsewhere
+            if col.name == "species" || col.name == "pos" {
+                continue;
+            }
+            let value = properties.get(&col.name).with_context(|| format!("missing property: {}", col.name))?;
+            match value {
+                Value::Array(values) if col.num_columns > 1 => {
+                    ensure!(values.len() == col.num_columns, "expect {} columns for property {}", col.num_columns, col.name);
+                    for v in values {
+                        columns.push(format_column_value(&col.name, col.r#type, v)?);
+                    }
+                }
+                _ if col.num_columns == 1 => columns.push(format_column_value(&col.name, col.r#type, value)?),
+                _ => bail!("expect {} columns for property {}", col.num_columns, col.name),
+            }
+        }
+        Ok(columns.join(" "))
+    }
+}
+// 3e3dc14c ends here
+
+// [[file:../extxyz.note::fbfda718][fbfda718]]
+// Atom lines are always written with element symbol and positions in
+// leading columns, so `Properties` in `info` is reordered if necessary.
+fn leading_species_pos(info: &Info) -> Result<Cow<'_, Info>> {
+    let properties = info.properties()?;
+    let names: Vec<_> = properties.iter().map(|p| p.to_string()).collect();
+    if names.starts_with(&["species:S:1".into(), "pos:R:3".into()]) {
+        return Ok(Cow::Borrowed(info));
+    }
+    let mut items = vec!["species:S:1:pos:R:3".to_string()];
+    items.extend(properties.iter().filter(|p| p.name != "species" && p.name != "pos").map(|p| p.to_string()));
+    let mut info = info.clone();
+    info.raw_map_mut().insert("Properties".into(), items.join(":").into());
+    Ok(Cow::Owned(info))
+}
+
+/// Format a complete frame in extxyz format, using key-value pairs in
+/// `info` as comment line. Each atom is represented as its element
+/// symbol, Cartesian coordinates and extra properties defined in
+/// `Properties` entry of `info`, which will be reordered to put
+/// `species` and `pos` first if needed.
+pub fn format_extxyz_frame<'a>(info: &Info, atoms: impl IntoIterator<Item = (&'a str, [f64; 3], &'a Map<String, Value>)>) -> Result<String> {
+    let info = leading_species_pos(info)?;
+    let mut lines = vec![];
+    for (symbol, position, properties) in atoms {
+        let extra = info.format_extra_columns(properties)?;
+        lines.push(format_atom_line(symbol, position, &extra));
+    }
+
+    let mut s = format!("{}\n{}\n", lines.len(), info.format_comment_line());
+    for line in lines {
+        s.push_str(&line);
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+fn format_atom_line(symbol: &str, [x, y, z]: [f64; 3], extra: &str) -> String {
+    let line = format!("{symbol:<3} {:>16} {:>16} {:>16}", format_real(x), format_real(y), format_real(z));
+    if extra.is_empty() {
+        line
+    } else {
+        format!("{line} {extra}")
+    }
+}
+
+pub(crate) fn format_column_row(column: &Column, i: usize) -> Vec<String> {
+    let w = column.width();
+    match column {
+        Column::Real(v) => vec![format_real(v[i])],
+        Column::Real3(v) => v[i].iter().map(|x| format_real(*x)).collect(),
+        Column::Integer(v) => vec![v[i].to_string()],
+        Column::Logical(v) => vec![format_bool(v[i]).into()],
+        Column::String(v) => vec![v[i].clone()],
+        Column::RealN(_, v) => v[i * w..(i + 1) * w].iter().map(|x| format_real(*x)).collect(),
+        Column::IntegerN(_, v) => v[i * w..(i + 1) * w].iter().map(|x| x.to_string()).collect(),
+        Column::LogicalN(_, v) => v[i * w..(i + 1) * w].iter().map(|x| format_bool(*x).into()).collect(),
+        Column::StringN(_, v) => v[i * w..(i + 1) * w].to_vec(),
+    }
+}
+
+impl Frame {
+    /// Format `Frame` as text in extxyz format. The `Lattice`, `pbc` and
+    /// `Properties` entries are generated from the frame data.
+    pub fn to_extxyz(&self) -> Result<String> {
+        let natoms = self.symbols.len();
+        ensure!(self.positions.len() == natoms, "inconsistent number of positions");
+
+        let mut properties = vec!["species:S:1:pos:R:3".to_string()];
+        for (name, column) in &self.arrays {
+            ensure!(column.len() == natoms, "inconsistent number of atoms in property {name:?}");
+            for s in column.iter_strings() {
+                ensure!(is_column_string(s), "invalid value for property {name:?}: {s:?}");
+            }
+            properties.push(column.property(name).to_string());
+        }
+
+        let mut info = self.info.clone();
+        let map = info.raw_map_mut();
+        if let Some(lattice) = self.lattice {
+            map.insert("Lattice".into(), lattice.iter().flatten().copied().collect());
+        }
+        if let Some(pbc) = self.pbc {
+            map.insert("pbc".into(), pbc.iter().copied().collect());
+        }
+        map.insert("Properties".into(), properties.join(":").into());
+
+        let mut s = format!("{natoms}\n{}\n", info.format_comment_line());
+        for (i, (symbol, position)) in self.symbols.iter().zip(&self.positions).enumerate() {
+            let extra: Vec<_> = self.arrays.values().flat_map(|column| format_column_row(column, i)).collect();
+            s.push_str(&format_atom_line(symbol, *position, &extra.join(" ")));
+            s.push('\n');
+        }
+        Ok(s)
+    }
+}
+
+/// Write `frames` into file in `path` in extxyz format.
+pub fn write_xyz_frames<F: Borrow<Frame>>(path: impl AsRef<Path>, frames: impl IntoIterator<Item = F>) -> Result<()> {
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).with_context(|| format!("Failed to create file {path:?}"))?;
+    let mut writer = std::io::BufWriter::new(f);
+    for frame in frames {
+        writer.write_all(frame.borrow().to_extxyz()?.as_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+// fbfda718 ends here
+
+// [[file:../extxyz.note::51a61b7d][51a61b7d]]
+#[test]
+fn test_format_value() -> Result<()> {
+    use serde_json::json;
+
+    // Check if `value` could be parsed back as is from comment line.
+    let round_trips = |repr: &str, value: &Value| {
+        let s = format!("x={repr}");
+        s.parse::<Info>().map(|info| info.get("x") == Some(value)).unwrap_or(false)
+    };
+
+    let values = [
+        json!(1),
+        json!(-1.0),
+        json!(1e-7),
+        json!(true),
+        json!("bulk"),
+        json!("two words"),
+        json!([1.0, 2.0, 3.5]),
+        json!([1, 2, 3]),
+        json!([true, false, true]),
+        json!([[1, 2], [3, 4]]),
+        json!(["a", "b"]),
+        json!([1, 2.5]),
+        json!("with \"quotes\" and \\"),
+        json!("line\nbreak"),
+        json!({"a": [1, 2], "b": "c d"}),
+        json!("12"),
+        json!("_JSON 1"),
+        json!([1.5]),
+        json!(["tab\tchar"]),
+        json!([[true], [false]]),
+        json!(null),
+    ];
+    for v in values {
+        let s = format_value(&v);
+        assert!(round_trips(&s, &v), "{s}");
+    }
+    assert_eq!(format_value(&json!([true, true, false])), "\"T T F\"");
+
+    // string looks like logical is kept in ASE style JSON
+    assert_eq!(format_value(&json!("T")), r#""_JSON \"T\"""#);
+    assert_eq!(format_key("a key"), "\"a key\"");
+
+    Ok(())
+}
+
+#[test]
+fn test_format_frame() -> Result<()> {
+    let input = r#"2
+Lattice="5.44 0.0 0.0 0.0 5.44 0.0 0.0 0.0 5.44" Properties=species:S:1:pos:R:3:forces:R:3:freeze:L:1:tag:I:1 energy=-1.5 pbc="T T F" config_type=bulk
+Si 0.0 0.0 0.0 0.1 0.2 0.3 T 1
+Si 1.36 1.36 1.36 -0.1 -0.2 -0.3 F 2
+"#;
+    let frame = Frame::parse(input)?;
+    let s = frame.to_extxyz()?;
+    let frame_ = Frame::parse(&s)?;
+    assert_eq!(frame, frame_);
+
+    // species and pos are moved to leading columns
+    let input = "1\nProperties=tag:I:1:pos:R:3:species:S:1\n7 0.0 0.0 1.0 H\n";
+    let atoms = crate::RawAtoms::parse_from(input)?;
+    let info: Info = atoms.comment.parse()?;
+    let properties: Vec<_> = atoms.atoms.iter().map(|a| info.parse_extra_columns(a.extra)).collect::<Result<_>>()?;
+    let s = format_extxyz_frame(&info, atoms.atoms.iter().zip(&properties).map(|(a, m)| (a.element, a.position, m)))?;
+    assert!(s.contains("Properties=species:S:1:pos:R:3:tag:I:1"), "{s}");
+    assert_eq!(Frame::parse(&s)?, Frame::parse(input)?);
+
+    // long labels and long reals are always separated
+    let input = "1\nProperties=species:S:1:pos:R:3\nUuo -0.30000000000000004 0.0 0.0\n";
+    let frame = Frame::parse(input)?;
+    let s = frame.to_extxyz()?;
+    assert!(s.contains("Uuo -0.30000000000000004"), "{s}");
+    assert_eq!(Frame::parse(&s)?, frame);
+
+    // string values other than bare strings, and columns in declared order
+    let input = "1\nProperties=species:S:1:pos:R:3:q:R:1:label:S:1\nC 0.0 0.0 0.0 0.5 C.3\n";
+    let mut frame = Frame::parse(input)?;
+    let s = frame.to_extxyz()?;
+    assert!(s.contains("Properties=species:S:1:pos:R:3:q:R:1:label:S:1"), "{s}");
+    assert_eq!(Frame::parse(&s)?, frame);
+    frame.arrays.insert("label".into(), Column::String(vec!["C 3".into()]));
+    assert!(frame.to_extxyz().is_err());
+
+    Ok(())
+}
+// 51a61b7d ends here
@@ -28,7 +28,7 @@ fn test_extxyz() -> anyhow::Result<()> {
     // get atom's properties
     for atom in atoms.atoms {
         // parse extra data for each atom
-        let atom_properties = info.parse_extra_columns(&atom.extra)?;
+        let atom_properties = info.parse_extra_columns(atom.extra)?;
         // get `forces` component for each atom
         let forces = &atom_properties["forces"];
         assert!(forces[0].is_f64());
@@ -39,3 +39,56 @@ fn test_extxyz() -> anyhow::Result<()> {
     Ok(())
 }
 // c53397f5 ends here
+
+// [[file:../extxyz.note::f8cb7598][f8cb7598]]
+#[test]
+fn test_extxyz_frame() -> anyhow::Result<()> {
+    use extxyz::Frame;
+
+    let f = "tests/files/cu.xyz";
+    let frames: Vec<Frame> = read_xyz_frames(f, 0..)?.map(|s| Frame::parse(&s)).collect::<anyhow::Result<_>>()?;
+    assert_eq!(frames.len(), 1);
+    let frame = &frames[0];
+    assert_eq!(frame.natoms, 107);
+    assert_eq!(frame.symbols.len(), 107);
+    assert_eq!(frame.lattice.unwrap()[2][2], 10.83);
+    assert_eq!(frame.pbc, Some([true; 3]));
+    assert_eq!(frame.info.get("energy").unwrap(), 0.63);
+    assert_eq!(frame.arrays["forces"].as_real3().unwrap().len(), 107);
+    assert_eq!(frame.arrays["energy"].as_real().unwrap().len(), 107);
+
+    Ok(())
+}
+// f8cb7598 ends here
+
+// [[file:../extxyz.note::302e1806][302e1806]]
+#[test]
+fn test_extxyz_write() -> anyhow::Result<()> {
+    use extxyz::{format_extxyz_frame, write_xyz_frames, Frame};
+
+    let f = "tests/files/cu.xyz";
+    let frame = read_xyz_frames(f, 0..)?.next().unwrap();
+    let atoms = RawAtoms::parse_from(&frame)?;
+    let info: Info = atoms.comment.parse()?;
+    let properties: Vec<_> = atoms.atoms.iter().map(|a| info.parse_extra_columns(a.extra)).collect::<anyhow::Result<_>>()?;
+
+    // parse -> write -> parse
+    let s = format_extxyz_frame(&info, atoms.atoms.iter().zip(&properties).map(|(a, m)| (a.element, a.position, m)))?;
+    let atoms_ = RawAtoms::parse_from(&s)?;
+    let info_: Info = atoms_.comment.parse()?;
+    assert_eq!(info, info_);
+    assert_eq!(atoms_.atoms.len(), atoms.atoms.len());
+    for (a, m) in atoms_.atoms.iter().zip(&properties) {
+        assert_eq!(&info_.parse_extra_columns(a.extra)?, m);
+    }
+
+    // write frames into file
+    let frames: Vec<_> = read_xyz_frames_direct("tests/files/water.xyz")?.map(|s| Frame::parse(&s)).collect::<anyhow::Result<_>>()?;
+    let tmp = std::env::temp_dir().join("extxyz-test-write.xyz");
+    write_xyz_frames(&tmp, &frames)?;
+    let frames_: Vec<_> = read_xyz_frames_direct(&tmp)?.map(|s| Frame::parse(&s)).collect::<anyhow::Result<_>>()?;
+    assert_eq!(frames, frames_);
+
+    Ok(())
+}
+// 302e1806 ends here
@@ -0,0 +1,101 @@
+// [[file:../extxyz.note::7b0e4a52][7b0e4a52]]
+use extxyz::Info;
+use serde_json::json;
+
+fn parse(s: &str) -> Info {
+    s.parse().unwrap_or_else(|e| panic!("{e:?}"))
+}
+// 7b0e4a52 ends here
+
+// [[file:../extxyz.note::b9d3f1c6][b9d3f1c6]]
+#[test]
+fn test_spec_scalars() {
+    let info = parse("i=1 neg=-2 r=1.5 e=1e-3 E=-1.5E+2 d=1.0d2 D=2D-1 s=bare_string");
+    assert_eq!(info.get("i").unwrap(), &json!(1));
+    assert_eq!(info.get("neg").unwrap(), &json!(-2));
+    assert_eq!(info.get("r").unwrap(), &json!(1.5));
+    assert_eq!(info.get("e").unwrap(), &json!(1e-3));
+    assert_eq!(info.get("E").unwrap(), &json!(-150.0));
+    assert_eq!(info.get("d").unwrap(), &json!(100.0));
+    assert_eq!(info.get("D").unwrap(), &json!(0.2));
+    assert_eq!(info.get("s").unwrap(), "bare_string");
+
+    // logicals in all allowed spellings
+    let info = parse("a=T b=F c=True d=False e=true f=false g=TRUE h=FALSE");
+    let values: Vec<_> = info.raw_map().values().cloned().collect();
+    assert_eq!(values, json!([true, false, true, false, true, false, true, false]).as_array().unwrap()[..]);
+}
+
+#[test]
+fn test_spec_strings() {
+    let info = parse(r#"a="quoted string" b="with \"escaped\" quotes" c="back\\slash" d="new\nline" e="""#);
+    assert_eq!(info.get("a").unwrap(), "quoted string");
+    assert_eq!(info.get("b").unwrap(), r#"with "escaped" quotes"#);
+    assert_eq!(info.get("c").unwrap(), r"back\slash");
+    assert_eq!(info.get("d").unwrap(), "new\nline");
+    assert_eq!(info.get("e").unwrap(), "");
+
+    // quoted keys
+    let info = parse(r#""quoted key"=1 "key with \"quote\""=2"#);
+    assert_eq!(info.get("quoted key").unwrap(), 1);
+    assert_eq!(info.get(r#"key with "quote""#).unwrap(), 2);
+}
+
+#[test]
+fn test_spec_arrays() {
+    // new style 1-D arrays
+    let info = parse(r#"i=[1, 2, 3] r=[1.0,2.0] b=[T, F, T] s=["a", "b c"] mixed=[1, 2.5]"#);
+    assert_eq!(info.get("i").unwrap(), &json!([1, 2, 3]));
+    assert_eq!(info.get("r").unwrap(), &json!([1.0, 2.0]));
+    assert_eq!(info.get("b").unwrap(), &json!([true, false, true]));
+    assert_eq!(info.get("s").unwrap(), &json!(["a", "b c"]));
+    // integers are promoted to reals
+    assert_eq!(info.get("mixed").unwrap(), &json!([1.0, 2.5]));
+
+    // new style 2-D arrays
+    let info = parse("Lattice=[[4.0, 0, 0], [0, 4.0, 0], [0, 0, 4.0]] m=[[1, 2], [3, 4]]");
+    assert_eq!(info.get("Lattice").unwrap()[1], json!([0.0, 4.0, 0.0]));
+    assert_eq!(info.get("m").unwrap(), &json!([[1, 2], [3, 4]]));
+
+    // old style 1-D arrays, in quotes or in curly braces
+    let info = parse(r#"Lattice="4.0 0.0 0.0 0.0 4.0 0.0 0.0 0.0 4.0" pbc="T T F" c={1 2 3} r="1 2.5""#);
+    assert_eq!(info.get("Lattice").unwrap().as_array().unwrap().len(), 9);
+    assert_eq!(info.get("pbc").unwrap(), &json!([true, true, false]));
+    assert_eq!(info.get("c").unwrap(), &json!([1, 2, 3]));
+    assert_eq!(info.get("r").unwrap(), &json!([1.0, 2.5]));
+    // not homogeneous: kept as string
+    let info = parse(r#"s="1 2 T""#);
+    assert_eq!(info.get("s").unwrap(), "1 2 T");
+}
+
+#[test]
+fn test_spec_separators() {
+    // whitespace around `=` and between pairs
+    let info = parse("  a = 1\tb= 2  c =3 ");
+    assert_eq!(info.raw_map().len(), 3);
+    assert_eq!(info.get("c").unwrap(), 3);
+
+    // bare keys without value are logical flags
+    let info = parse("Properties=species:S:1:pos:R:3 charge=0 is_true");
+    assert_eq!(info.get("is_true").unwrap(), true);
+    assert_eq!(info.get("charge").unwrap(), 0);
+}
+
+#[test]
+fn test_spec_invalid() {
+    for s in [
+        // mixed types in new style array
+        "a=[1, T]",
+        // 2-D array with rows of different length or type
+        "a=[[1, 2], [3]]",
+        "a=[[1, 2], [T, F]]",
+        // commas are not allowed in bare strings
+        "a=b,c",
+        // unterminated quoted string
+        r#"a="abc"#,
+        "a=",
+    ] {
+        assert!(s.parse::<Info>().is_err(), "{s}");
+    }
+}
+// b9d3f1c6 ends here